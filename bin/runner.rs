@@ -1,4 +1,8 @@
-use risc_v_simulator::{runner::run_simple_simulator, sim::SimulatorConfig};
+use risc_v_simulator::{
+    delegations::DelegationRegistry,
+    runner::run_simple_simulator,
+    sim::{SimulatorConfig, Watchpoint},
+};
 
 pub fn main() {
     // let args: Vec<String> = std::env::args().collect();
@@ -15,6 +19,23 @@ pub fn main() {
 
     let mut config = SimulatorConfig::simple(path);
     config.entry_point = 0;
+
+    // `--watch <read|write|change>:<start>(..<end>|+<len>)[:stop]`, may be repeated.
+    // `--no-delegations` runs without the builtin delegations.
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--watch" => {
+                let spec = args.next().expect("--watch requires a watchpoint");
+                let watchpoint = spec
+                    .parse::<Watchpoint>()
+                    .unwrap_or_else(|e| panic!("{}", e));
+                config.watchpoints.push(watchpoint);
+            }
+            "--no-delegations" => config.delegations = DelegationRegistry::new(),
+            _ => panic!("unknown argument `{}`", arg),
+        }
+    }
     config.diagnostics = Some({
         let mut d = DiagnosticsConfig::new(std::path::PathBuf::from(path_sym));
 
@@ -31,6 +52,10 @@ pub fn main() {
         d
     });
 
-    let output = run_simple_simulator(config);
-    dbg!(output);
+    let outcome = run_simple_simulator(config);
+    if let Some(fault) = outcome.fault {
+        eprintln!("{}", fault);
+        std::process::exit(1);
+    }
+    dbg!(outcome.registers);
 }
@@ -1,3 +1,4 @@
+use super::uninit::UninitRead;
 use crate::cycle::status_registers::TrapReason;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -54,6 +55,17 @@ pub trait MemorySource {
         trap: &mut TrapReason,
     );
     fn get(&self, phys_address: u64, access_type: AccessType, trap: &mut TrapReason) -> u32;
+
+    /// Called by loads, stores and instruction fetches before they touch the memory, with the
+    /// exact (possibly unaligned) bytes they cover.
+    #[inline(always)]
+    fn before_access(&mut self, _phys_address: u64, _num_bytes: u32, _access_type: AccessType) {}
+
+    /// Reads of uninitialized memory since the last call, see `UninitCheckingMemory`.
+    #[inline(always)]
+    fn take_uninit_reads(&mut self) -> Vec<UninitRead> {
+        Vec::new()
+    }
 }
 
 pub struct VectorMemoryImpl {
@@ -97,7 +109,7 @@ impl MemorySource for VectorMemoryImpl {
         } else {
             match access_type {
                 AccessType::Instruction => *trap = TrapReason::InstructionAccessFault,
-                AccessType::MemLoad => *trap = TrapReason::LoadAccessFault,
+                AccessType::MemLoad | AccessType::RegWrite => *trap = TrapReason::LoadAccessFault,
                 AccessType::MemStore => *trap = TrapReason::StoreOrAMOAccessFault,
                 _ => unreachable!(),
             }
@@ -121,7 +133,9 @@ impl MemorySource for VectorMemoryImpl {
             match access_type {
                 AccessType::Instruction => *trap = TrapReason::InstructionAccessFault,
                 AccessType::MemLoad => *trap = TrapReason::LoadAccessFault,
-                AccessType::MemStore => *trap = TrapReason::StoreOrAMOAccessFault,
+                AccessType::MemStore | AccessType::RegWrite => {
+                    *trap = TrapReason::StoreOrAMOAccessFault
+                }
                 _ => unreachable!(),
             }
         }
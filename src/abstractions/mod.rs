@@ -9,6 +9,7 @@ pub mod csr_processor;
 pub mod memory;
 pub mod non_determinism;
 pub mod tracer;
+pub mod uninit;
 
 #[must_use]
 #[inline(always)]
@@ -34,6 +35,7 @@ pub fn mem_read<
     if SUPPORT_LOAD_LESS_THAN_WORD {
         let value = match (unalignment, num_bytes) {
             (0, 4) | (0, 2) | (2, 2) | (0, 1) | (1, 1) | (2, 1) | (3, 1) => {
+                memory_source.before_access(phys_address, num_bytes, access_type);
                 let value = memory_source.get(aligned_address, access_type, trap);
                 if access_type == AccessType::Instruction {
                     tracer.trace_opcode_read(aligned_address, value, proc_cycle, cycle_timestamp);
@@ -64,6 +66,7 @@ pub fn mem_read<
     } else {
         let value = match (unalignment, num_bytes) {
             (0, 4) => {
+                memory_source.before_access(phys_address, num_bytes, access_type);
                 let value = memory_source.get(aligned_address, access_type, trap);
                 if access_type == AccessType::Instruction {
                     tracer.trace_opcode_read(aligned_address, value, proc_cycle, cycle_timestamp);
@@ -112,6 +115,7 @@ pub fn mem_write<
             | a @ (2, 1)
             | a @ (3, 1) => {
                 let (unalignment, num_bytes) = a;
+                memory_source.before_access(phys_address, num_bytes, AccessType::MemStore);
 
                 // we need to load old value - just for easier comparison of simulator/in_circuit implementation
                 let old_value = memory_source.get(aligned_address, AccessType::MemLoad, trap);
@@ -160,6 +164,8 @@ pub fn mem_write<
     } else {
         match (unalignment, num_bytes) {
             _a @ (0, 4) => {
+                memory_source.before_access(phys_address, num_bytes, AccessType::MemStore);
+
                 // we need to load old value - just for easier comparison of simulator/in_circuit implementation
                 let old_value = memory_source.get(aligned_address, AccessType::MemLoad, trap);
                 if trap.is_a_trap() {
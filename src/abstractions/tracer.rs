@@ -1,4 +1,8 @@
-use crate::cycle::{state::RiscV32State, MachineConfig};
+use crate::cycle::status_registers::TrapReason;
+use crate::cycle::{
+    state::{Mode, RiscV32State},
+    MachineConfig,
+};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum BatchAccessPartialData {
@@ -118,6 +122,99 @@ pub trait Tracer<C: MachineConfig> {
         _cycle_timestamp: u32,
     ) {
     }
+
+    /// Further accesses of the delegation last reported by `trace_batch_memory_access`, to the
+    /// words starting at `phys_address` instead of a 64 KiB boundary
+    #[inline(always)]
+    fn trace_batch_memory_access_at(
+        &mut self,
+        _access_id: u32,
+        _phys_address: u64,
+        _accesses: &[BatchAccessPartialData],
+        _proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+    }
+
+    /// Accesses to devices through `MMIOImplementation`
+    #[inline(always)]
+    fn trace_mmio_read(
+        &mut self,
+        _phys_address: u64,
+        _read_value: u32,
+        _proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+    }
+
+    #[inline(always)]
+    fn trace_mmio_write(
+        &mut self,
+        _phys_address: u64,
+        _written_value: u32,
+        _proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+    }
+
+    /// Only covers CSRs implemented by the machine itself (satp and machine trap CSRs)
+    #[inline(always)]
+    fn trace_csr_read(
+        &mut self,
+        _csr_number: u32,
+        _read_value: u32,
+        _proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+    }
+
+    /// Only covers CSRs implemented by the machine itself (satp and machine trap CSRs)
+    #[inline(always)]
+    fn trace_csr_write(
+        &mut self,
+        _csr_number: u32,
+        _read_value: u32,
+        _written_value: u32,
+        _proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+    }
+
+    /// Called for every trap, before it's handled. If the machine doesn't handle exceptions
+    /// it's the last event before the simulator panics
+    #[inline(always)]
+    fn trace_trap(
+        &mut self,
+        _trap: TrapReason,
+        _pc: u32,
+        _instr: u32,
+        _proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+    }
+
+    #[inline(always)]
+    fn trace_privilege_mode_change(
+        &mut self,
+        _previous_mode: Mode,
+        _new_mode: Mode,
+        _proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+    }
+
+    #[inline(always)]
+    fn trace_wait_for_interrupt_entry(
+        &mut self,
+        _pc: u32,
+        _proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+    }
+
+    #[inline(always)]
+    fn trace_wait_for_interrupt_exit(&mut self, _pc: u32, _proc_cycle: u32, _cycle_timestamp: u32) {
+    }
 }
 
 impl<C: MachineConfig> Tracer<C> for () {
@@ -127,3 +224,453 @@ impl<C: MachineConfig> Tracer<C> for () {
         ()
     }
 }
+
+// Forwards every hook of `Tracer` (or `DynTracer`, they share names and signatures) either
+// to a fixed list of targets, or to every element produced by an iterator
+macro_rules! forward_tracer_hooks {
+    (@call [] $trait:ident $method:ident $args:tt) => {};
+    (@call [$target:expr $(, $rest:expr)*] $trait:ident $method:ident ($($arg:ident),*)) => {
+        $trait::<C>::$method($target, $($arg),*);
+        forward_tracer_hooks!(@call [$($rest),*] $trait $method ($($arg),*));
+    };
+    (@call (for $el:ident in $iter:expr) $trait:ident $method:ident ($($arg:ident),*)) => {
+        for $el in $iter {
+            $trait::<C>::$method($el, $($arg),*);
+        }
+    };
+    ($self_:ident, $targets:tt, $trait:ident) => {
+        #[inline(always)]
+        fn at_cycle_start(&mut $self_, current_state: &RiscV32State<C>) {
+            forward_tracer_hooks!(@call $targets $trait at_cycle_start (current_state));
+        }
+
+        #[inline(always)]
+        fn at_cycle_end(&mut $self_, current_state: &RiscV32State<C>) {
+            forward_tracer_hooks!(@call $targets $trait at_cycle_end (current_state));
+        }
+
+        #[inline(always)]
+        fn trace_opcode_read(
+            &mut $self_,
+            phys_address: u64,
+            read_value: u32,
+            proc_cycle: u32,
+            cycle_timestamp: u32,
+        ) {
+            forward_tracer_hooks!(@call $targets $trait trace_opcode_read (phys_address, read_value, proc_cycle, cycle_timestamp));
+        }
+
+        #[inline(always)]
+        fn trace_rs1_read(
+            &mut $self_,
+            reg_idx: u32,
+            read_value: u32,
+            proc_cycle: u32,
+            cycle_timestamp: u32,
+        ) {
+            forward_tracer_hooks!(@call $targets $trait trace_rs1_read (reg_idx, read_value, proc_cycle, cycle_timestamp));
+        }
+
+        #[inline(always)]
+        fn trace_rs2_read(
+            &mut $self_,
+            reg_idx: u32,
+            read_value: u32,
+            proc_cycle: u32,
+            cycle_timestamp: u32,
+        ) {
+            forward_tracer_hooks!(@call $targets $trait trace_rs2_read (reg_idx, read_value, proc_cycle, cycle_timestamp));
+        }
+
+        #[inline(always)]
+        fn trace_rd_write(
+            &mut $self_,
+            reg_idx: u32,
+            read_value: u32,
+            written_value: u32,
+            proc_cycle: u32,
+            cycle_timestamp: u32,
+        ) {
+            forward_tracer_hooks!(@call $targets $trait trace_rd_write (reg_idx, read_value, written_value, proc_cycle, cycle_timestamp));
+        }
+
+        #[inline(always)]
+        fn trace_non_determinism_read(
+            &mut $self_,
+            read_value: u32,
+            proc_cycle: u32,
+            cycle_timestamp: u32,
+        ) {
+            forward_tracer_hooks!(@call $targets $trait trace_non_determinism_read (read_value, proc_cycle, cycle_timestamp));
+        }
+
+        #[inline(always)]
+        fn trace_non_determinism_write(
+            &mut $self_,
+            written_value: u32,
+            proc_cycle: u32,
+            cycle_timestamp: u32,
+        ) {
+            forward_tracer_hooks!(@call $targets $trait trace_non_determinism_write (written_value, proc_cycle, cycle_timestamp));
+        }
+
+        #[inline(always)]
+        fn trace_ram_read(
+            &mut $self_,
+            phys_address: u64,
+            read_value: u32,
+            proc_cycle: u32,
+            cycle_timestamp: u32,
+        ) {
+            forward_tracer_hooks!(@call $targets $trait trace_ram_read (phys_address, read_value, proc_cycle, cycle_timestamp));
+        }
+
+        #[inline(always)]
+        fn trace_ram_read_write(
+            &mut $self_,
+            phys_address: u64,
+            read_value: u32,
+            written_value: u32,
+            proc_cycle: u32,
+            cycle_timestamp: u32,
+        ) {
+            forward_tracer_hooks!(@call $targets $trait trace_ram_read_write (phys_address, read_value, written_value, proc_cycle, cycle_timestamp));
+        }
+
+        #[inline(always)]
+        fn trace_address_translation(
+            &mut $self_,
+            satp_value: u32,
+            virtual_address: u64,
+            phys_address: u64,
+            proc_cycle: u32,
+            cycle_timestamp: u32,
+        ) {
+            forward_tracer_hooks!(@call $targets $trait trace_address_translation (satp_value, virtual_address, phys_address, proc_cycle, cycle_timestamp));
+        }
+
+        #[inline(always)]
+        fn trace_batch_memory_access(
+            &mut $self_,
+            access_id: u32,
+            phys_address_high: u16,
+            accesses: &[BatchAccessPartialData],
+            proc_cycle: u32,
+            cycle_timestamp: u32,
+        ) {
+            forward_tracer_hooks!(@call $targets $trait trace_batch_memory_access (access_id, phys_address_high, accesses, proc_cycle, cycle_timestamp));
+        }
+
+        #[inline(always)]
+        fn trace_batch_memory_access_at(
+            &mut $self_,
+            access_id: u32,
+            phys_address: u64,
+            accesses: &[BatchAccessPartialData],
+            proc_cycle: u32,
+            cycle_timestamp: u32,
+        ) {
+            forward_tracer_hooks!(@call $targets $trait trace_batch_memory_access_at (access_id, phys_address, accesses, proc_cycle, cycle_timestamp));
+        }
+
+        #[inline(always)]
+        fn trace_mmio_read(
+            &mut $self_,
+            phys_address: u64,
+            read_value: u32,
+            proc_cycle: u32,
+            cycle_timestamp: u32,
+        ) {
+            forward_tracer_hooks!(@call $targets $trait trace_mmio_read (phys_address, read_value, proc_cycle, cycle_timestamp));
+        }
+
+        #[inline(always)]
+        fn trace_mmio_write(
+            &mut $self_,
+            phys_address: u64,
+            written_value: u32,
+            proc_cycle: u32,
+            cycle_timestamp: u32,
+        ) {
+            forward_tracer_hooks!(@call $targets $trait trace_mmio_write (phys_address, written_value, proc_cycle, cycle_timestamp));
+        }
+
+        #[inline(always)]
+        fn trace_csr_read(
+            &mut $self_,
+            csr_number: u32,
+            read_value: u32,
+            proc_cycle: u32,
+            cycle_timestamp: u32,
+        ) {
+            forward_tracer_hooks!(@call $targets $trait trace_csr_read (csr_number, read_value, proc_cycle, cycle_timestamp));
+        }
+
+        #[inline(always)]
+        fn trace_csr_write(
+            &mut $self_,
+            csr_number: u32,
+            read_value: u32,
+            written_value: u32,
+            proc_cycle: u32,
+            cycle_timestamp: u32,
+        ) {
+            forward_tracer_hooks!(@call $targets $trait trace_csr_write (csr_number, read_value, written_value, proc_cycle, cycle_timestamp));
+        }
+
+        #[inline(always)]
+        fn trace_trap(
+            &mut $self_,
+            trap: TrapReason,
+            pc: u32,
+            instr: u32,
+            proc_cycle: u32,
+            cycle_timestamp: u32,
+        ) {
+            forward_tracer_hooks!(@call $targets $trait trace_trap (trap, pc, instr, proc_cycle, cycle_timestamp));
+        }
+
+        #[inline(always)]
+        fn trace_privilege_mode_change(
+            &mut $self_,
+            previous_mode: Mode,
+            new_mode: Mode,
+            proc_cycle: u32,
+            cycle_timestamp: u32,
+        ) {
+            forward_tracer_hooks!(@call $targets $trait trace_privilege_mode_change (previous_mode, new_mode, proc_cycle, cycle_timestamp));
+        }
+
+        #[inline(always)]
+        fn trace_wait_for_interrupt_entry(&mut $self_, pc: u32, proc_cycle: u32, cycle_timestamp: u32) {
+            forward_tracer_hooks!(@call $targets $trait trace_wait_for_interrupt_entry (pc, proc_cycle, cycle_timestamp));
+        }
+
+        #[inline(always)]
+        fn trace_wait_for_interrupt_exit(&mut $self_, pc: u32, proc_cycle: u32, cycle_timestamp: u32) {
+            forward_tracer_hooks!(@call $targets $trait trace_wait_for_interrupt_exit (pc, proc_cycle, cycle_timestamp));
+        }
+    };
+}
+
+impl<C: MachineConfig, A: Tracer<C>, B: Tracer<C>> Tracer<C> for (A, B) {
+    type AuxData = (A::AuxData, B::AuxData);
+
+    fn create_from_initial_state(state: &RiscV32State<C>, aux_data: Self::AuxData) -> Self {
+        let (a, b) = aux_data;
+        (
+            A::create_from_initial_state(state, a),
+            B::create_from_initial_state(state, b),
+        )
+    }
+
+    forward_tracer_hooks!(self, [&mut self.0, &mut self.1], Tracer);
+}
+
+impl<C: MachineConfig, A: Tracer<C>, B: Tracer<C>, D: Tracer<C>> Tracer<C> for (A, B, D) {
+    type AuxData = (A::AuxData, B::AuxData, D::AuxData);
+
+    fn create_from_initial_state(state: &RiscV32State<C>, aux_data: Self::AuxData) -> Self {
+        let (a, b, d) = aux_data;
+        (
+            A::create_from_initial_state(state, a),
+            B::create_from_initial_state(state, b),
+            D::create_from_initial_state(state, d),
+        )
+    }
+
+    forward_tracer_hooks!(self, [&mut self.0, &mut self.1, &mut self.2], Tracer);
+}
+
+/// Borrowed tracer, so that a tracer can be combined with others without giving up its
+/// ownership. There is nothing to create, the borrow is passed as the auxiliary data
+impl<'a, C: MachineConfig, T: Tracer<C>> Tracer<C> for &'a mut T {
+    type AuxData = &'a mut T;
+
+    fn create_from_initial_state(_state: &RiscV32State<C>, aux_data: Self::AuxData) -> Self {
+        aux_data
+    }
+
+    forward_tracer_hooks!(self, [&mut **self], Tracer);
+}
+
+/// Tracer that can be switched off at runtime. `None` ignores every event
+impl<C: MachineConfig, T: Tracer<C>> Tracer<C> for Option<T> {
+    type AuxData = Option<T::AuxData>;
+
+    fn create_from_initial_state(state: &RiscV32State<C>, aux_data: Self::AuxData) -> Self {
+        aux_data.map(|aux_data| T::create_from_initial_state(state, aux_data))
+    }
+
+    forward_tracer_hooks!(self, (for tracer in self.iter_mut()), Tracer);
+}
+
+/// Object safe counterpart of `Tracer`, so that tracers of different types can be
+/// collected into a `Vec<Box<dyn DynTracer<C>>>` that is a `Tracer` itself.
+/// Implemented for every `Tracer`
+pub trait DynTracer<C: MachineConfig> {
+    fn at_cycle_start(&mut self, current_state: &RiscV32State<C>);
+
+    fn at_cycle_end(&mut self, current_state: &RiscV32State<C>);
+
+    fn trace_opcode_read(
+        &mut self,
+        phys_address: u64,
+        read_value: u32,
+        proc_cycle: u32,
+        cycle_timestamp: u32,
+    );
+
+    fn trace_rs1_read(
+        &mut self,
+        reg_idx: u32,
+        read_value: u32,
+        proc_cycle: u32,
+        cycle_timestamp: u32,
+    );
+
+    fn trace_rs2_read(
+        &mut self,
+        reg_idx: u32,
+        read_value: u32,
+        proc_cycle: u32,
+        cycle_timestamp: u32,
+    );
+
+    fn trace_rd_write(
+        &mut self,
+        reg_idx: u32,
+        read_value: u32,
+        written_value: u32,
+        proc_cycle: u32,
+        cycle_timestamp: u32,
+    );
+
+    fn trace_non_determinism_read(
+        &mut self,
+        read_value: u32,
+        proc_cycle: u32,
+        cycle_timestamp: u32,
+    );
+
+    fn trace_non_determinism_write(
+        &mut self,
+        written_value: u32,
+        proc_cycle: u32,
+        cycle_timestamp: u32,
+    );
+
+    fn trace_ram_read(
+        &mut self,
+        phys_address: u64,
+        read_value: u32,
+        proc_cycle: u32,
+        cycle_timestamp: u32,
+    );
+
+    fn trace_ram_read_write(
+        &mut self,
+        phys_address: u64,
+        read_value: u32,
+        written_value: u32,
+        proc_cycle: u32,
+        cycle_timestamp: u32,
+    );
+
+    fn trace_address_translation(
+        &mut self,
+        satp_value: u32,
+        virtual_address: u64,
+        phys_address: u64,
+        proc_cycle: u32,
+        cycle_timestamp: u32,
+    );
+
+    fn trace_batch_memory_access(
+        &mut self,
+        access_id: u32,
+        phys_address_high: u16,
+        accesses: &[BatchAccessPartialData],
+        proc_cycle: u32,
+        cycle_timestamp: u32,
+    );
+
+    fn trace_batch_memory_access_at(
+        &mut self,
+        access_id: u32,
+        phys_address: u64,
+        accesses: &[BatchAccessPartialData],
+        proc_cycle: u32,
+        cycle_timestamp: u32,
+    );
+
+    fn trace_mmio_read(
+        &mut self,
+        phys_address: u64,
+        read_value: u32,
+        proc_cycle: u32,
+        cycle_timestamp: u32,
+    );
+
+    fn trace_mmio_write(
+        &mut self,
+        phys_address: u64,
+        written_value: u32,
+        proc_cycle: u32,
+        cycle_timestamp: u32,
+    );
+
+    fn trace_csr_read(
+        &mut self,
+        csr_number: u32,
+        read_value: u32,
+        proc_cycle: u32,
+        cycle_timestamp: u32,
+    );
+
+    fn trace_csr_write(
+        &mut self,
+        csr_number: u32,
+        read_value: u32,
+        written_value: u32,
+        proc_cycle: u32,
+        cycle_timestamp: u32,
+    );
+
+    fn trace_trap(
+        &mut self,
+        trap: TrapReason,
+        pc: u32,
+        instr: u32,
+        proc_cycle: u32,
+        cycle_timestamp: u32,
+    );
+
+    fn trace_privilege_mode_change(
+        &mut self,
+        previous_mode: Mode,
+        new_mode: Mode,
+        proc_cycle: u32,
+        cycle_timestamp: u32,
+    );
+
+    fn trace_wait_for_interrupt_entry(&mut self, pc: u32, proc_cycle: u32, cycle_timestamp: u32);
+
+    fn trace_wait_for_interrupt_exit(&mut self, pc: u32, proc_cycle: u32, cycle_timestamp: u32);
+}
+
+impl<C: MachineConfig, T: Tracer<C>> DynTracer<C> for T {
+    forward_tracer_hooks!(self, [self], Tracer);
+}
+
+/// Dynamic list of tracers. There is nothing to create from the initial state, so
+/// the list is passed as is as the auxiliary data
+impl<C: MachineConfig> Tracer<C> for Vec<Box<dyn DynTracer<C>>> {
+    type AuxData = Self;
+
+    fn create_from_initial_state(_state: &RiscV32State<C>, aux_data: Self::AuxData) -> Self {
+        aux_data
+    }
+
+    forward_tracer_hooks!(self, (for tracer in self.iter_mut().map(|el| &mut **el)), DynTracer);
+}
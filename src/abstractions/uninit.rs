@@ -0,0 +1,175 @@
+//! Detection of reads of memory that was never written. Every byte has a shadow bit that is set
+//! when the byte is initialized by the image, a store or a delegation, and loads and instruction
+//! fetches of bytes without the bit are reported.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::ops::Range;
+
+use super::memory::{AccessType, MemorySource};
+use crate::cycle::status_registers::TrapReason;
+
+const PAGE_SIZE: u64 = 4096;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UninitRead {
+    /// Address of the first byte read.
+    pub address: u32,
+    pub num_bytes: u32,
+    pub access_type: AccessType,
+    /// Address of the instruction that made the read.
+    pub pc: u32,
+    /// Call stack of the instruction, innermost frame first. Filled by the simulator when the
+    /// profiler is enabled.
+    pub backtrace: Vec<String>,
+}
+
+/// `MemorySource` wrapper that tracks which bytes were initialized. Only the accesses announced
+/// through `MemorySource::before_access` are checked, so the reads the simulator makes for
+/// itself (e.g. the old value of a partial store) are not reported.
+pub struct UninitCheckingMemory<M: MemorySource> {
+    pub inner: M,
+    // One bit per byte, by page.
+    shadow: HashMap<u64, Box<[u64; (PAGE_SIZE / 64) as usize]>>,
+    // Reads from these ranges are never reported.
+    allowlist: Vec<Range<u32>>,
+    // Reads go through `MemorySource::get`, which only borrows the memory.
+    pending: Cell<Option<(u64, u32, AccessType)>>,
+    // Address of the last fetched instruction.
+    pc: Cell<u32>,
+    reads: RefCell<Vec<UninitRead>>,
+    reported: usize,
+}
+
+impl<M: MemorySource> UninitCheckingMemory<M> {
+    /// Wraps the memory with nothing initialized.
+    pub fn new(inner: M) -> Self {
+        Self {
+            inner,
+            shadow: HashMap::new(),
+            allowlist: Vec::new(),
+            pending: Cell::new(None),
+            pc: Cell::new(0),
+            reads: RefCell::new(Vec::new()),
+            reported: 0,
+        }
+    }
+
+    /// Marks the bytes as initialized, e.g. the ones covered by the loaded image.
+    pub fn mark_initialized(&mut self, address: u32, len: u32) {
+        for address in address as u64..address as u64 + len as u64 {
+            let page = self
+                .shadow
+                .entry(address / PAGE_SIZE)
+                .or_insert_with(|| Box::new([0; (PAGE_SIZE / 64) as usize]));
+            let bit = address % PAGE_SIZE;
+            page[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+
+    /// Never reports reads from the range, e.g. `.bss` that is zeroed by the loader.
+    pub fn allow(&mut self, range: Range<u32>) {
+        self.allowlist.push(range);
+    }
+
+    pub fn is_initialized(&self, address: u32) -> bool {
+        let address = address as u64;
+        let bit = address % PAGE_SIZE;
+
+        self.shadow
+            .get(&(address / PAGE_SIZE))
+            .is_some_and(|page| page[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Every read of uninitialized memory so far.
+    pub fn reads(&self) -> Vec<UninitRead> {
+        self.reads.borrow().clone()
+    }
+
+    // Bytes of the aligned word covered by the pending access, or the whole word.
+    fn pending_bytes(&self, aligned_address: u64, access_type: AccessType) -> (u64, u32) {
+        match self.pending.get() {
+            Some((address, num_bytes, pending_type))
+                if address & !3 == aligned_address && pending_type == access_type =>
+            {
+                (address, num_bytes)
+            }
+            _ => (aligned_address, 4),
+        }
+    }
+
+    fn check(&self, aligned_address: u64, access_type: AccessType) {
+        if access_type == AccessType::Instruction {
+            self.pc.set(aligned_address as u32);
+        }
+
+        let (address, num_bytes) = self.pending_bytes(aligned_address, access_type);
+
+        if self
+            .allowlist
+            .iter()
+            .any(|range| range.contains(&(address as u32)))
+        {
+            return;
+        }
+
+        if (address..address + num_bytes as u64).any(|byte| !self.is_initialized(byte as u32)) {
+            self.reads.borrow_mut().push(UninitRead {
+                address: address as u32,
+                num_bytes,
+                access_type,
+                pc: self.pc.get(),
+                backtrace: Vec::new(),
+            });
+        }
+    }
+}
+
+impl<M: MemorySource> MemorySource for UninitCheckingMemory<M> {
+    #[inline(always)]
+    fn before_access(&mut self, phys_address: u64, num_bytes: u32, access_type: AccessType) {
+        self.pending
+            .set(Some((phys_address, num_bytes, access_type)));
+    }
+
+    fn get(&self, phys_address: u64, access_type: AccessType, trap: &mut TrapReason) -> u32 {
+        let value = self.inner.get(phys_address, access_type, trap);
+
+        // The old value read by a store is announced as a store, and so is not checked.
+        if let Some((_, _, pending_type)) = self.pending.get()
+            && pending_type == access_type
+        {
+            if !trap.is_a_trap() {
+                self.check(phys_address, access_type);
+            }
+            self.pending.set(None);
+        }
+
+        value
+    }
+
+    fn set(
+        &mut self,
+        phys_address: u64,
+        value: u32,
+        access_type: AccessType,
+        trap: &mut TrapReason,
+    ) {
+        self.inner.set(phys_address, value, access_type, trap);
+        if trap.is_a_trap() {
+            return;
+        }
+
+        let (address, num_bytes) = self.pending_bytes(phys_address, access_type);
+        self.mark_initialized(address as u32, num_bytes);
+        self.pending.set(None);
+    }
+
+    fn take_uninit_reads(&mut self) -> Vec<UninitRead> {
+        let reads = self.reads.get_mut();
+        let new_reads = reads[self.reported..].to_vec();
+        self.reported = reads.len();
+
+        new_reads
+    }
+}
@@ -0,0 +1,382 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+
+use super::decode::decode;
+use super::delegation_name;
+use crate::abstractions::tracer::{BatchAccessPartialData, Tracer};
+use crate::cycle::state::RiscV32State;
+use crate::cycle::status_registers::TrapReason;
+use crate::cycle::MachineConfig;
+use crate::sim::Symbolizer;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub enum InstructionClass {
+    Alu,
+    MulDiv,
+    Branch,
+    Jump,
+    Load,
+    Store,
+    Csr,
+    Mop,
+}
+
+impl InstructionClass {
+    pub fn of(instr: u32) -> Option<Self> {
+        let class = match decode(instr)?.mnemonic {
+            "mul" | "mulh" | "mulhsu" | "mulhu" | "div" | "divu" | "rem" | "remu" => Self::MulDiv,
+            "beq" | "bne" | "blt" | "bge" | "bltu" | "bgeu" => Self::Branch,
+            "jal" | "jalr" => Self::Jump,
+            "lb" | "lh" | "lw" | "lbu" | "lhu" => Self::Load,
+            "sb" | "sh" | "sw" => Self::Store,
+            "csrrw" | "csrrs" | "csrrc" | "csrrwi" | "csrrsi" | "csrrci" => Self::Csr,
+            "addmod" | "submod" | "mulmod" => Self::Mop,
+            _ => Self::Alu,
+        };
+
+        Some(class)
+    }
+}
+
+/// Weights of everything that has a cost in the circuit. Defaults are uniform and only
+/// make the report equivalent to counting events, actual numbers depend on the circuit
+#[derive(Clone, Debug)]
+pub struct CostModel {
+    pub alu: u64,
+    pub mul_div: u64,
+    pub branch: u64,
+    pub jump: u64,
+    pub load: u64,
+    pub store: u64,
+    pub csr: u64,
+    pub mop: u64,
+    /// Per RAM read or read-write performed by loads, stores and address translation
+    pub memory_access: u64,
+    pub delegation_invocation: u64,
+    /// Per delegation CSR, takes precedence over `delegation_invocation`
+    pub delegation_invocation_overrides: HashMap<u32, u64>,
+    /// Per element of the batch that a delegation reads or writes
+    pub batch_access: u64,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self {
+            alu: 1,
+            mul_div: 1,
+            branch: 1,
+            jump: 1,
+            load: 1,
+            store: 1,
+            csr: 1,
+            mop: 1,
+            memory_access: 1,
+            delegation_invocation: 1,
+            delegation_invocation_overrides: HashMap::new(),
+            batch_access: 1,
+        }
+    }
+}
+
+impl CostModel {
+    pub fn instruction_weight(&self, class: InstructionClass) -> u64 {
+        match class {
+            InstructionClass::Alu => self.alu,
+            InstructionClass::MulDiv => self.mul_div,
+            InstructionClass::Branch => self.branch,
+            InstructionClass::Jump => self.jump,
+            InstructionClass::Load => self.load,
+            InstructionClass::Store => self.store,
+            InstructionClass::Csr => self.csr,
+            InstructionClass::Mop => self.mop,
+        }
+    }
+
+    pub fn delegation_weight(&self, access_id: u32) -> u64 {
+        self.delegation_invocation_overrides
+            .get(&access_id)
+            .copied()
+            .unwrap_or(self.delegation_invocation)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct DelegationCounters {
+    invocations: u64,
+    batch_accesses: u64,
+}
+
+/// Evaluates the cost model over the run. Every cost is also attributed to the PC of the
+/// instruction that caused it, so it can be later attributed to functions
+#[derive(Clone, Debug)]
+pub struct CostTracer {
+    model: CostModel,
+    current_pc: u32,
+    current_opcode: Option<u32>,
+    current_cycle_trapped: bool,
+    instructions: HashMap<InstructionClass, u64>,
+    memory_accesses: u64,
+    delegations: HashMap<u32, DelegationCounters>,
+    cost_per_pc: HashMap<u32, u64>,
+}
+
+impl CostTracer {
+    pub fn new(model: CostModel) -> Self {
+        Self {
+            model,
+            current_pc: 0,
+            current_opcode: None,
+            current_cycle_trapped: false,
+            instructions: HashMap::new(),
+            memory_accesses: 0,
+            delegations: HashMap::new(),
+            cost_per_pc: HashMap::new(),
+        }
+    }
+
+    fn charge(&mut self, cost: u64) {
+        *self.cost_per_pc.entry(self.current_pc).or_default() += cost;
+    }
+}
+
+impl<C: MachineConfig> Tracer<C> for CostTracer {
+    type AuxData = CostModel;
+
+    fn create_from_initial_state(_state: &RiscV32State<C>, aux_data: Self::AuxData) -> Self {
+        Self::new(aux_data)
+    }
+
+    fn at_cycle_start(&mut self, current_state: &RiscV32State<C>) {
+        self.current_pc = current_state.pc;
+        self.current_opcode = None;
+        self.current_cycle_trapped = false;
+    }
+
+    fn trace_opcode_read(
+        &mut self,
+        _phys_address: u64,
+        read_value: u32,
+        _proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+        self.current_opcode = Some(read_value);
+    }
+
+    fn trace_ram_read(
+        &mut self,
+        _phys_address: u64,
+        _read_value: u32,
+        _proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+        self.memory_accesses += 1;
+        self.charge(self.model.memory_access);
+    }
+
+    fn trace_ram_read_write(
+        &mut self,
+        _phys_address: u64,
+        _read_value: u32,
+        _written_value: u32,
+        _proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+        self.memory_accesses += 1;
+        self.charge(self.model.memory_access);
+    }
+
+    fn trace_batch_memory_access(
+        &mut self,
+        access_id: u32,
+        _phys_address_high: u16,
+        accesses: &[BatchAccessPartialData],
+        _proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+        let counters = self.delegations.entry(access_id).or_default();
+        counters.invocations += 1;
+        counters.batch_accesses += accesses.len() as u64;
+        let cost = self.model.delegation_weight(access_id)
+            + self.model.batch_access * accesses.len() as u64;
+        self.charge(cost);
+    }
+
+    fn trace_batch_memory_access_at(
+        &mut self,
+        access_id: u32,
+        _phys_address: u64,
+        accesses: &[BatchAccessPartialData],
+        _proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+        let counters = self.delegations.entry(access_id).or_default();
+        counters.batch_accesses += accesses.len() as u64;
+        self.charge(self.model.batch_access * accesses.len() as u64);
+    }
+
+    fn trace_trap(
+        &mut self,
+        _trap: TrapReason,
+        _pc: u32,
+        _instr: u32,
+        _proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+        self.current_cycle_trapped = true;
+    }
+
+    fn at_cycle_end(&mut self, _current_state: &RiscV32State<C>) {
+        if let Some(opcode) = self.current_opcode.take()
+            && !self.current_cycle_trapped
+            && let Some(class) = InstructionClass::of(opcode)
+        {
+            *self.instructions.entry(class).or_default() += 1;
+            self.charge(self.model.instruction_weight(class));
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ItemCost {
+    pub count: u64,
+    pub cost: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct DelegationCost {
+    pub invocations: u64,
+    pub batch_accesses: u64,
+    pub cost: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FunctionCost {
+    pub name: String,
+    pub cost: u64,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CostReport {
+    pub total_cost: u64,
+    pub instructions: BTreeMap<InstructionClass, ItemCost>,
+    pub memory_accesses: ItemCost,
+    pub delegations: BTreeMap<String, DelegationCost>,
+    /// Only filled if the report was built with a symbolizer, most expensive first
+    pub per_function: Vec<FunctionCost>,
+}
+
+impl CostTracer {
+    pub fn report(&self, symbolizer: Option<&mut Symbolizer>) -> CostReport {
+        let mut report = CostReport::default();
+
+        for (&class, &count) in self.instructions.iter() {
+            let cost = count * self.model.instruction_weight(class);
+            report.instructions.insert(class, ItemCost { count, cost });
+            report.total_cost += cost;
+        }
+
+        report.memory_accesses = ItemCost {
+            count: self.memory_accesses,
+            cost: self.memory_accesses * self.model.memory_access,
+        };
+        report.total_cost += report.memory_accesses.cost;
+
+        for (&access_id, counters) in self.delegations.iter() {
+            let cost = counters.invocations * self.model.delegation_weight(access_id)
+                + counters.batch_accesses * self.model.batch_access;
+            report.delegations.insert(
+                delegation_name(access_id),
+                DelegationCost {
+                    invocations: counters.invocations,
+                    batch_accesses: counters.batch_accesses,
+                    cost,
+                },
+            );
+            report.total_cost += cost;
+        }
+
+        if let Some(symbolizer) = symbolizer {
+            let mut per_function: HashMap<String, u64> = HashMap::new();
+            for (&pc, &cost) in self.cost_per_pc.iter() {
+                let name = symbolizer
+                    .function_name(pc as u64)
+                    .unwrap_or("[unknown]")
+                    .to_owned();
+                *per_function.entry(name).or_default() += cost;
+            }
+            report.per_function = per_function
+                .into_iter()
+                .map(|(name, cost)| FunctionCost { name, cost })
+                .collect();
+            report
+                .per_function
+                .sort_by(|a, b| b.cost.cmp(&a.cost).then_with(|| a.name.cmp(&b.name)));
+        }
+
+        report
+    }
+}
+
+impl CostReport {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("report is always serializable")
+    }
+}
+
+impl std::fmt::Display for CostReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let percentage = |cost: u64| 100.0 * cost as f64 / self.total_cost.max(1) as f64;
+
+        writeln!(f, "Total cost: {}", self.total_cost)?;
+        writeln!(
+            f,
+            "{:<32} {:>14} {:>14} {:>8}",
+            "item", "count", "cost", "%"
+        )?;
+        for (class, item) in self.instructions.iter() {
+            writeln!(
+                f,
+                "{:<32} {:>14} {:>14} {:>7.2}%",
+                format!("{:?}", class),
+                item.count,
+                item.cost,
+                percentage(item.cost)
+            )?;
+        }
+        writeln!(
+            f,
+            "{:<32} {:>14} {:>14} {:>7.2}%",
+            "memory accesses",
+            self.memory_accesses.count,
+            self.memory_accesses.cost,
+            percentage(self.memory_accesses.cost)
+        )?;
+        for (name, delegation) in self.delegations.iter() {
+            writeln!(
+                f,
+                "{:<32} {:>14} {:>14} {:>7.2}%",
+                name,
+                delegation.invocations,
+                delegation.cost,
+                percentage(delegation.cost)
+            )?;
+        }
+
+        if !self.per_function.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "{:>14} {:>8}  function", "cost", "%")?;
+            for function in self.per_function.iter() {
+                writeln!(
+                    f,
+                    "{:>14} {:>7.2}%  {}",
+                    function.cost,
+                    percentage(function.cost),
+                    function.name
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
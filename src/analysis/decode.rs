@@ -0,0 +1,201 @@
+use crate::cycle::opcode_formats::*;
+use crate::utils::*;
+
+/// Operands of a decoded instruction. Immediates are sign extended the same way the
+/// simulator does it, so branch and jump offsets are relative to the instruction address
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operands {
+    Upper { rd: u32, imm: u32 },
+    Jump { rd: u32, offset: i32 },
+    RegImm { rd: u32, rs1: u32, imm: i32 },
+    RegReg { rd: u32, rs1: u32, rs2: u32 },
+    Branch { rs1: u32, rs2: u32, offset: i32 },
+    Load { rd: u32, rs1: u32, offset: i32 },
+    Store { rs1: u32, rs2: u32, offset: i32 },
+    Csr { rd: u32, csr: u32, rs1: u32 },
+    CsrImm { rd: u32, csr: u32, uimm: u32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodedInstruction {
+    pub mnemonic: &'static str,
+    pub operands: Operands,
+}
+
+fn signed_imm(imm: u32, total_bits: u32) -> i32 {
+    let mut imm = imm;
+    sign_extend(&mut imm, total_bits);
+
+    imm as i32
+}
+
+/// Decodes any instruction that the simulator can execute under at least one `MachineConfig`.
+/// Returns `None` for encodings that always trap
+pub fn decode(instr: u32) -> Option<DecodedInstruction> {
+    let rd = get_rd(instr);
+    let rs1 = RTypeOpcode::rs1(instr);
+    let rs2 = RTypeOpcode::rs2(instr);
+    let funct3 = RTypeOpcode::funct3(instr);
+    let funct7 = RTypeOpcode::funct7(instr);
+
+    let (mnemonic, operands) = match get_opcode(instr) {
+        0b0110111 => (
+            "lui",
+            Operands::Upper {
+                rd,
+                imm: UTypeOpcode::imm(instr),
+            },
+        ),
+        0b0010111 => (
+            "auipc",
+            Operands::Upper {
+                rd,
+                imm: UTypeOpcode::imm(instr),
+            },
+        ),
+        0b1101111 => (
+            "jal",
+            Operands::Jump {
+                rd,
+                offset: signed_imm(JTypeOpcode::imm(instr), 21),
+            },
+        ),
+        0b1100111 => (
+            "jalr",
+            Operands::RegImm {
+                rd,
+                rs1,
+                imm: signed_imm(ITypeOpcode::imm(instr), 12),
+            },
+        ),
+        0b1100011 => {
+            let mnemonic = match funct3 {
+                0 => "beq",
+                1 => "bne",
+                4 => "blt",
+                5 => "bge",
+                6 => "bltu",
+                7 => "bgeu",
+                _ => return None,
+            };
+            let offset = signed_imm(BTypeOpcode::imm(instr), 13);
+
+            (mnemonic, Operands::Branch { rs1, rs2, offset })
+        }
+        0b0000011 => {
+            let mnemonic = match funct3 {
+                0 => "lb",
+                1 => "lh",
+                2 => "lw",
+                4 => "lbu",
+                5 => "lhu",
+                _ => return None,
+            };
+            let offset = signed_imm(ITypeOpcode::imm(instr), 12);
+
+            (mnemonic, Operands::Load { rd, rs1, offset })
+        }
+        0b0100011 => {
+            let mnemonic = match funct3 {
+                0 => "sb",
+                1 => "sh",
+                2 => "sw",
+                _ => return None,
+            };
+            let offset = signed_imm(STypeOpcode::imm(instr), 12);
+
+            (mnemonic, Operands::Store { rs1, rs2, offset })
+        }
+        0b0010011 => {
+            let mnemonic = match (funct3, funct7) {
+                (0, _) => "addi",
+                (1, 0) => "slli",
+                (1, 0b0110000) => "roli",
+                (2, _) => "slti",
+                (3, _) => "sltiu",
+                (4, _) => "xori",
+                (5, 0) => "srli",
+                (5, 0b0100000) => "srai",
+                (5, 0b0110000) => "rori",
+                (6, _) => "ori",
+                (7, _) => "andi",
+                _ => return None,
+            };
+            let imm = match funct3 {
+                // shift amount
+                1 | 5 => rs2 as i32,
+                _ => signed_imm(ITypeOpcode::imm(instr), 12),
+            };
+
+            (mnemonic, Operands::RegImm { rd, rs1, imm })
+        }
+        0b0110011 => {
+            let mnemonic = match (funct7, funct3) {
+                (0, 0) => "add",
+                (0b0100000, 0) => "sub",
+                (0, 1) => "sll",
+                (0, 2) => "slt",
+                (0, 3) => "sltu",
+                (0, 4) => "xor",
+                (0, 5) => "srl",
+                (0b0100000, 5) => "sra",
+                (0, 6) => "or",
+                (0, 7) => "and",
+                (1, 0) => "mul",
+                (1, 1) => "mulh",
+                (1, 2) => "mulhsu",
+                (1, 3) => "mulhu",
+                (1, 4) => "div",
+                (1, 5) => "divu",
+                (1, 6) => "rem",
+                (1, 7) => "remu",
+                _ => return None,
+            };
+
+            (mnemonic, Operands::RegReg { rd, rs1, rs2 })
+        }
+        0b1110011 => {
+            let csr = ITypeOpcode::imm(instr);
+            match funct3 {
+                1 => ("csrrw", Operands::Csr { rd, csr, rs1 }),
+                2 => ("csrrs", Operands::Csr { rd, csr, rs1 }),
+                3 => ("csrrc", Operands::Csr { rd, csr, rs1 }),
+                5 => ("csrrwi", Operands::CsrImm { rd, csr, uimm: rs1 }),
+                6 => ("csrrsi", Operands::CsrImm { rd, csr, uimm: rs1 }),
+                7 => ("csrrci", Operands::CsrImm { rd, csr, uimm: rs1 }),
+                4 if funct7 & 0b1000001 == 0b1000001 => {
+                    let mop_number = ((funct7 & 0b110) >> 1) | ((funct7 & 0b100000) >> 5);
+                    let mnemonic = match mop_number {
+                        0 => "addmod",
+                        1 => "submod",
+                        2 => "mulmod",
+                        _ => return None,
+                    };
+
+                    (mnemonic, Operands::RegReg { rd, rs1, rs2 })
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+
+    Some(DecodedInstruction { mnemonic, operands })
+}
+
+impl std::fmt::Display for DecodedInstruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.mnemonic)?;
+        match self.operands {
+            Operands::Upper { rd, imm } => write!(f, " x{}, 0x{:x}", rd, imm >> 12),
+            Operands::Jump { rd, offset } => write!(f, " x{}, {}", rd, offset),
+            Operands::RegImm { rd, rs1, imm } => write!(f, " x{}, x{}, {}", rd, rs1, imm),
+            Operands::RegReg { rd, rs1, rs2 } => write!(f, " x{}, x{}, x{}", rd, rs1, rs2),
+            Operands::Branch { rs1, rs2, offset } => write!(f, " x{}, x{}, {}", rs1, rs2, offset),
+            Operands::Load { rd, rs1, offset } => write!(f, " x{}, {}(x{})", rd, offset, rs1),
+            Operands::Store { rs1, rs2, offset } => write!(f, " x{}, {}(x{})", rs2, offset, rs1),
+            Operands::Csr { rd, csr, rs1 } => write!(f, " x{}, 0x{:03x}, x{}", rd, csr, rs1),
+            Operands::CsrImm { rd, csr, uimm } => write!(f, " x{}, 0x{:03x}, {}", rd, csr, uimm),
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+
+use super::decode::{decode, Operands};
+use super::delegation_name;
+use crate::abstractions::tracer::Tracer;
+use crate::cycle::state::{is_standard_csr, RiscV32State, NON_DETERMINISM_CSR};
+use crate::cycle::status_registers::TrapReason;
+use crate::cycle::{IMStandardIsaConfig, MachineConfig, ReducedIMIsaConfig};
+
+/// Parts of the ISA that are optional in `MachineConfig`, plus delegations, whose support
+/// depends on the runtime registry rather than the config
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub enum IsaFeature {
+    SignedMul,
+    SignedDiv,
+    SignedLoad,
+    LoadLessThanWord,
+    Sra,
+    Rot,
+    Mops,
+    StandardCsrs,
+    CsrOpsOtherThanCsrrw,
+    Delegation,
+}
+
+impl IsaFeature {
+    pub fn is_supported_by<C: MachineConfig>(&self) -> bool {
+        match self {
+            Self::SignedMul => C::SUPPORT_SIGNED_MUL,
+            Self::SignedDiv => C::SUPPORT_SIGNED_DIV,
+            Self::SignedLoad => C::SUPPORT_SIGNED_LOAD,
+            Self::LoadLessThanWord => C::SUPPORT_LOAD_LESS_THAN_WORD,
+            Self::Sra => C::SUPPORT_SRA,
+            Self::Rot => C::SUPPORT_ROT,
+            Self::Mops => C::SUPPORT_MOPS,
+            Self::StandardCsrs => C::SUPPORT_STANDARD_CSRS,
+            Self::CsrOpsOtherThanCsrrw => !C::SUPPORT_ONLY_CSRRW,
+            // a property of the delegation registry, which can be emptied with
+            // `--no-delegations`, not of `MachineConfig`
+            Self::Delegation => true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub enum CsrKind {
+    NonDeterminism,
+    Standard,
+    Delegation,
+}
+
+/// Counts instructions that were executed without a trap. Raw opcodes are collected
+/// during the run and only decoded when the report is built
+#[derive(Clone, Debug, Default)]
+pub struct InstructionMixTracer {
+    current_opcode: Option<u32>,
+    current_cycle_trapped: bool,
+    retired: HashMap<u32, u64>,
+}
+
+impl<C: MachineConfig> Tracer<C> for InstructionMixTracer {
+    type AuxData = ();
+
+    fn create_from_initial_state(_state: &RiscV32State<C>, _aux_data: Self::AuxData) -> Self {
+        Self::default()
+    }
+
+    fn at_cycle_start(&mut self, _current_state: &RiscV32State<C>) {
+        self.current_opcode = None;
+        self.current_cycle_trapped = false;
+    }
+
+    fn trace_opcode_read(
+        &mut self,
+        _phys_address: u64,
+        read_value: u32,
+        _proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+        // page table walk for the instruction fetch is traced the same way, and the
+        // opcode itself is always read last
+        self.current_opcode = Some(read_value);
+    }
+
+    fn trace_trap(
+        &mut self,
+        _trap: TrapReason,
+        _pc: u32,
+        _instr: u32,
+        _proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+        self.current_cycle_trapped = true;
+    }
+
+    fn at_cycle_end(&mut self, _current_state: &RiscV32State<C>) {
+        if let Some(opcode) = self.current_opcode.take()
+            && !self.current_cycle_trapped
+        {
+            *self.retired.entry(opcode).or_default() += 1;
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct InstructionMixReport {
+    pub total_retired: u64,
+    pub by_mnemonic: BTreeMap<&'static str, u64>,
+    pub by_feature: BTreeMap<IsaFeature, u64>,
+    pub by_csr_kind: BTreeMap<CsrKind, u64>,
+    /// Named after the delegation if it's known, otherwise after the CSR number
+    pub delegations: BTreeMap<String, u64>,
+    /// Existing configs that could have executed this run, from the most restricted one
+    pub sufficient_configs: Vec<&'static str>,
+}
+
+impl InstructionMixTracer {
+    pub fn report(&self) -> InstructionMixReport {
+        let mut report = InstructionMixReport::default();
+
+        for (&opcode, &count) in self.retired.iter() {
+            // the instruction has retired, so it must be decodable
+            let instruction = decode(opcode).expect("retired instruction must be decodable");
+
+            report.total_retired += count;
+            *report.by_mnemonic.entry(instruction.mnemonic).or_default() += count;
+
+            let mut features = Vec::with_capacity(2);
+            match instruction.mnemonic {
+                "mulh" | "mulhsu" => features.push(IsaFeature::SignedMul),
+                "div" | "rem" => features.push(IsaFeature::SignedDiv),
+                "lb" | "lh" => {
+                    features.push(IsaFeature::SignedLoad);
+                    features.push(IsaFeature::LoadLessThanWord);
+                }
+                "lbu" | "lhu" | "sb" | "sh" => features.push(IsaFeature::LoadLessThanWord),
+                "sra" | "srai" => features.push(IsaFeature::Sra),
+                "roli" | "rori" => features.push(IsaFeature::Rot),
+                "addmod" | "submod" | "mulmod" => features.push(IsaFeature::Mops),
+                _ => {}
+            }
+
+            let csr = match instruction.operands {
+                Operands::Csr { csr, .. } | Operands::CsrImm { csr, .. } => Some(csr),
+                _ => None,
+            };
+            if let Some(csr) = csr {
+                if instruction.mnemonic != "csrrw" {
+                    features.push(IsaFeature::CsrOpsOtherThanCsrrw);
+                }
+                let kind = if csr == NON_DETERMINISM_CSR {
+                    CsrKind::NonDeterminism
+                } else if is_standard_csr(csr) {
+                    features.push(IsaFeature::StandardCsrs);
+                    CsrKind::Standard
+                } else {
+                    features.push(IsaFeature::Delegation);
+                    *report.delegations.entry(delegation_name(csr)).or_default() += count;
+                    CsrKind::Delegation
+                };
+                *report.by_csr_kind.entry(kind).or_default() += count;
+            }
+
+            for feature in features {
+                *report.by_feature.entry(feature).or_default() += count;
+            }
+        }
+
+        let is_sufficient =
+            |supported: fn(&IsaFeature) -> bool| report.by_feature.keys().all(supported);
+        let mut sufficient_configs = vec![];
+        if is_sufficient(IsaFeature::is_supported_by::<ReducedIMIsaConfig>) {
+            sufficient_configs.push("ReducedIMIsaConfig");
+        }
+        if is_sufficient(IsaFeature::is_supported_by::<IMStandardIsaConfig>) {
+            sufficient_configs.push("IMStandardIsaConfig");
+        }
+        report.sufficient_configs = sufficient_configs;
+
+        report
+    }
+}
+
+impl InstructionMixReport {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("report is always serializable")
+    }
+}
+
+impl std::fmt::Display for InstructionMixReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let percentage = |count: u64| 100.0 * count as f64 / self.total_retired.max(1) as f64;
+
+        writeln!(f, "Retired instructions: {}", self.total_retired)?;
+
+        let mut by_mnemonic: Vec<_> = self.by_mnemonic.iter().collect();
+        by_mnemonic.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        writeln!(f, "{:<16} {:>14} {:>8}", "mnemonic", "count", "%")?;
+        for (mnemonic, count) in by_mnemonic {
+            writeln!(
+                f,
+                "{:<16} {:>14} {:>7.2}%",
+                mnemonic,
+                count,
+                percentage(*count)
+            )?;
+        }
+
+        if !self.by_feature.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "{:<24} {:>14}", "feature", "count")?;
+            for (feature, count) in self.by_feature.iter() {
+                writeln!(f, "{:<24} {:>14}", format!("{:?}", feature), count)?;
+            }
+        }
+
+        if !self.by_csr_kind.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "{:<24} {:>14}", "csr kind", "count")?;
+            for (kind, count) in self.by_csr_kind.iter() {
+                writeln!(f, "{:<24} {:>14}", format!("{:?}", kind), count)?;
+            }
+        }
+
+        if !self.delegations.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "{:<32} {:>14}", "delegation", "count")?;
+            for (name, count) in self.delegations.iter() {
+                writeln!(f, "{:<32} {:>14}", name, count)?;
+            }
+        }
+
+        writeln!(f)?;
+        if self.sufficient_configs.is_empty() {
+            write!(f, "None of the existing configs is sufficient for this run")
+        } else {
+            write!(
+                f,
+                "Sufficient configs: {}",
+                self.sufficient_configs.join(", ")
+            )
+        }
+    }
+}
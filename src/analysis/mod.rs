@@ -0,0 +1,13 @@
+pub mod cost_model;
+pub mod decode;
+pub mod instruction_mix;
+
+use crate::delegations::DelegationRegistry;
+
+/// Name of the delegation behind the CSR, or the CSR number if it's not a known one
+pub(crate) fn delegation_name(csr: u32) -> String {
+    DelegationRegistry::builtin()
+        .name(csr)
+        .map(|name| name.to_owned())
+        .unwrap_or_else(|| format!("csr_0x{:03x}", csr))
+}
@@ -17,6 +17,12 @@ pub const NUM_REGISTERS: usize = 32;
 pub const MAX_MEMORY_OPS_PER_CYCLE: u32 = 3;
 pub const NON_DETERMINISM_CSR: u32 = 0x7c0;
 
+/// CSRs implemented by the machine itself when it supports standard CSRs: satp and the machine
+/// trap ones.
+pub(crate) const fn is_standard_csr(csr_number: u32) -> bool {
+    matches!(csr_number, 0x180 | 0x300 | 0x304 | 0x305 | 0x340..=0x344)
+}
+
 // static CSR_COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -301,6 +307,10 @@ where
         tracer.trace_rd_write(reg_idx, read_value, value, proc_cycle, cycle_timestamp);
     }
 
+    /// Runs a cycle without delegations, their CSRs trap as any other unknown one. With the
+    /// `delegation` feature the builtin delegations are available, see
+    /// `DelegationRegistry::builtin`. `Simulator` runs with `SimulatorConfig::delegations`
+    /// instead, and `cycle_ext` takes any `DelegationRegistry` as the CSR processor.
     pub fn cycle<
         'a,
         M: MemorySource,
@@ -343,6 +353,12 @@ where
         }
     }
 
+    /// Runs a cycle with `csr_processor` handling the non-standard CSRs.
+    ///
+    /// With a `DelegationRegistry` as the processor, a delegation that rejects its input only
+    /// traps if the machine handles exceptions. Otherwise the instruction retires silently, and
+    /// the fault is only visible through `DelegationRegistry::take_faults`, which the caller has
+    /// to check after every cycle.
     pub fn cycle_ext<
         'a,
         M: MemorySource,
@@ -363,8 +379,15 @@ where
         tracer.at_cycle_start(&*self);
 
         if self.extra_flags.get_wait_for_interrupt() != 0 {
-            tracer.at_cycle_end(&*self);
-            return;
+            // the hart resumes once any enabled interrupt becomes pending, even if interrupts
+            // are globally disabled
+            let state = &self.machine_mode_trap_data.state;
+            if state.ip & state.ie == 0 {
+                tracer.at_cycle_end(&*self);
+                return;
+            }
+            self.extra_flags.clear_wait_for_interrupt_bit();
+            tracer.trace_wait_for_interrupt_exit(self.pc, proc_cycle, cycle_timestamp);
         }
 
         let current_privilege_mode = self.extra_flags.get_current_mode();
@@ -384,6 +407,7 @@ where
                 memory_source,
                 tracer,
                 proc_cycle,
+                cycle_timestamp,
                 &mut trap,
             );
             if trap.is_a_trap() {
@@ -529,7 +553,7 @@ where
                     // will be abstracted away into external interface hiding memory translation too
                     let operand_phys_address = mmu.map_virtual_to_physical(
                         virtual_address, current_privilege_mode, AccessType::MemLoad, memory_source,
-                        tracer, proc_cycle, &mut trap
+                        tracer, proc_cycle, cycle_timestamp, &mut trap
                     );
                     if trap.is_a_trap() {
                         // error during address translation
@@ -609,7 +633,7 @@ where
                     // will be abstracted away into external interface hiding memory translation too
                     let operand_phys_address = mmu.map_virtual_to_physical(
                         virtual_address, current_privilege_mode, AccessType::MemStore, memory_source, tracer,
-                        proc_cycle, &mut trap
+                        proc_cycle, cycle_timestamp, &mut trap
                     );
                     if trap.is_a_trap() {
                         debug_assert_eq!(trap, TrapReason::StoreOrAMOPageFault);
@@ -920,9 +944,11 @@ where
                                             // do nothing
                                         } else {
                                             non_determinism_source.write_with_memory_access(&*memory_source, write_val);
+                                            tracer.trace_non_determinism_write(write_val, proc_cycle, cycle_timestamp);
                                         }
                                     } else {
                                         non_determinism_source.write_with_memory_access(&*memory_source, write_val);
+                                        tracer.trace_non_determinism_write(write_val, proc_cycle, cycle_timestamp);
                                     }
                                 }
                                 _ => {
@@ -935,6 +961,10 @@ where
                                 }
                             }
                         } else {
+                            // CSRs that are implemented by the machine itself. Oracle and custom ones
+                            // are traced by the corresponding sources/processors
+                            let is_standard_csr = is_standard_csr(csr_number);
+
                             // read
                             match csr_number {
                                 0x180 => {
@@ -985,6 +1015,10 @@ where
                                 }
                             }
 
+                            if is_standard_csr {
+                                tracer.trace_csr_read(csr_number, ret_val, proc_cycle, cycle_timestamp);
+                            }
+
                             let write_val;
                             // update
                             if Config::SUPPORT_ONLY_CSRRW == false {
@@ -1035,9 +1069,11 @@ where
                                             // do nothing
                                         } else {
                                             non_determinism_source.write_with_memory_access(&*memory_source, write_val);
+                                            tracer.trace_non_determinism_write(write_val, proc_cycle, cycle_timestamp);
                                         }
                                     } else {
                                         non_determinism_source.write_with_memory_access(&*memory_source, write_val);
+                                        tracer.trace_non_determinism_write(write_val, proc_cycle, cycle_timestamp);
                                     }
                                 }
                                 _ => {
@@ -1049,12 +1085,26 @@ where
                                     }
                                 }
                             }
+
+                            if is_standard_csr {
+                                tracer.trace_csr_write(csr_number, ret_val, write_val, proc_cycle, cycle_timestamp);
+                            }
                         }
                         // and writeback
                     } else if funct3 == 0b000 {
                         // TODO: add to configuration later
-                        trap = TrapReason::IllegalInstruction;
-                        break 'cycle_block;
+                        // only WFI is supported, by machines with the machine mode CSRs
+                        if Config::SUPPORT_STANDARD_CSRS
+                            && csr_number == 0x105
+                            && rd == 0
+                            && ITypeOpcode::rs1(instr) == 0
+                        {
+                            self.extra_flags.set_wait_for_interrupt_bit();
+                            tracer.trace_wait_for_interrupt_entry(pc, proc_cycle, cycle_timestamp);
+                        } else {
+                            trap = TrapReason::IllegalInstruction;
+                            break 'cycle_block;
+                        }
 
                         // // SYSTEM
                         // rd = 0;
@@ -1168,6 +1218,7 @@ where
                 "trap: {:?}, pc: {:08x}, proc_cycle: {:?}, instr: {:08x}",
                 trap, pc, proc_cycle, instr
             );
+            tracer.trace_trap(trap, pc, instr, proc_cycle, cycle_timestamp);
 
             if Config::HANDLE_EXCEPTIONS == false {
                 panic!("Simulator encountered an exception");
@@ -1211,6 +1262,16 @@ where
         // for debugging
         self.sapt = mmu.read_sapt(current_privilege_mode, &mut trap);
 
+        let new_privilege_mode = self.extra_flags.get_current_mode();
+        if new_privilege_mode != current_privilege_mode {
+            tracer.trace_privilege_mode_change(
+                current_privilege_mode,
+                new_privilege_mode,
+                proc_cycle,
+                cycle_timestamp,
+            );
+        }
+
         tracer.at_cycle_end(&*self);
 
         //let trap = trap.as_register_value();
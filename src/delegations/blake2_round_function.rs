@@ -1,4 +1,4 @@
-use crate::cycle::{state::NON_DETERMINISM_CSR, status_registers::TrapReason};
+use crate::cycle::state::NON_DETERMINISM_CSR;
 use blake2s_u32::{mixing_function, IV, SIGMAS};
 
 use super::*;
@@ -12,23 +12,33 @@ use super::*;
 pub const BLAKE2_ROUND_FUNCTION_ABI_NUM_MEM_ACCESSES: usize = 16 + 16 + 1;
 pub const BLAKE2_ROUND_FUNCTION_ACCESS_ID: u32 = NON_DETERMINISM_CSR + 2;
 
-pub fn blake2_round_function<
-    M: MemorySource,
-    TR: Tracer<C>,
-    MMU: MMUImplementation<M, TR, C>,
-    C: MachineConfig,
->(
-    memory_source: &mut M,
-    tracer: &mut TR,
-    _mmu: &mut MMU,
+#[derive(Clone, Copy, Debug)]
+pub struct Blake2RoundFunction;
+
+impl DelegationHandler for Blake2RoundFunction {
+    fn name(&self) -> &str {
+        "blake2_round_function"
+    }
+
+    fn num_words(&self) -> usize {
+        BLAKE2_ROUND_FUNCTION_ABI_NUM_MEM_ACCESSES
+    }
+
+    fn execute(
+        &self,
+        context: &mut dyn DelegationContext,
+        rs1_value: u32,
+    ) -> Result<(), DelegationError> {
+        blake2_round_function(context, rs1_value)
+    }
+}
+
+pub fn blake2_round_function(
+    context: &mut dyn DelegationContext,
     rs1_value: u32,
-    trap: &mut TrapReason,
-    proc_cycle: u32,
-    cycle_timestamp: u32,
-) {
+) -> Result<(), DelegationError> {
     // we consider high bits as the offset
-    assert_eq!(rs1_value as u16, 0, "unaligned");
-    let mem_offset = (rs1_value & 0xffff0000) as usize;
+    let mem_offset = memory_offset(rs1_value)?;
 
     // we perform batch accesses
     let mut accesses = [BatchAccessPartialData::Read { read_value: 0 };
@@ -38,10 +48,7 @@ pub fn blake2_round_function<
     let mut extended_state = [0u32; 16];
     for low_offset in 0..16 {
         let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
-        let read_value = memory_source.get(address as u64, AccessType::RegWrite, trap);
-        if trap.is_a_trap() {
-            panic!("error in blake2s memory access");
-        }
+        let read_value = context.read(address as u64)?;
 
         *it.next().unwrap() = BatchAccessPartialData::Write {
             read_value: read_value,
@@ -53,10 +60,7 @@ pub fn blake2_round_function<
     let mut message_block = [0u32; 16];
     for (low_offset, dst) in (16..32usize).zip(message_block.iter_mut()) {
         let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
-        let read_value = memory_source.get(address as u64, AccessType::RegWrite, trap);
-        if trap.is_a_trap() {
-            panic!("error in blake2s memory access");
-        }
+        let read_value = context.read(address as u64)?;
 
         *it.next().unwrap() = BatchAccessPartialData::Read { read_value };
         *dst = read_value;
@@ -64,15 +68,17 @@ pub fn blake2_round_function<
 
     // bitmask controlling the permutation
     let address: usize = mem_offset + 32 * core::mem::size_of::<u32>();
-    let read_value = memory_source.get(address as u64, AccessType::RegWrite, trap);
-    if trap.is_a_trap() {
-        panic!("error in blake2s memory access");
-    }
+    let read_value = context.read(address as u64)?;
 
     *it.next().unwrap() = BatchAccessPartialData::Read { read_value };
     let permutation_bitmask = read_value;
-    assert!(permutation_bitmask.is_power_of_two());
     let permutation_index = permutation_bitmask.trailing_zeros() as usize;
+    if !permutation_bitmask.is_power_of_two() || permutation_index >= SIGMAS.len() {
+        return Err(DelegationError::InvalidArgument(format!(
+            "invalid permutation bitmask 0x{:08x}",
+            permutation_bitmask
+        )));
+    }
 
     if permutation_index == 0 {
         // overwrite elements 8-11, 13, 15
@@ -104,17 +110,10 @@ pub fn blake2_round_function<
 
         *written_value = value;
         let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
-        memory_source.set(address as u64, value, AccessType::RegWrite, trap);
-        if trap.is_a_trap() {
-            panic!("error in blake2s memory access");
-        }
+        context.write(address as u64, value)?;
     }
 
-    tracer.trace_batch_memory_access(
-        BLAKE2_ROUND_FUNCTION_ACCESS_ID,
-        (mem_offset >> 16) as u16,
-        &accesses,
-        proc_cycle,
-        cycle_timestamp,
-    );
+    context.trace_batch_memory_access((mem_offset >> 16) as u16, &accesses);
+
+    Ok(())
 }
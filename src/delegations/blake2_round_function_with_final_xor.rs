@@ -1,4 +1,4 @@
-use crate::cycle::{state::NON_DETERMINISM_CSR, status_registers::TrapReason};
+use crate::cycle::state::NON_DETERMINISM_CSR;
 use blake2s_u32::{mixing_function, IV, SIGMAS};
 
 use super::*;
@@ -13,23 +13,33 @@ use super::*;
 pub const BLAKE2_ROUND_FUNCTION_ABI_NUM_MEM_ACCESSES: usize = 8 + 16 + 16 + 1 + 1;
 pub const BLAKE2_ROUND_FUNCTION_WITH_XOR_ACCESS_ID: u32 = NON_DETERMINISM_CSR + 3;
 
-pub fn blake2_round_function_with_xor<
-    M: MemorySource,
-    TR: Tracer<C>,
-    MMU: MMUImplementation<M, TR, C>,
-    C: MachineConfig,
->(
-    memory_source: &mut M,
-    tracer: &mut TR,
-    _mmu: &mut MMU,
+#[derive(Clone, Copy, Debug)]
+pub struct Blake2RoundFunctionWithXor;
+
+impl DelegationHandler for Blake2RoundFunctionWithXor {
+    fn name(&self) -> &str {
+        "blake2_round_function_with_xor"
+    }
+
+    fn num_words(&self) -> usize {
+        BLAKE2_ROUND_FUNCTION_ABI_NUM_MEM_ACCESSES
+    }
+
+    fn execute(
+        &self,
+        context: &mut dyn DelegationContext,
+        rs1_value: u32,
+    ) -> Result<(), DelegationError> {
+        blake2_round_function_with_xor(context, rs1_value)
+    }
+}
+
+pub fn blake2_round_function_with_xor(
+    context: &mut dyn DelegationContext,
     rs1_value: u32,
-    trap: &mut TrapReason,
-    proc_cycle: u32,
-    cycle_timestamp: u32,
-) {
+) -> Result<(), DelegationError> {
     // we consider high bits as the offset
-    assert_eq!(rs1_value as u16, 0, "unaligned");
-    let mem_offset = (rs1_value & 0xffff0000) as usize;
+    let mem_offset = memory_offset(rs1_value)?;
 
     // we perform batch accesses
     let mut accesses = [BatchAccessPartialData::Read { read_value: 0 };
@@ -39,10 +49,7 @@ pub fn blake2_round_function_with_xor<
     let mut initial_state = [0u32; 8];
     for (low_offset, dst) in (0..8).zip(initial_state.iter_mut()) {
         let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
-        let read_value = memory_source.get(address as u64, AccessType::RegWrite, trap);
-        if trap.is_a_trap() {
-            panic!("error in blake2s memory access");
-        }
+        let read_value = context.read(address as u64)?;
 
         *it.next().unwrap() = BatchAccessPartialData::Write {
             read_value: read_value,
@@ -54,10 +61,7 @@ pub fn blake2_round_function_with_xor<
     let mut extended_state = [0u32; 16];
     for (low_offset, dst) in (8..24usize).zip(extended_state.iter_mut()) {
         let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
-        let read_value = memory_source.get(address as u64, AccessType::RegWrite, trap);
-        if trap.is_a_trap() {
-            panic!("error in blake2s memory access");
-        }
+        let read_value = context.read(address as u64)?;
 
         *it.next().unwrap() = BatchAccessPartialData::Write {
             read_value: read_value,
@@ -69,10 +73,7 @@ pub fn blake2_round_function_with_xor<
     let mut message_block = [0u32; 16];
     for (low_offset, dst) in (24..40usize).zip(message_block.iter_mut()) {
         let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
-        let read_value = memory_source.get(address as u64, AccessType::RegWrite, trap);
-        if trap.is_a_trap() {
-            panic!("error in blake2s memory access");
-        }
+        let read_value = context.read(address as u64)?;
 
         *it.next().unwrap() = BatchAccessPartialData::Read { read_value };
         *dst = read_value;
@@ -80,24 +81,34 @@ pub fn blake2_round_function_with_xor<
 
     // bitmask controlling the permutation
     let address: usize = mem_offset + 40 * core::mem::size_of::<u32>();
-    let read_value = memory_source.get(address as u64, AccessType::RegWrite, trap);
-    if trap.is_a_trap() {
-        panic!("error in blake2s memory access");
-    }
+    let read_value = context.read(address as u64)?;
     *it.next().unwrap() = BatchAccessPartialData::Read { read_value };
     let permutation_bitmask = read_value;
-    assert!(permutation_bitmask.is_power_of_two());
     // bit to control final output
     let address: usize = mem_offset + 41 * core::mem::size_of::<u32>();
-    let read_value = memory_source.get(address as u64, AccessType::RegWrite, trap);
-    if trap.is_a_trap() {
-        panic!("error in blake2s memory access");
-    }
+    let read_value = context.read(address as u64)?;
     *it.next().unwrap() = BatchAccessPartialData::Read { read_value };
     let flush_bit = read_value;
-    assert!(flush_bit == 0 || flush_bit == 1);
 
     let permutation_index = permutation_bitmask.trailing_zeros() as usize;
+    if !permutation_bitmask.is_power_of_two() || permutation_index >= SIGMAS.len() {
+        return Err(DelegationError::InvalidArgument(format!(
+            "invalid permutation bitmask 0x{:08x}",
+            permutation_bitmask
+        )));
+    }
+    if flush_bit > 1 {
+        return Err(DelegationError::InvalidArgument(format!(
+            "invalid flush bit {}",
+            flush_bit
+        )));
+    }
+    if flush_bit == 1 && permutation_index != 6 && permutation_index != 9 {
+        return Err(DelegationError::InvalidArgument(format!(
+            "expected to support only 7 or 10 round invocations, but got a request to flush after {} rounds",
+            permutation_index + 1
+        )));
+    }
 
     if permutation_index == 0 {
         // we take values from the initial state, and put them into 0..8 range
@@ -124,7 +135,6 @@ pub fn blake2_round_function_with_xor<
     mixing_function(&mut extended_state, &message_block, sigma);
 
     if flush_bit == 1 {
-        assert!(permutation_index == 6 || permutation_index == 9, "expected to support only 7 or 10 round invocaitons, but got a request to flush after {} rounds", permutation_index + 1);
         // xor it and write into first 8 elements
         for i in 0..8 {
             initial_state[i] ^= extended_state[i];
@@ -146,10 +156,7 @@ pub fn blake2_round_function_with_xor<
 
         *written_value = value;
         let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
-        memory_source.set(address as u64, value, AccessType::RegWrite, trap);
-        if trap.is_a_trap() {
-            panic!("error in blake2s memory access");
-        }
+        context.write(address as u64, value)?;
     }
 
     // write back
@@ -168,17 +175,10 @@ pub fn blake2_round_function_with_xor<
 
         *written_value = value;
         let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
-        memory_source.set(address as u64, value, AccessType::RegWrite, trap);
-        if trap.is_a_trap() {
-            panic!("error in blake2s memory access");
-        }
+        context.write(address as u64, value)?;
     }
 
-    tracer.trace_batch_memory_access(
-        BLAKE2_ROUND_FUNCTION_WITH_XOR_ACCESS_ID,
-        (mem_offset >> 16) as u16,
-        &accesses,
-        proc_cycle,
-        cycle_timestamp,
-    );
+    context.trace_batch_memory_access((mem_offset >> 16) as u16, &accesses);
+
+    Ok(())
 }
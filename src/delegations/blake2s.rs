@@ -1,4 +1,4 @@
-use crate::cycle::{state::NON_DETERMINISM_CSR, status_registers::TrapReason};
+use crate::cycle::state::NON_DETERMINISM_CSR;
 use blake2s_u32::*;
 
 use super::*;
@@ -13,24 +13,33 @@ use super::*;
 pub const BLAKE2S_ABI_NUM_MEM_ACCESSES: usize = 8 + 2 + 16;
 pub const BLAKE2S_ACCESS_ID: u32 = NON_DETERMINISM_CSR + 1;
 
-pub fn blake2s_round_function<
-    M: MemorySource,
-    TR: Tracer<C>,
-    MMU: MMUImplementation<M, TR, C>,
-    C: MachineConfig,
-    const REDUCED_ROUNDS: bool,
->(
-    memory_source: &mut M,
-    tracer: &mut TR,
-    _mmu: &mut MMU,
+#[derive(Clone, Copy, Debug)]
+pub struct Blake2s;
+
+impl DelegationHandler for Blake2s {
+    fn name(&self) -> &str {
+        "blake2s"
+    }
+
+    fn num_words(&self) -> usize {
+        BLAKE2S_ABI_NUM_MEM_ACCESSES
+    }
+
+    fn execute(
+        &self,
+        context: &mut dyn DelegationContext,
+        rs1_value: u32,
+    ) -> Result<(), DelegationError> {
+        blake2s_round_function::<false>(context, rs1_value)
+    }
+}
+
+pub fn blake2s_round_function<const REDUCED_ROUNDS: bool>(
+    context: &mut dyn DelegationContext,
     rs1_value: u32,
-    trap: &mut TrapReason,
-    proc_cycle: u32,
-    cycle_timestamp: u32,
-) {
+) -> Result<(), DelegationError> {
     // we consider high bits as the offset
-    assert_eq!(rs1_value as u16, 0, "unaligned");
-    let mem_offset = (rs1_value & 0xffff0000) as usize;
+    let mem_offset = memory_offset(rs1_value)?;
 
     // we perform batch accesses
     let mut accesses =
@@ -40,10 +49,7 @@ pub fn blake2s_round_function<
     let mut extended_state = [0u32; 16];
     for low_offset in 0..8 {
         let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
-        let read_value = memory_source.get(address as u64, AccessType::RegWrite, trap);
-        if trap.is_a_trap() {
-            panic!("error in blake2s memory access");
-        }
+        let read_value = context.read(address as u64)?;
 
         *it.next().unwrap() = BatchAccessPartialData::Write {
             read_value: read_value,
@@ -54,10 +60,7 @@ pub fn blake2s_round_function<
 
     for (low_offset, dst_index) in (8..10usize).zip([12, 14].into_iter()) {
         let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
-        let read_value = memory_source.get(address as u64, AccessType::RegWrite, trap);
-        if trap.is_a_trap() {
-            panic!("error in blake2s memory access");
-        }
+        let read_value = context.read(address as u64)?;
 
         *it.next().unwrap() = BatchAccessPartialData::Read { read_value };
         extended_state[dst_index] = read_value;
@@ -75,10 +78,7 @@ pub fn blake2s_round_function<
     let mut message_block = [0u32; 16];
     for (low_offset, dst) in (10..26usize).zip(message_block.iter_mut()) {
         let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
-        let read_value = memory_source.get(address as u64, AccessType::RegWrite, trap);
-        if trap.is_a_trap() {
-            panic!("error in blake2s memory access");
-        }
+        let read_value = context.read(address as u64)?;
 
         *it.next().unwrap() = BatchAccessPartialData::Read { read_value };
         *dst = read_value;
@@ -118,17 +118,10 @@ pub fn blake2s_round_function<
 
         *written_value = value;
         let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
-        memory_source.set(address as u64, value, AccessType::RegWrite, trap);
-        if trap.is_a_trap() {
-            panic!("error in blake2s memory access");
-        }
+        context.write(address as u64, value)?;
     }
 
-    tracer.trace_batch_memory_access(
-        BLAKE2S_ACCESS_ID,
-        (mem_offset >> 16) as u16,
-        &accesses,
-        proc_cycle,
-        cycle_timestamp,
-    );
+    context.trace_batch_memory_access((mem_offset >> 16) as u16, &accesses);
+
+    Ok(())
 }
@@ -0,0 +1,263 @@
+use crate::cycle::state::NON_DETERMINISM_CSR;
+
+use super::u256::{add_with_carry, less_than, mul_mod, sub_with_borrow, U256};
+use super::*;
+
+// elliptic curve point operation binary interface is
+// - 8xu32 words of x1 and 8xu32 words of y1, the first point. the result is written here
+// - 8xu32 words of x2 and 8xu32 words of y2, the second point, only used by the addition
+// - one u32 bitmask, that will determine the operation (see `EC_OP_*`)
+// coordinates are affine, least significant word first, and must be reduced modulo the field
+// prime. the point at infinity can't be represented, so neither an input nor a result may be it:
+// adding a point to its negation and doubling a point with y = 0 are not supported.
+// adding a point to itself doubles it
+
+pub const EC_ABI_NUM_MEM_ACCESSES: usize = 16 + 16 + 1;
+pub const SECP256K1_ACCESS_ID: u32 = NON_DETERMINISM_CSR + 7;
+pub const BN254_G1_ACCESS_ID: u32 = NON_DETERMINISM_CSR + 8;
+
+/// `p1 = p1 + p2`
+pub const EC_OP_ADD: u32 = 1 << 0;
+/// `p1 = 2 * p1`
+pub const EC_OP_DOUBLE: u32 = 1 << 1;
+
+/// Short Weierstrass curve `y^2 = x^3 + b` over a prime field.
+#[derive(Clone, Copy, Debug)]
+pub struct Curve {
+    pub name: &'static str,
+    pub modulus: U256,
+    pub b: u32,
+}
+
+pub const SECP256K1: Curve = Curve {
+    name: "secp256k1",
+    modulus: [
+        0xfffffc2f, 0xfffffffe, 0xffffffff, 0xffffffff, 0xffffffff, 0xffffffff, 0xffffffff,
+        0xffffffff,
+    ],
+    b: 7,
+};
+
+pub const BN254_G1: Curve = Curve {
+    name: "bn254_g1",
+    modulus: [
+        0xd87cfd47, 0x3c208c16, 0x6871ca8d, 0x97816a91, 0x8181585d, 0xb85045b6, 0xe131a029,
+        0x30644e72,
+    ],
+    b: 3,
+};
+
+pub type AffinePoint = (U256, U256);
+
+#[derive(Clone, Copy, Debug)]
+pub struct Secp256k1;
+
+impl DelegationHandler for Secp256k1 {
+    fn name(&self) -> &str {
+        SECP256K1.name
+    }
+
+    fn num_words(&self) -> usize {
+        EC_ABI_NUM_MEM_ACCESSES
+    }
+
+    fn execute(
+        &self,
+        context: &mut dyn DelegationContext,
+        rs1_value: u32,
+    ) -> Result<(), DelegationError> {
+        ec_round_function(&SECP256K1, context, rs1_value)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Bn254G1;
+
+impl DelegationHandler for Bn254G1 {
+    fn name(&self) -> &str {
+        BN254_G1.name
+    }
+
+    fn num_words(&self) -> usize {
+        EC_ABI_NUM_MEM_ACCESSES
+    }
+
+    fn execute(
+        &self,
+        context: &mut dyn DelegationContext,
+        rs1_value: u32,
+    ) -> Result<(), DelegationError> {
+        ec_round_function(&BN254_G1, context, rs1_value)
+    }
+}
+
+impl Curve {
+    fn add(&self, a: &U256, b: &U256) -> U256 {
+        let (sum, carry) = add_with_carry(a, b, false);
+        if carry || !less_than(&sum, &self.modulus) {
+            sub_with_borrow(&sum, &self.modulus, false).0
+        } else {
+            sum
+        }
+    }
+
+    fn sub(&self, a: &U256, b: &U256) -> U256 {
+        let (difference, borrow) = sub_with_borrow(a, b, false);
+        if borrow {
+            add_with_carry(&difference, &self.modulus, false).0
+        } else {
+            difference
+        }
+    }
+
+    fn mul(&self, a: &U256, b: &U256) -> U256 {
+        mul_mod(a, b, &self.modulus)
+    }
+
+    // Fermat's little theorem, a^(p - 2)
+    fn inverse(&self, a: &U256) -> U256 {
+        assert!(a.iter().any(|word| *word != 0), "inverse of zero");
+        let mut two = [0u32; 8];
+        two[0] = 2;
+        let exponent = sub_with_borrow(&self.modulus, &two, false).0;
+
+        let mut result = [0u32; 8];
+        result[0] = 1;
+        for bit in (0..256).rev() {
+            result = self.mul(&result, &result);
+            if (exponent[bit / 32] >> (bit % 32)) & 1 != 0 {
+                result = self.mul(&result, a);
+            }
+        }
+
+        result
+    }
+
+    // completes the addition or doubling with the slope of the line through the points
+    fn with_slope(&self, lambda: &U256, p1: &AffinePoint, x2: &U256) -> AffinePoint {
+        let x3 = self.sub(&self.sub(&self.mul(lambda, lambda), &p1.0), x2);
+        let y3 = self.sub(&self.mul(lambda, &self.sub(&p1.0, &x3)), &p1.1);
+
+        (x3, y3)
+    }
+
+    pub fn double(&self, p: &AffinePoint) -> AffinePoint {
+        let (x, y) = p;
+        assert!(
+            y.iter().any(|word| *word != 0),
+            "doubling a point of order two"
+        );
+
+        // a = 0, so lambda = 3 * x^2 / (2 * y)
+        let x_squared = self.mul(x, x);
+        let numerator = self.add(&self.add(&x_squared, &x_squared), &x_squared);
+        let lambda = self.mul(&numerator, &self.inverse(&self.add(y, y)));
+
+        self.with_slope(&lambda, p, x)
+    }
+
+    pub fn add_points(&self, p1: &AffinePoint, p2: &AffinePoint) -> AffinePoint {
+        if p1.0 == p2.0 {
+            assert_eq!(p1.1, p2.1, "adding a point to its negation");
+            return self.double(p1);
+        }
+
+        let lambda = self.mul(
+            &self.sub(&p2.1, &p1.1),
+            &self.inverse(&self.sub(&p2.0, &p1.0)),
+        );
+
+        self.with_slope(&lambda, p1, &p2.0)
+    }
+
+    pub fn is_on_curve(&self, p: &AffinePoint) -> bool {
+        let (x, y) = p;
+        let mut b = [0u32; 8];
+        b[0] = self.b;
+
+        self.mul(y, y) == self.add(&self.mul(&self.mul(x, x), x), &b)
+    }
+}
+
+pub fn ec_round_function(
+    curve: &Curve,
+    context: &mut dyn DelegationContext,
+    rs1_value: u32,
+) -> Result<(), DelegationError> {
+    // we consider high bits as the offset
+    let mem_offset = memory_offset(rs1_value)?;
+
+    let mut words = [0u32; EC_ABI_NUM_MEM_ACCESSES];
+    for (low_offset, dst) in words.iter_mut().enumerate() {
+        let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
+        *dst = context.read(address as u64)?;
+    }
+
+    let p1: AffinePoint = (
+        words[0..8].try_into().unwrap(),
+        words[8..16].try_into().unwrap(),
+    );
+    let p2: AffinePoint = (
+        words[16..24].try_into().unwrap(),
+        words[24..32].try_into().unwrap(),
+    );
+    let op = words[32];
+
+    // reject inputs the host side arithmetic would assert on
+    let invalid = |reason: &str| {
+        Err(DelegationError::InvalidArgument(format!(
+            "{} {}",
+            curve.name, reason
+        )))
+    };
+    let coordinates = [&p1.0, &p1.1, &p2.0, &p2.1];
+    if coordinates
+        .iter()
+        .any(|coordinate| !less_than(coordinate, &curve.modulus))
+    {
+        return invalid("coordinate is not reduced");
+    }
+    let doubling = match op {
+        EC_OP_ADD => p1 == p2,
+        EC_OP_DOUBLE => true,
+        _ => return invalid(&format!("unknown operation 0x{:08x}", op)),
+    };
+    if op == EC_OP_ADD && p1.0 == p2.0 && p1.1 != p2.1 {
+        return invalid("addition of a point to its negation");
+    }
+    if doubling && p1.1 == [0u32; 8] {
+        return invalid("doubling of a point of order two");
+    }
+
+    let (x3, y3) = if op == EC_OP_ADD {
+        curve.add_points(&p1, &p2)
+    } else {
+        curve.double(&p1)
+    };
+
+    // the first point is written back, the second one and the operation are only read
+    let mut accesses = [BatchAccessPartialData::Read { read_value: 0 }; EC_ABI_NUM_MEM_ACCESSES];
+    for (low_offset, access) in accesses.iter_mut().enumerate() {
+        let read_value = words[low_offset];
+        if low_offset >= 16 {
+            *access = BatchAccessPartialData::Read { read_value };
+            continue;
+        }
+
+        let written_value = if low_offset < 8 {
+            x3[low_offset]
+        } else {
+            y3[low_offset - 8]
+        };
+        *access = BatchAccessPartialData::Write {
+            read_value,
+            written_value,
+        };
+        let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
+        context.write(address as u64, written_value)?;
+    }
+
+    context.trace_batch_memory_access((mem_offset >> 16) as u16, &accesses);
+
+    Ok(())
+}
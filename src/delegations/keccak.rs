@@ -0,0 +1,150 @@
+use crate::cycle::state::NON_DETERMINISM_CSR;
+
+use super::*;
+
+// keccak-f[1600] binary interface is
+// - 50xu32 words of the state, lane `i` being words `2 * i` (low half) and `2 * i + 1` (high half)
+// at the end we will overwrite all 50 words with the permuted state
+
+pub const KECCAK_F1600_ABI_NUM_MEM_ACCESSES: usize = 50;
+pub const KECCAK_F1600_ACCESS_ID: u32 = NON_DETERMINISM_CSR + 4;
+
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808a,
+    0x8000000080008000,
+    0x000000000000808b,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008a,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000a,
+    0x000000008000808b,
+    0x800000000000008b,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800a,
+    0x800000008000000a,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+// rotation offsets and destinations of the rho and pi steps, walking the lanes from (1, 0)
+const RHO_OFFSETS: [u32; 24] = [
+    1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44,
+];
+const PI_LANES: [usize; 24] = [
+    10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1,
+];
+
+#[derive(Clone, Copy, Debug)]
+pub struct KeccakF1600;
+
+impl DelegationHandler for KeccakF1600 {
+    fn name(&self) -> &str {
+        "keccak_f1600"
+    }
+
+    fn num_words(&self) -> usize {
+        KECCAK_F1600_ABI_NUM_MEM_ACCESSES
+    }
+
+    fn execute(
+        &self,
+        context: &mut dyn DelegationContext,
+        rs1_value: u32,
+    ) -> Result<(), DelegationError> {
+        keccak_f1600_round_function(context, rs1_value)
+    }
+}
+
+/// All 24 rounds of the permutation, with lane `x + 5 * y` at index `x + 5 * y`.
+pub fn keccak_f1600(state: &mut [u64; 25]) {
+    for round_constant in ROUND_CONSTANTS {
+        // theta
+        let mut columns = [0u64; 5];
+        for x in 0..5 {
+            columns[x] = state[x] ^ state[x + 5] ^ state[x + 10] ^ state[x + 15] ^ state[x + 20];
+        }
+        for x in 0..5 {
+            let d = columns[(x + 4) % 5] ^ columns[(x + 1) % 5].rotate_left(1);
+            for y in 0..5 {
+                state[x + 5 * y] ^= d;
+            }
+        }
+
+        // rho and pi
+        let mut carry = state[1];
+        for (offset, lane) in RHO_OFFSETS.into_iter().zip(PI_LANES) {
+            let next = state[lane];
+            state[lane] = carry.rotate_left(offset);
+            carry = next;
+        }
+
+        // chi
+        for y in 0..5 {
+            let row: [u64; 5] = state[5 * y..5 * y + 5].try_into().unwrap();
+            for x in 0..5 {
+                state[x + 5 * y] = row[x] ^ (!row[(x + 1) % 5] & row[(x + 2) % 5]);
+            }
+        }
+
+        // iota
+        state[0] ^= round_constant;
+    }
+}
+
+pub fn keccak_f1600_round_function(
+    context: &mut dyn DelegationContext,
+    rs1_value: u32,
+) -> Result<(), DelegationError> {
+    // we consider high bits as the offset
+    let mem_offset = memory_offset(rs1_value)?;
+
+    // we perform batch accesses
+    let mut accesses = [BatchAccessPartialData::Write {
+        read_value: 0,
+        written_value: 0,
+    }; KECCAK_F1600_ABI_NUM_MEM_ACCESSES];
+
+    let mut state = [0u64; 25];
+    for (low_offset, access) in accesses.iter_mut().enumerate() {
+        let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
+        let read_value = context.read(address as u64)?;
+
+        *access = BatchAccessPartialData::Write {
+            read_value,
+            written_value: 0,
+        };
+        state[low_offset / 2] |= (read_value as u64) << (32 * (low_offset % 2));
+    }
+
+    keccak_f1600(&mut state);
+
+    // write back
+    for (low_offset, access) in accesses.iter_mut().enumerate() {
+        let BatchAccessPartialData::Write {
+            read_value: _,
+            written_value,
+        } = access
+        else {
+            unreachable!()
+        };
+
+        let value = (state[low_offset / 2] >> (32 * (low_offset % 2))) as u32;
+        *written_value = value;
+        let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
+        context.write(address as u64, value)?;
+    }
+
+    context.trace_batch_memory_access((mem_offset >> 16) as u16, &accesses);
+
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use crate::cycle::{state::NON_DETERMINISM_CSR, status_registers::TrapReason};
+
+use super::*;
+
+// memory copy binary interface is a descriptor of
+// - 1xu32 word of the operation, copy or fill
+// - 1xu32 word of the destination address
+// - 1xu32 word of the source address for a copy, or of the value for a fill
+// - 1xu32 word of the length, in words
+// the descriptor is only read, the destination range is overwritten
+//
+// both addresses are byte addresses of word aligned ranges, and a copy behaves as memmove: all the
+// source words are read before the first destination word is written. ranges longer than
+// `MEMORY_COPY_MAX_WORDS` are split by the guest into several calls
+
+pub const MEMORY_COPY_DESCRIPTOR_NUM_WORDS: usize = 4;
+pub const MEMORY_COPY_ACCESS_ID: u32 = NON_DETERMINISM_CSR + 10;
+
+/// Longest range a single call copies or fills, 16 KiB.
+pub const MEMORY_COPY_MAX_WORDS: u32 = 1 << 12;
+
+/// Copies the source range to the destination one.
+pub const MEMORY_OP_COPY: u32 = 1 << 0;
+/// Sets every word of the destination range to the value.
+pub const MEMORY_OP_FILL: u32 = 1 << 1;
+
+#[derive(Clone, Copy, Debug)]
+pub struct MemoryCopy;
+
+impl DelegationHandler for MemoryCopy {
+    fn name(&self) -> &str {
+        "memory_copy"
+    }
+
+    fn num_words(&self) -> usize {
+        MEMORY_COPY_DESCRIPTOR_NUM_WORDS
+    }
+
+    fn execute(
+        &self,
+        context: &mut dyn DelegationContext,
+        rs1_value: u32,
+    ) -> Result<(), DelegationError> {
+        memory_copy_round_function(context, rs1_value)
+    }
+}
+
+// Checks a range of `num_words` words starting at `address` is word aligned and doesn't wrap
+// around the address space. `trap` is the one of a misaligned access to the range.
+fn check_range(address: u32, num_words: u32, trap: TrapReason) -> Result<(), DelegationError> {
+    if address % 4 != 0 {
+        return Err(DelegationError::Misaligned { address, trap });
+    }
+    if address as u64 + num_words as u64 * 4 > 1 << 32 {
+        return Err(DelegationError::InvalidArgument(format!(
+            "range of {} words at 0x{:08x} wraps around the address space",
+            num_words, address
+        )));
+    }
+
+    Ok(())
+}
+
+pub fn memory_copy_round_function(
+    context: &mut dyn DelegationContext,
+    rs1_value: u32,
+) -> Result<(), DelegationError> {
+    // we consider high bits as the offset
+    let mem_offset = memory_offset(rs1_value)?;
+
+    let mut descriptor = [0u32; MEMORY_COPY_DESCRIPTOR_NUM_WORDS];
+    for (low_offset, dst) in descriptor.iter_mut().enumerate() {
+        let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
+        *dst = context.read(address as u64)?;
+    }
+    let [op, destination, source, num_words] = descriptor;
+
+    if num_words > MEMORY_COPY_MAX_WORDS {
+        return Err(DelegationError::InvalidArgument(format!(
+            "length of {} words is over the limit of {}",
+            num_words, MEMORY_COPY_MAX_WORDS
+        )));
+    }
+    check_range(
+        destination,
+        num_words,
+        TrapReason::StoreOrAMOAddressMisaligned,
+    )?;
+
+    // everything is read before anything is written, so a fault leaves the memory unchanged
+    let mut source_accesses = Vec::new();
+    let values = match op {
+        MEMORY_OP_COPY => {
+            check_range(source, num_words, TrapReason::LoadAddressMisaligned)?;
+            let mut values = Vec::with_capacity(num_words as usize);
+            for idx in 0..num_words as u64 {
+                values.push(context.read(source as u64 + idx * 4)?);
+            }
+            source_accesses = values
+                .iter()
+                .map(|&read_value| BatchAccessPartialData::Read { read_value })
+                .collect();
+
+            values
+        }
+        MEMORY_OP_FILL => vec![source; num_words as usize],
+        _ => {
+            return Err(DelegationError::InvalidArgument(format!(
+                "unknown memory operation 0x{:08x}",
+                op
+            )))
+        }
+    };
+
+    let mut destination_accesses = Vec::with_capacity(num_words as usize);
+    for (idx, &written_value) in values.iter().enumerate() {
+        // the destination is only read to be traced, a fault there is one of the write
+        let read_value = context
+            .read(destination as u64 + idx as u64 * 4)
+            .map_err(|error| match error {
+                DelegationError::AccessFault { address, .. } => DelegationError::AccessFault {
+                    address,
+                    trap: TrapReason::StoreOrAMOAccessFault,
+                },
+                error => error,
+            })?;
+        destination_accesses.push(BatchAccessPartialData::Write {
+            read_value,
+            written_value,
+        });
+    }
+    for (idx, &written_value) in values.iter().enumerate() {
+        context.write(destination as u64 + idx as u64 * 4, written_value)?;
+    }
+
+    let descriptor_accesses =
+        descriptor.map(|read_value| BatchAccessPartialData::Read { read_value });
+    context.trace_batch_memory_access((mem_offset >> 16) as u16, &descriptor_accesses);
+    if !source_accesses.is_empty() {
+        context.trace_batch_memory_access_at(source as u64, &source_accesses);
+    }
+    if !destination_accesses.is_empty() {
+        context.trace_batch_memory_access_at(destination as u64, &destination_accesses);
+    }
+
+    Ok(())
+}
@@ -1,23 +1,461 @@
-use blake2s::blake2s_round_function;
-use blake2s::BLAKE2S_ACCESS_ID;
+//! Delegations are precompiles the guest invokes by writing to their CSR. They are looked up at
+//! runtime in a `DelegationRegistry`, so the same binary can run a program with and without them.
+//!
+//! The Blake2s delegations need the `delegation` feature, as they're built on the optional
+//! `blake2s_u32` crate. The feature also makes `RiscV32State::cycle` run the builtin delegations,
+//! without it only `Simulator` and `cycle_ext` with a registry do. CI builds and tests both with
+//! and without it.
 
-use blake2_round_function::blake2_round_function;
-use blake2_round_function::BLAKE2_ROUND_FUNCTION_ACCESS_ID;
-
-use blake2_round_function_with_final_xor::blake2_round_function_with_xor;
-use blake2_round_function_with_final_xor::BLAKE2_ROUND_FUNCTION_WITH_XOR_ACCESS_ID;
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display};
+use std::marker::PhantomData;
+use std::sync::{Arc, LazyLock};
 
 use crate::abstractions::csr_processor::CustomCSRProcessor;
 use crate::abstractions::memory::*;
 use crate::abstractions::tracer::*;
+use crate::cycle::state::NON_DETERMINISM_CSR;
 use crate::cycle::status_registers::TrapReason;
 use crate::cycle::MachineConfig;
 use crate::mmu::*;
 
+#[cfg(feature = "delegation")]
 pub mod blake2_round_function;
+#[cfg(feature = "delegation")]
 pub mod blake2_round_function_with_final_xor;
+#[cfg(feature = "delegation")]
 pub mod blake2s;
+pub mod ec;
+pub mod keccak;
+pub mod memory_copy;
+pub mod poseidon2;
+pub mod sha256;
+pub mod u256;
+
+/// Reading this CSR tells the guest which delegations are registered: bit `i` of the value is set
+/// if CSR `0x7c0 + 32 * rs1 + i` has a handler. Writes are ignored.
+pub const DELEGATION_DISCOVERY_CSR: u32 = 0x7ff;
+
+/// Why a delegation refused to execute. The guest takes the trap of `DelegationError::trap`
+/// instead, and the memory is left unchanged.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DelegationError {
+    /// The words of a delegation start at a multiple of 64 KiB, so the low half of rs1 must be 0.
+    /// Addresses the delegation reads from its words must be word aligned. `trap` tells whether
+    /// the address was to be read from or written to.
+    Misaligned { address: u32, trap: TrapReason },
+    /// The memory trapped on one of the words.
+    AccessFault { address: u32, trap: TrapReason },
+    /// The words hold an input the delegation can't process.
+    InvalidArgument(String),
+}
+
+impl DelegationError {
+    pub fn trap(&self) -> TrapReason {
+        match self {
+            Self::Misaligned { trap, .. } | Self::AccessFault { trap, .. } => *trap,
+            Self::InvalidArgument(_) => TrapReason::IllegalInstruction,
+        }
+    }
+}
+
+impl Display for DelegationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Misaligned { address, trap } | Self::AccessFault { address, trap } => {
+                write!(f, "{:?} at 0x{:08x}", trap, address)
+            }
+            Self::InvalidArgument(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// A delegation that trapped, as reported by `DelegationRegistry::take_faults`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DelegationFault {
+    pub csr_index: u32,
+    pub delegation: String,
+    pub rs1_value: u32,
+    pub cycle: u32,
+    pub error: DelegationError,
+}
+
+impl Display for DelegationFault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Delegation {} (CSR 0x{:03x}) with rs1 0x{:08x} trapped at cycle {}: {}",
+            self.delegation, self.csr_index, self.rs1_value, self.cycle, self.error
+        )
+    }
+}
+
+/// The address of the first word of a delegation, taken from the high half of rs1.
+pub fn memory_offset(rs1_value: u32) -> Result<usize, DelegationError> {
+    if rs1_value as u16 != 0 {
+        return Err(DelegationError::Misaligned {
+            address: rs1_value,
+            trap: TrapReason::LoadAddressMisaligned,
+        });
+    }
+
+    Ok((rs1_value & 0xffff0000) as usize)
+}
+
+/// Memory and tracer of the cycle the delegation is executed in.
+pub trait DelegationContext {
+    fn read(&mut self, phys_address: u64) -> Result<u32, DelegationError>;
+
+    fn write(&mut self, phys_address: u64, value: u32) -> Result<(), DelegationError>;
+
+    /// Reports the accesses the delegation made to the words starting at
+    /// `phys_address_high << 16`.
+    fn trace_batch_memory_access(
+        &mut self,
+        phys_address_high: u16,
+        accesses: &[BatchAccessPartialData],
+    );
+
+    /// Reports further accesses, to the words starting at `phys_address`, after the ones of
+    /// `trace_batch_memory_access`.
+    fn trace_batch_memory_access_at(
+        &mut self,
+        phys_address: u64,
+        accesses: &[BatchAccessPartialData],
+    );
+}
+
+/// A delegation, executed when the guest writes `rs1_value` to its CSR.
+pub trait DelegationHandler: Debug + Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Number of words starting at `memory_offset(rs1_value)` the delegation accesses. They are
+    /// checked to be accessible before `execute` is called.
+    fn num_words(&self) -> usize;
+
+    /// Reports invalid input as an error before writing anything to memory.
+    fn execute(
+        &self,
+        context: &mut dyn DelegationContext,
+        rs1_value: u32,
+    ) -> Result<(), DelegationError>;
+}
+
+struct CycleContext<'a, M, TR, C> {
+    memory_source: &'a mut M,
+    tracer: &'a mut TR,
+    csr_index: u32,
+    proc_cycle: u32,
+    cycle_timestamp: u32,
+    _marker: PhantomData<C>,
+}
+
+impl<M: MemorySource, TR: Tracer<C>, C: MachineConfig> DelegationContext
+    for CycleContext<'_, M, TR, C>
+{
+    fn read(&mut self, phys_address: u64) -> Result<u32, DelegationError> {
+        let mut trap = TrapReason::NoTrap;
+        let value = self
+            .memory_source
+            .get(phys_address, AccessType::RegWrite, &mut trap);
+        if trap.is_a_trap() {
+            return Err(DelegationError::AccessFault {
+                address: phys_address as u32,
+                trap,
+            });
+        }
+
+        Ok(value)
+    }
+
+    fn write(&mut self, phys_address: u64, value: u32) -> Result<(), DelegationError> {
+        let mut trap = TrapReason::NoTrap;
+        self.memory_source
+            .set(phys_address, value, AccessType::RegWrite, &mut trap);
+        if trap.is_a_trap() {
+            return Err(DelegationError::AccessFault {
+                address: phys_address as u32,
+                trap,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn trace_batch_memory_access(
+        &mut self,
+        phys_address_high: u16,
+        accesses: &[BatchAccessPartialData],
+    ) {
+        self.tracer.trace_batch_memory_access(
+            self.csr_index,
+            phys_address_high,
+            accesses,
+            self.proc_cycle,
+            self.cycle_timestamp,
+        );
+    }
+
+    fn trace_batch_memory_access_at(
+        &mut self,
+        phys_address: u64,
+        accesses: &[BatchAccessPartialData],
+    ) {
+        self.tracer.trace_batch_memory_access_at(
+            self.csr_index,
+            phys_address,
+            accesses,
+            self.proc_cycle,
+            self.cycle_timestamp,
+        );
+    }
+}
+
+/// Delegation handlers by CSR index.
+///
+/// A delegation that rejects its input is recorded for `take_faults`. If the machine handles
+/// exceptions the guest also takes the trap of the error, otherwise the instruction retires
+/// without any effect and it is up to the caller to stop the run, as `Simulator` does.
+#[derive(Clone, Debug, Default)]
+pub struct DelegationRegistry {
+    handlers: BTreeMap<u32, Arc<dyn DelegationHandler>>,
+    faults: Vec<DelegationFault>,
+}
+
+static BUILTIN_DELEGATIONS: LazyLock<DelegationRegistry> = LazyLock::new(|| {
+    let mut registry = DelegationRegistry::new();
+    registry.register(
+        keccak::KECCAK_F1600_ACCESS_ID,
+        Arc::new(keccak::KeccakF1600),
+    );
+    registry.register(sha256::SHA256_ACCESS_ID, Arc::new(sha256::Sha256));
+    registry.register(u256::U256_ACCESS_ID, Arc::new(u256::U256Arithmetic));
+    registry.register(ec::SECP256K1_ACCESS_ID, Arc::new(ec::Secp256k1));
+    registry.register(ec::BN254_G1_ACCESS_ID, Arc::new(ec::Bn254G1));
+    registry.register(
+        poseidon2::POSEIDON2_ACCESS_ID,
+        Arc::new(poseidon2::Poseidon2 {
+            constants: poseidon2::Poseidon2Constants::plonky3(),
+        }),
+    );
+    registry.register(
+        memory_copy::MEMORY_COPY_ACCESS_ID,
+        Arc::new(memory_copy::MemoryCopy),
+    );
+
+    #[cfg(feature = "delegation")]
+    {
+        registry.register(blake2s::BLAKE2S_ACCESS_ID, Arc::new(blake2s::Blake2s));
+        registry.register(
+            blake2_round_function::BLAKE2_ROUND_FUNCTION_ACCESS_ID,
+            Arc::new(blake2_round_function::Blake2RoundFunction),
+        );
+        registry.register(
+            blake2_round_function_with_final_xor::BLAKE2_ROUND_FUNCTION_WITH_XOR_ACCESS_ID,
+            Arc::new(blake2_round_function_with_final_xor::Blake2RoundFunctionWithXor),
+        );
+    }
+
+    registry
+});
+
+impl DelegationRegistry {
+    /// Registry without any delegations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The delegations compiled into the simulator: keccak, sha256, u256, the curve ones,
+    /// Poseidon2 with Plonky3's constants and memory copy, and the blake2 ones with the
+    /// `delegation` feature.
+    pub fn builtin() -> Self {
+        BUILTIN_DELEGATIONS.clone()
+    }
+
+    /// Registers the handler of a custom CSR, between the non-determinism and the discovery
+    /// ones.
+    pub fn register(&mut self, csr_index: u32, handler: Arc<dyn DelegationHandler>) {
+        assert!(
+            csr_index > NON_DETERMINISM_CSR && csr_index < DELEGATION_DISCOVERY_CSR,
+            "CSR 0x{:03x} can't be used for a delegation",
+            csr_index
+        );
+        let previous = self.handlers.insert(csr_index, handler);
+        assert!(
+            previous.is_none(),
+            "CSR 0x{:03x} already has a delegation",
+            csr_index
+        );
+    }
+
+    pub fn remove(&mut self, csr_index: u32) -> Option<Arc<dyn DelegationHandler>> {
+        self.handlers.remove(&csr_index)
+    }
+
+    pub fn get(&self, csr_index: u32) -> Option<&dyn DelegationHandler> {
+        self.handlers.get(&csr_index).map(|handler| &**handler)
+    }
+
+    pub fn name(&self, csr_index: u32) -> Option<&str> {
+        self.get(csr_index).map(|handler| handler.name())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+
+    /// The registered CSR indices with their handlers, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &dyn DelegationHandler)> {
+        self.handlers
+            .iter()
+            .map(|(csr_index, handler)| (*csr_index, &**handler))
+    }
+
+    /// The value the guest reads from `DELEGATION_DISCOVERY_CSR` with `window` in rs1.
+    pub fn discovery_mask(&self, window: u32) -> u32 {
+        let Some(base) = window
+            .checked_mul(32)
+            .and_then(|offset| offset.checked_add(NON_DETERMINISM_CSR))
+        else {
+            return 0;
+        };
+
+        self.handlers
+            .range(base..base.saturating_add(32))
+            .fold(0, |mask, (csr_index, _)| mask | 1 << (csr_index - base))
+    }
 
+    #[inline(always)]
+    fn read_csr(&self, csr_index: u32, rs1_value: u32, ret_val: &mut u32, trap: &mut TrapReason) {
+        *ret_val = 0;
+        if csr_index == DELEGATION_DISCOVERY_CSR {
+            *ret_val = self.discovery_mask(rs1_value);
+        } else if !self.handlers.contains_key(&csr_index) {
+            *trap = TrapReason::IllegalInstruction;
+        }
+    }
+
+    /// The delegations that faulted since the last call, in order.
+    pub fn take_faults(&mut self) -> Vec<DelegationFault> {
+        std::mem::take(&mut self.faults)
+    }
+
+    #[inline(always)]
+    fn write_csr(
+        &self,
+        context: &mut dyn DelegationContext,
+        csr_index: u32,
+        rs1_value: u32,
+        trap: &mut TrapReason,
+        proc_cycle: u32,
+    ) -> Option<DelegationFault> {
+        // faults are returned rather than trapped, it's up to the caller to raise them
+        if csr_index == DELEGATION_DISCOVERY_CSR {
+            return None;
+        }
+
+        let Some(handler) = self.handlers.get(&csr_index) else {
+            *trap = TrapReason::IllegalInstruction;
+            return None;
+        };
+
+        let error = execute_checked(&**handler, context, rs1_value).err()?;
+
+        Some(DelegationFault {
+            csr_index,
+            delegation: handler.name().to_string(),
+            rs1_value,
+            cycle: proc_cycle,
+            error,
+        })
+    }
+}
+
+fn execute_checked(
+    handler: &dyn DelegationHandler,
+    context: &mut dyn DelegationContext,
+    rs1_value: u32,
+) -> Result<(), DelegationError> {
+    // check the whole range first, so a fault doesn't leave the memory partially written
+    let mem_offset = memory_offset(rs1_value)?;
+    for low_offset in 0..handler.num_words() {
+        let address = mem_offset + low_offset * core::mem::size_of::<u32>();
+        context.read(address as u64)?;
+    }
+
+    handler.execute(context, rs1_value)
+}
+
+impl CustomCSRProcessor for DelegationRegistry {
+    #[inline(always)]
+    fn process_read<
+        M: MemorySource,
+        TR: Tracer<C>,
+        MMU: MMUImplementation<M, TR, C>,
+        C: MachineConfig,
+    >(
+        &mut self,
+        _memory_source: &mut M,
+        _tracer: &mut TR,
+        _mmu: &mut MMU,
+        csr_index: u32,
+        rs1_value: u32,
+        _zimm: u32,
+        ret_val: &mut u32,
+        trap: &mut TrapReason,
+        _proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+        self.read_csr(csr_index, rs1_value, ret_val, trap);
+    }
+
+    #[inline(always)]
+    fn process_write<
+        M: MemorySource,
+        TR: Tracer<C>,
+        MMU: MMUImplementation<M, TR, C>,
+        C: MachineConfig,
+    >(
+        &mut self,
+        memory_source: &mut M,
+        tracer: &mut TR,
+        _mmu: &mut MMU,
+        csr_index: u32,
+        rs1_value: u32,
+        _zimm: u32,
+        trap: &mut TrapReason,
+        proc_cycle: u32,
+        cycle_timestamp: u32,
+    ) {
+        let Some(fault) = self.write_csr(
+            &mut CycleContext::<M, TR, C> {
+                memory_source,
+                tracer,
+                csr_index,
+                proc_cycle,
+                cycle_timestamp,
+                _marker: PhantomData,
+            },
+            csr_index,
+            rs1_value,
+            trap,
+            proc_cycle,
+        ) else {
+            return;
+        };
+
+        // Without exceptions the instruction retires as if nothing happened, the recorded fault
+        // is the only trace of it.
+        if C::HANDLE_EXCEPTIONS {
+            *trap = fault.error.trap();
+        }
+        self.faults.push(fault);
+    }
+}
+
+/// Processor of the builtin delegations, see `DelegationRegistry::builtin`. There is nowhere to
+/// record faults, so the guest takes their trap, and the host panics with the details if the
+/// machine doesn't handle exceptions.
 #[derive(Clone, Copy, Debug)]
 pub struct DelegationsCSRProcessor;
 
@@ -34,22 +472,14 @@ impl CustomCSRProcessor for DelegationsCSRProcessor {
         _tracer: &mut TR,
         _mmu: &mut MMU,
         csr_index: u32,
-        _rs1_value: u32,
+        rs1_value: u32,
         _zimm: u32,
         ret_val: &mut u32,
         trap: &mut TrapReason,
         _proc_cycle: u32,
         _cycle_timestamp: u32,
     ) {
-        *ret_val = 0;
-        match csr_index {
-            BLAKE2S_ACCESS_ID => {}
-            BLAKE2_ROUND_FUNCTION_ACCESS_ID => {}
-            BLAKE2_ROUND_FUNCTION_WITH_XOR_ACCESS_ID => {}
-            _ => {
-                *trap = TrapReason::IllegalInstruction;
-            }
-        }
+        BUILTIN_DELEGATIONS.read_csr(csr_index, rs1_value, ret_val, trap);
     }
 
     #[inline(always)]
@@ -62,7 +492,7 @@ impl CustomCSRProcessor for DelegationsCSRProcessor {
         &mut self,
         memory_source: &mut M,
         tracer: &mut TR,
-        mmu: &mut MMU,
+        _mmu: &mut MMU,
         csr_index: u32,
         rs1_value: u32,
         _zimm: u32,
@@ -70,43 +500,26 @@ impl CustomCSRProcessor for DelegationsCSRProcessor {
         proc_cycle: u32,
         cycle_timestamp: u32,
     ) {
-        match csr_index {
-            BLAKE2S_ACCESS_ID => {
-                blake2s_round_function::<_, _, _, _, false>(
-                    memory_source,
-                    tracer,
-                    mmu,
-                    rs1_value,
-                    trap,
-                    proc_cycle,
-                    cycle_timestamp,
-                );
-            }
-            BLAKE2_ROUND_FUNCTION_ACCESS_ID => {
-                blake2_round_function::<_, _, _, _>(
-                    memory_source,
-                    tracer,
-                    mmu,
-                    rs1_value,
-                    trap,
-                    proc_cycle,
-                    cycle_timestamp,
-                );
-            }
-            BLAKE2_ROUND_FUNCTION_WITH_XOR_ACCESS_ID => {
-                blake2_round_function_with_xor::<_, _, _, _>(
-                    memory_source,
-                    tracer,
-                    mmu,
-                    rs1_value,
-                    trap,
-                    proc_cycle,
-                    cycle_timestamp,
-                );
-            }
-            _ => {
-                *trap = TrapReason::IllegalInstruction;
-            }
+        let Some(fault) = BUILTIN_DELEGATIONS.write_csr(
+            &mut CycleContext::<M, TR, C> {
+                memory_source,
+                tracer,
+                csr_index,
+                proc_cycle,
+                cycle_timestamp,
+                _marker: PhantomData,
+            },
+            csr_index,
+            rs1_value,
+            trap,
+            proc_cycle,
+        ) else {
+            return;
+        };
+
+        if !C::HANDLE_EXCEPTIONS {
+            panic!("{}", fault);
         }
+        *trap = fault.error.trap();
     }
 }
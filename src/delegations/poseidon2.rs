@@ -0,0 +1,268 @@
+use crate::cycle::state::NON_DETERMINISM_CSR;
+
+use super::*;
+
+// poseidon2 binary interface is
+// - 16xu32 words of the state, elements of the Mersenne31 field. words are reduced modulo the
+//   prime on read, so 0x7fffffff is read as zero
+// at the end we will overwrite all 16 words with the permuted state, in canonical form
+
+pub const POSEIDON2_ABI_NUM_MEM_ACCESSES: usize = POSEIDON2_WIDTH;
+pub const POSEIDON2_ACCESS_ID: u32 = NON_DETERMINISM_CSR + 9;
+
+pub const M31_MODULUS: u32 = 0x7fffffff;
+pub const POSEIDON2_WIDTH: usize = 16;
+pub const POSEIDON2_HALF_FULL_ROUNDS: usize = 4;
+pub const POSEIDON2_PARTIAL_ROUNDS: usize = 14;
+
+/// M4 of the Poseidon2 paper. The external layer applies the matrix to every chunk of four
+/// elements and then adds the sum of the chunks to each of them.
+pub const POSEIDON2_PAPER_M4: [[u32; 4]; 4] =
+    [[5, 7, 1, 3], [4, 6, 1, 1], [1, 3, 5, 7], [1, 1, 4, 6]];
+
+/// M4 of Plonky3's `MDSMat4`, the circulant matrix circ(2, 3, 1, 1).
+pub const POSEIDON2_CIRCULANT_M4: [[u32; 4]; 4] =
+    [[2, 3, 1, 1], [1, 2, 3, 1], [1, 1, 2, 3], [3, 1, 1, 2]];
+
+/// V of an internal layer `1 + diag(V)`, with V = [-2, 2^0, 2^1, .., 2^8, 2^10, 2^12, .., 2^16].
+pub const POSEIDON2_INTERNAL_DIAGONAL: [u32; POSEIDON2_WIDTH] = [
+    M31_MODULUS - 2,
+    1,
+    1 << 1,
+    1 << 2,
+    1 << 3,
+    1 << 4,
+    1 << 5,
+    1 << 6,
+    1 << 7,
+    1 << 8,
+    1 << 10,
+    1 << 12,
+    1 << 13,
+    1 << 14,
+    1 << 15,
+    1 << 16,
+];
+
+/// Round constants and linear layers of the permutation. They differ between proof systems, so
+/// they must be the ones of the proof system the guest targets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Poseidon2Constants {
+    /// 4x4 matrix of the external layer, see `POSEIDON2_CIRCULANT_M4`.
+    pub external_matrix: [[u32; 4]; 4],
+    /// V of the internal layer `1 + diag(V)`, see `POSEIDON2_INTERNAL_DIAGONAL`.
+    pub internal_diagonal: [u32; POSEIDON2_WIDTH],
+    pub initial_external: [[u32; POSEIDON2_WIDTH]; POSEIDON2_HALF_FULL_ROUNDS],
+    pub internal: [u32; POSEIDON2_PARTIAL_ROUNDS],
+    pub terminal_external: [[u32; POSEIDON2_WIDTH]; POSEIDON2_HALF_FULL_ROUNDS],
+}
+
+impl Poseidon2Constants {
+    /// Plonky3's `Poseidon2Mersenne31::<16>::new_from_rng_128` with
+    /// `Xoroshiro128Plus::seed_from_u64(1)`: the initial external, terminal external and
+    /// internal round constants are drawn in that order, each as the top 31 bits of `next_u32`
+    /// with the prime itself rejected.
+    pub fn plonky3() -> Self {
+        let mut rng = Xoroshiro128Plus::seed_from_u64(1);
+        let mut next = || loop {
+            let value = rng.next_u32() >> 1;
+            if value != M31_MODULUS {
+                break value;
+            }
+        };
+
+        let initial_external = core::array::from_fn(|_| core::array::from_fn(|_| next()));
+        let terminal_external = core::array::from_fn(|_| core::array::from_fn(|_| next()));
+        let internal = core::array::from_fn(|_| next());
+
+        Self {
+            external_matrix: POSEIDON2_CIRCULANT_M4,
+            internal_diagonal: POSEIDON2_INTERNAL_DIAGONAL,
+            initial_external,
+            internal,
+            terminal_external,
+        }
+    }
+}
+
+// `rand_xoshiro`'s generator, only used to derive the constants
+struct Xoroshiro128Plus {
+    s0: u64,
+    s1: u64,
+}
+
+impl Xoroshiro128Plus {
+    // the state is the first two outputs of SplitMix64
+    fn seed_from_u64(mut seed: u64) -> Self {
+        let mut split_mix = || {
+            seed = seed.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^ (z >> 31)
+        };
+
+        Self {
+            s0: split_mix(),
+            s1: split_mix(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let result = self.s0.wrapping_add(self.s1);
+        self.s1 ^= self.s0;
+        self.s0 = self.s0.rotate_left(24) ^ self.s1 ^ (self.s1 << 16);
+        self.s1 = self.s1.rotate_left(37);
+
+        result
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+}
+
+/// Builtin at `POSEIDON2_ACCESS_ID` with `Poseidon2Constants::plonky3`. Register it with other
+/// constants for a proof system that uses different ones.
+#[derive(Clone, Debug)]
+pub struct Poseidon2 {
+    pub constants: Poseidon2Constants,
+}
+
+impl DelegationHandler for Poseidon2 {
+    fn name(&self) -> &str {
+        "poseidon2_m31"
+    }
+
+    fn num_words(&self) -> usize {
+        POSEIDON2_ABI_NUM_MEM_ACCESSES
+    }
+
+    fn execute(
+        &self,
+        context: &mut dyn DelegationContext,
+        rs1_value: u32,
+    ) -> Result<(), DelegationError> {
+        poseidon2_round_function(&self.constants, context, rs1_value)
+    }
+}
+
+fn reduce(value: u64) -> u32 {
+    (value % M31_MODULUS as u64) as u32
+}
+
+fn sbox(x: u32) -> u32 {
+    let x2 = reduce(x as u64 * x as u64);
+    let x4 = reduce(x2 as u64 * x2 as u64);
+
+    reduce(x4 as u64 * x as u64)
+}
+
+pub fn external_linear_layer(state: &mut [u32; POSEIDON2_WIDTH], matrix: &[[u32; 4]; 4]) {
+    let mut chunks = [[0u64; 4]; POSEIDON2_WIDTH / 4];
+    for (chunk, input) in chunks.iter_mut().zip(state.chunks(4)) {
+        for (dst, row) in chunk.iter_mut().zip(matrix) {
+            *dst = row
+                .iter()
+                .zip(input)
+                .map(|(m, x)| *m as u64 * *x as u64)
+                .sum();
+        }
+    }
+
+    let sums: [u64; 4] = core::array::from_fn(|i| chunks.iter().map(|chunk| chunk[i]).sum());
+    for (dst, (chunk, idx)) in state
+        .iter_mut()
+        .zip(chunks.iter().flat_map(|chunk| chunk.iter().zip(0..4)))
+    {
+        *dst = reduce(chunk + sums[idx]);
+    }
+}
+
+pub fn internal_linear_layer(
+    state: &mut [u32; POSEIDON2_WIDTH],
+    diagonal: &[u32; POSEIDON2_WIDTH],
+) {
+    let sum: u64 = state.iter().map(|x| *x as u64).sum();
+    for (x, diagonal) in state.iter_mut().zip(diagonal) {
+        *x = reduce(sum + *x as u64 * *diagonal as u64);
+    }
+}
+
+fn full_round(
+    state: &mut [u32; POSEIDON2_WIDTH],
+    round_constants: &[u32; POSEIDON2_WIDTH],
+    matrix: &[[u32; 4]; 4],
+) {
+    for (x, constant) in state.iter_mut().zip(round_constants) {
+        *x = sbox(reduce(*x as u64 + *constant as u64));
+    }
+    external_linear_layer(state, matrix);
+}
+
+/// The Poseidon2 permutation of width 16 over Mersenne31, with x^5 as the S-box, 8 full and 14
+/// partial rounds.
+pub fn poseidon2_permutation(state: &mut [u32; POSEIDON2_WIDTH], constants: &Poseidon2Constants) {
+    external_linear_layer(state, &constants.external_matrix);
+
+    for round_constants in &constants.initial_external {
+        full_round(state, round_constants, &constants.external_matrix);
+    }
+
+    for constant in constants.internal {
+        state[0] = sbox(reduce(state[0] as u64 + constant as u64));
+        internal_linear_layer(state, &constants.internal_diagonal);
+    }
+
+    for round_constants in &constants.terminal_external {
+        full_round(state, round_constants, &constants.external_matrix);
+    }
+}
+
+pub fn poseidon2_round_function(
+    constants: &Poseidon2Constants,
+    context: &mut dyn DelegationContext,
+    rs1_value: u32,
+) -> Result<(), DelegationError> {
+    // we consider high bits as the offset
+    let mem_offset = memory_offset(rs1_value)?;
+
+    // we perform batch accesses
+    let mut accesses = [BatchAccessPartialData::Write {
+        read_value: 0,
+        written_value: 0,
+    }; POSEIDON2_ABI_NUM_MEM_ACCESSES];
+
+    let mut state = [0u32; POSEIDON2_WIDTH];
+    for (low_offset, (access, dst)) in accesses.iter_mut().zip(state.iter_mut()).enumerate() {
+        let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
+        let read_value = context.read(address as u64)?;
+
+        *access = BatchAccessPartialData::Write {
+            read_value,
+            written_value: 0,
+        };
+        *dst = reduce(read_value as u64);
+    }
+
+    poseidon2_permutation(&mut state, constants);
+
+    // write back
+    for (low_offset, (access, value)) in accesses.iter_mut().zip(state).enumerate() {
+        let BatchAccessPartialData::Write {
+            read_value: _,
+            written_value,
+        } = access
+        else {
+            unreachable!()
+        };
+
+        *written_value = value;
+        let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
+        context.write(address as u64, value)?;
+    }
+
+    context.trace_batch_memory_access((mem_offset >> 16) as u16, &accesses);
+
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use crate::cycle::state::NON_DETERMINISM_CSR;
+
+use super::*;
+
+// sha256 compression function binary interface is
+// - 8xu32 words of the state, H0 to H7
+// - 16xu32 words of the message block, W0 to W15
+// at the end we will overwrite first 8 words as the new state
+//
+// words are the big-endian words of the specification, so a guest that holds the block as bytes
+// loads every word with a byte swap. the state is not finalized in any way: the guest starts from
+// the initial hash value, pads the message itself and reads the digest as the big-endian bytes of
+// the 8 words after the last block
+
+pub const SHA256_ABI_NUM_MEM_ACCESSES: usize = 8 + 16;
+pub const SHA256_ACCESS_ID: u32 = NON_DETERMINISM_CSR + 5;
+
+/// Initial hash value of the specification.
+pub const SHA256_IV: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const ROUND_CONSTANTS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+#[derive(Clone, Copy, Debug)]
+pub struct Sha256;
+
+impl DelegationHandler for Sha256 {
+    fn name(&self) -> &str {
+        "sha256"
+    }
+
+    fn num_words(&self) -> usize {
+        SHA256_ABI_NUM_MEM_ACCESSES
+    }
+
+    fn execute(
+        &self,
+        context: &mut dyn DelegationContext,
+        rs1_value: u32,
+    ) -> Result<(), DelegationError> {
+        sha256_round_function(context, rs1_value)
+    }
+}
+
+/// Compresses one message block into the state.
+pub fn sha256_compress(state: &mut [u32; 8], block: &[u32; 16]) {
+    let mut schedule = [0u32; 64];
+    schedule[..16].copy_from_slice(block);
+    for i in 16..64 {
+        let s0 = schedule[i - 15].rotate_right(7)
+            ^ schedule[i - 15].rotate_right(18)
+            ^ (schedule[i - 15] >> 3);
+        let s1 = schedule[i - 2].rotate_right(17)
+            ^ schedule[i - 2].rotate_right(19)
+            ^ (schedule[i - 2] >> 10);
+        schedule[i] = schedule[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(schedule[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for (round_constant, word) in ROUND_CONSTANTS.into_iter().zip(schedule) {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(round_constant)
+            .wrapping_add(word);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *word = word.wrapping_add(value);
+    }
+}
+
+pub fn sha256_round_function(
+    context: &mut dyn DelegationContext,
+    rs1_value: u32,
+) -> Result<(), DelegationError> {
+    // we consider high bits as the offset
+    let mem_offset = memory_offset(rs1_value)?;
+
+    // we perform batch accesses
+    let mut accesses =
+        [BatchAccessPartialData::Read { read_value: 0 }; SHA256_ABI_NUM_MEM_ACCESSES];
+    let mut it = accesses.iter_mut();
+
+    let mut state = [0u32; 8];
+    for (low_offset, dst) in state.iter_mut().enumerate() {
+        let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
+        let read_value = context.read(address as u64)?;
+
+        *it.next().unwrap() = BatchAccessPartialData::Write {
+            read_value,
+            written_value: 0,
+        };
+        *dst = read_value;
+    }
+
+    let mut block = [0u32; 16];
+    for (low_offset, dst) in (8..24usize).zip(block.iter_mut()) {
+        let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
+        let read_value = context.read(address as u64)?;
+
+        *it.next().unwrap() = BatchAccessPartialData::Read { read_value };
+        *dst = read_value;
+    }
+
+    sha256_compress(&mut state, &block);
+
+    // write back
+    for (low_offset, (access, value)) in accesses.iter_mut().zip(state).enumerate() {
+        let BatchAccessPartialData::Write {
+            read_value: _,
+            written_value,
+        } = access
+        else {
+            unreachable!()
+        };
+
+        *written_value = value;
+        let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
+        context.write(address as u64, value)?;
+    }
+
+    context.trace_batch_memory_access((mem_offset >> 16) as u16, &accesses);
+
+    Ok(())
+}
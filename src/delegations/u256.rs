@@ -0,0 +1,196 @@
+use crate::cycle::state::NON_DETERMINISM_CSR;
+
+use super::*;
+
+// u256 binary interface is
+// - 8xu32 words of the first operand `a`, least significant first. the result is written here
+// - 8xu32 words of the second operand `b`. widening multiplication writes the high half here
+// - 8xu32 words of the modulus, only used by the modular multiplication
+// - one u32 bitmask, that will determine the operation (see `U256_OP_*`)
+// - one u32 word of carry, with the carry (borrow) in at bit 0 for addition (subtraction), and
+//   the carry (borrow) out written back. other operations leave it as is
+// all 26 words are reported as accessed, whatever the operation is
+
+pub const U256_ABI_NUM_MEM_ACCESSES: usize = 8 + 8 + 8 + 1 + 1;
+pub const U256_ACCESS_ID: u32 = NON_DETERMINISM_CSR + 6;
+
+/// `a = a + b + carry`
+pub const U256_OP_ADD: u32 = 1 << 0;
+/// `a = a - b - borrow`
+pub const U256_OP_SUB: u32 = 1 << 1;
+/// `(a, b) = (low, high)` of `a * b`
+pub const U256_OP_MUL_WIDE: u32 = 1 << 2;
+/// `a = low(a * b)`
+pub const U256_OP_MUL_LOW: u32 = 1 << 3;
+/// `a = high(a * b)`
+pub const U256_OP_MUL_HIGH: u32 = 1 << 4;
+/// `a = a * b mod modulus`, with a non-zero modulus
+pub const U256_OP_MUL_MOD: u32 = 1 << 5;
+
+pub type U256 = [u32; 8];
+
+#[derive(Clone, Copy, Debug)]
+pub struct U256Arithmetic;
+
+impl DelegationHandler for U256Arithmetic {
+    fn name(&self) -> &str {
+        "u256"
+    }
+
+    fn num_words(&self) -> usize {
+        U256_ABI_NUM_MEM_ACCESSES
+    }
+
+    fn execute(
+        &self,
+        context: &mut dyn DelegationContext,
+        rs1_value: u32,
+    ) -> Result<(), DelegationError> {
+        u256_round_function(context, rs1_value)
+    }
+}
+
+pub fn add_with_carry(a: &U256, b: &U256, carry: bool) -> (U256, bool) {
+    let mut result = [0u32; 8];
+    let mut carry = carry as u64;
+    for (dst, (a, b)) in result.iter_mut().zip(a.iter().zip(b)) {
+        let sum = *a as u64 + *b as u64 + carry;
+        *dst = sum as u32;
+        carry = sum >> 32;
+    }
+
+    (result, carry != 0)
+}
+
+pub fn sub_with_borrow(a: &U256, b: &U256, borrow: bool) -> (U256, bool) {
+    let mut result = [0u32; 8];
+    let mut borrow = borrow;
+    for (dst, (a, b)) in result.iter_mut().zip(a.iter().zip(b)) {
+        let (difference, borrow_a) = a.overflowing_sub(*b);
+        let (difference, borrow_b) = difference.overflowing_sub(borrow as u32);
+        *dst = difference;
+        borrow = borrow_a || borrow_b;
+    }
+
+    (result, borrow)
+}
+
+/// Full 512-bit product, least significant word first.
+pub fn widening_mul(a: &U256, b: &U256) -> [u32; 16] {
+    let mut result = [0u32; 16];
+    for (i, a) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, b) in b.iter().enumerate() {
+            let product = *a as u64 * *b as u64 + result[i + j] as u64 + carry;
+            result[i + j] = product as u32;
+            carry = product >> 32;
+        }
+        result[i + 8] = carry as u32;
+    }
+
+    result
+}
+
+pub fn mul_mod(a: &U256, b: &U256, modulus: &U256) -> U256 {
+    assert!(modulus.iter().any(|word| *word != 0), "zero modulus");
+    let product = widening_mul(a, b);
+
+    // shift the product into the remainder bit by bit, keeping it below the modulus
+    let mut remainder = [0u32; 8];
+    for bit in (0..512).rev() {
+        let overflow = remainder[7] >> 31 != 0;
+        for i in (1..8).rev() {
+            remainder[i] = (remainder[i] << 1) | (remainder[i - 1] >> 31);
+        }
+        remainder[0] = (remainder[0] << 1) | ((product[bit / 32] >> (bit % 32)) & 1);
+
+        if overflow || !less_than(&remainder, modulus) {
+            remainder = sub_with_borrow(&remainder, modulus, false).0;
+        }
+    }
+
+    remainder
+}
+
+pub fn less_than(a: &U256, b: &U256) -> bool {
+    a.iter().rev().cmp(b.iter().rev()).is_lt()
+}
+
+pub fn u256_round_function(
+    context: &mut dyn DelegationContext,
+    rs1_value: u32,
+) -> Result<(), DelegationError> {
+    // we consider high bits as the offset
+    let mem_offset = memory_offset(rs1_value)?;
+
+    let mut words = [0u32; U256_ABI_NUM_MEM_ACCESSES];
+    for (low_offset, dst) in words.iter_mut().enumerate() {
+        let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
+        *dst = context.read(address as u64)?;
+    }
+
+    let a: U256 = words[0..8].try_into().unwrap();
+    let b: U256 = words[8..16].try_into().unwrap();
+    let modulus: U256 = words[16..24].try_into().unwrap();
+    let op = words[24];
+    let carry = words[25];
+    if op == U256_OP_MUL_MOD && modulus == [0u32; 8] {
+        return Err(DelegationError::InvalidArgument(
+            "modular multiplication by a zero modulus".to_string(),
+        ));
+    }
+
+    let mut results = words;
+    match op {
+        U256_OP_ADD => {
+            let (sum, carry) = add_with_carry(&a, &b, carry & 1 != 0);
+            results[0..8].copy_from_slice(&sum);
+            results[25] = carry as u32;
+        }
+        U256_OP_SUB => {
+            let (difference, borrow) = sub_with_borrow(&a, &b, carry & 1 != 0);
+            results[0..8].copy_from_slice(&difference);
+            results[25] = borrow as u32;
+        }
+        U256_OP_MUL_WIDE => {
+            results[0..16].copy_from_slice(&widening_mul(&a, &b));
+        }
+        U256_OP_MUL_LOW => {
+            results[0..8].copy_from_slice(&widening_mul(&a, &b)[0..8]);
+        }
+        U256_OP_MUL_HIGH => {
+            results[0..8].copy_from_slice(&widening_mul(&a, &b)[8..16]);
+        }
+        U256_OP_MUL_MOD => {
+            results[0..8].copy_from_slice(&mul_mod(&a, &b, &modulus));
+        }
+        _ => {
+            return Err(DelegationError::InvalidArgument(format!(
+                "unknown u256 operation 0x{:08x}",
+                op
+            )))
+        }
+    }
+
+    // operands and carry are written back, the modulus and the operation are only read
+    let mut accesses = [BatchAccessPartialData::Read { read_value: 0 }; U256_ABI_NUM_MEM_ACCESSES];
+    for (low_offset, access) in accesses.iter_mut().enumerate() {
+        let read_value = words[low_offset];
+        if (16..25).contains(&low_offset) {
+            *access = BatchAccessPartialData::Read { read_value };
+            continue;
+        }
+
+        let written_value = results[low_offset];
+        *access = BatchAccessPartialData::Write {
+            read_value,
+            written_value,
+        };
+        let address: usize = mem_offset + low_offset * core::mem::size_of::<u32>();
+        context.write(address as u64, written_value)?;
+    }
+
+    context.trace_batch_memory_access((mem_offset >> 16) as u16, &accesses);
+
+    Ok(())
+}
@@ -5,7 +5,9 @@
 #![feature(let_chains)]
 
 pub mod abstractions;
+pub mod analysis;
 pub mod cycle;
+pub mod delegations;
 pub mod mmio;
 pub mod mmu;
 mod qol;
@@ -13,8 +15,5 @@ pub mod runner;
 pub mod sim;
 pub mod utils;
 
-#[cfg(feature = "delegation")]
-pub mod delegations;
-
 #[cfg(test)]
 mod tests;
@@ -1,8 +1,11 @@
+use crate::abstractions::tracer::Tracer;
 use crate::cycle::status_registers::TrapReason;
+use crate::cycle::MachineConfig;
 
 pub mod quasi_uart;
 
-// we assume that any necessary tracing is INSIDE of the MMIO
+// accesses are reported to the tracer by `MMIOImplementation`, any device specific
+// tracing is INSIDE of the MMIO
 pub trait MMIOSource {
     fn address_range(&self) -> std::ops::Range<u64>;
     fn read(&mut self, address: u64, trap: &mut TrapReason) -> u32;
@@ -40,10 +43,18 @@ impl<'a, const N: usize> MMIOImplementation<'a, N> {
         }
     }
 
-    pub fn read(&mut self, phys_address: u64, trap: &mut TrapReason) -> Result<u32, ()> {
+    pub fn read<C: MachineConfig, TR: Tracer<C>>(
+        &mut self,
+        phys_address: u64,
+        tracer: &mut TR,
+        proc_cycle: u32,
+        cycle_timestamp: u32,
+        trap: &mut TrapReason,
+    ) -> Result<u32, ()> {
         for (range, source) in self.sources.iter_mut() {
             if range.contains(&phys_address) {
                 let value = source.read(phys_address, trap);
+                tracer.trace_mmio_read(phys_address, value, proc_cycle, cycle_timestamp);
 
                 return Ok(value);
             }
@@ -52,15 +63,19 @@ impl<'a, const N: usize> MMIOImplementation<'a, N> {
         Err(())
     }
 
-    pub fn write(
+    pub fn write<C: MachineConfig, TR: Tracer<C>>(
         &mut self,
         phys_address: u64,
         value: u32,
+        tracer: &mut TR,
+        proc_cycle: u32,
+        cycle_timestamp: u32,
         trap: &mut TrapReason,
     ) -> Result<(), ()> {
         for (range, source) in self.sources.iter_mut() {
             if range.contains(&phys_address) {
                 source.write(phys_address, value, trap);
+                tracer.trace_mmio_write(phys_address, value, proc_cycle, cycle_timestamp);
 
                 return Ok(());
             }
@@ -17,6 +17,7 @@ pub trait MMUImplementation<M: MemorySource, TR: Tracer<C>, C: MachineConfig> {
         memory_source: &mut M,
         tracer: &mut TR,
         proc_cycle: u32,
+        cycle_timestamp: u32,
         trap: &mut TrapReason,
     ) -> u64;
 }
@@ -176,6 +177,7 @@ impl<M: MemorySource, TR: Tracer<C>, C: MachineConfig> MMUImplementation<M, TR,
         _memory_source: &mut M,
         _tracer: &mut TR,
         _proc_cycle: u32,
+        _cycle_timestamp: u32,
         _trap: &mut TrapReason,
     ) -> u64 {
         virt_address as u64
@@ -213,6 +215,7 @@ impl<M: MemorySource, TR: Tracer<C>, C: MachineConfig> MMUImplementation<M, TR,
         memory_source: &mut M,
         tracer: &mut TR,
         proc_cycle: u32,
+        cycle_timestamp: u32,
         trap: &mut TrapReason,
     ) -> u64 {
         let should_translate = mode.as_register_value() < Mode::Machine.as_register_value()
@@ -237,7 +240,7 @@ impl<M: MemorySource, TR: Tracer<C>, C: MachineConfig> MMUImplementation<M, TR,
                     4,
                     access_type,
                     proc_cycle,
-                    proc_cycle,
+                    cycle_timestamp,
                     trap,
                 );
                 if trap.is_a_trap() {
@@ -319,7 +322,16 @@ impl<M: MemorySource, TR: Tracer<C>, C: MachineConfig> MMUImplementation<M, TR,
                 return 0;
             }
 
-            physical_address_candidates[i as usize]
+            let phys_address = physical_address_candidates[i as usize];
+            tracer.trace_address_translation(
+                self.sapt,
+                virt_address as u64,
+                phys_address,
+                proc_cycle,
+                cycle_timestamp,
+            );
+
+            phys_address
         }
     }
 }
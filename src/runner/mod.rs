@@ -1,11 +1,15 @@
+use std::ops::Range;
 use std::path::Path;
 
 use crate::abstractions::non_determinism::NonDeterminismCSRSource;
 use crate::abstractions::non_determinism::QuasiUARTSource;
+use crate::abstractions::tracer::Tracer;
+use crate::abstractions::uninit::{UninitCheckingMemory, UninitRead};
 use crate::cycle::state::StateTracer;
 use crate::cycle::IMStandardIsaConfig;
 use crate::cycle::MachineConfig;
 use crate::mmu::NoMMU;
+use crate::sim::RunFault;
 use crate::sim::Simulator;
 use crate::sim::SimulatorConfig;
 use crate::{abstractions::memory::VectorMemoryImpl, cycle::state::RiscV32State};
@@ -13,24 +17,35 @@ use crate::{abstractions::memory::VectorMemoryImpl, cycle::state::RiscV32State};
 pub const DEFAULT_ENTRY_POINT: u32 = 0x01000000;
 pub const CUSTOM_ENTRY_POINT: u32 = 0;
 
-pub fn run_simple_simulator(config: SimulatorConfig) -> [u32; 8] {
+/// The end of a run: the program's output, and the fault it stopped at if it didn't finish.
+#[derive(Clone, Debug)]
+pub struct RunOutcome {
+    /// `a0` to `a7`, only meaningful without a fault.
+    pub registers: [u32; 8],
+    pub fault: Option<RunFault>,
+}
+
+pub fn run_simple_simulator(config: SimulatorConfig) -> RunOutcome {
     run_simple_with_entry_point(config)
 }
 
-pub fn run_simple_with_entry_point(config: SimulatorConfig) -> [u32; 8] {
-    let (_, state) =
+pub fn run_simple_with_entry_point(config: SimulatorConfig) -> RunOutcome {
+    let (_, fault, state) =
         run_simple_with_entry_point_and_non_determimism_source(config, QuasiUARTSource::default());
     let registers = state.registers;
-    [
-        registers[10],
-        registers[11],
-        registers[12],
-        registers[13],
-        registers[14],
-        registers[15],
-        registers[16],
-        registers[17],
-    ]
+    RunOutcome {
+        registers: [
+            registers[10],
+            registers[11],
+            registers[12],
+            registers[13],
+            registers[14],
+            registers[15],
+            registers[16],
+            registers[17],
+        ],
+        fault,
+    }
 }
 
 pub fn run_simple_with_entry_point_and_non_determimism_source<
@@ -38,42 +53,100 @@ pub fn run_simple_with_entry_point_and_non_determimism_source<
 >(
     config: SimulatorConfig,
     non_determinism_source: S,
-) -> (S, RiscV32State) {
+) -> (S, Option<RunFault>, RiscV32State) {
     run_simple_with_entry_point_and_non_determimism_source_for_config::<S, IMStandardIsaConfig>(
         config,
         non_determinism_source,
     )
 }
 
+/// Runs the program until it reaches its end, or stops at a fault or a watchpoint. A fault is
+/// returned along with the state it left.
 pub fn run_simple_with_entry_point_and_non_determimism_source_for_config<
     S: NonDeterminismCSRSource<VectorMemoryImpl>,
     C: MachineConfig,
 >(
     config: SimulatorConfig,
     non_determinism_source: S,
-) -> (S, RiscV32State<C>)
+) -> (S, Option<RunFault>, RiscV32State<C>)
+where
+    [(); { C::SUPPORT_LOAD_LESS_THAN_WORD } as usize]:,
+{
+    let (non_determinism_source, (), fault, state) =
+        run_simple_with_tracer_for_config(config, non_determinism_source, ());
+
+    (non_determinism_source, fault, state)
+}
+
+/// Same as `run_simple_with_entry_point_and_non_determimism_source_for_config`, but reports
+/// every event to the provided tracer, and returns it after the run
+pub fn run_simple_with_tracer_for_config<
+    S: NonDeterminismCSRSource<VectorMemoryImpl>,
+    TR: Tracer<C>,
+    C: MachineConfig,
+>(
+    config: SimulatorConfig,
+    non_determinism_source: S,
+    tracer: TR,
+) -> (S, TR, Option<RunFault>, RiscV32State<C>)
 where
     [(); { C::SUPPORT_LOAD_LESS_THAN_WORD } as usize]:,
 {
     let state = RiscV32State::<C>::initial(config.entry_point);
-    let memory_tracer = ();
     let mmu = NoMMU { sapt: 0 };
 
     let mut memory = VectorMemoryImpl::new_for_byte_size(1 << 32); // use full RAM
     memory.load_image(config.entry_point, read_bin(&config.bin_path).into_iter());
 
-    let mut sim = Simulator::new(
-        config,
-        state,
-        memory,
-        memory_tracer,
-        mmu,
-        non_determinism_source,
-    );
+    let mut sim = Simulator::new(config, state, memory, tracer, mmu, non_determinism_source);
+
+    sim.run(|_, _| {}, |_, _| {});
+
+    (
+        sim.non_determinism_source,
+        sim.memory_tracer,
+        sim.fault,
+        sim.state,
+    )
+}
+
+/// Same as `run_simple_with_entry_point_and_non_determimism_source_for_config`, but reports every
+/// read of memory that was neither loaded from the image nor written by the program. Reads from
+/// the `allowlist` ranges are not reported.
+pub fn run_with_uninit_read_checks_for_config<
+    S: NonDeterminismCSRSource<UninitCheckingMemory<VectorMemoryImpl>>,
+    C: MachineConfig,
+>(
+    config: SimulatorConfig,
+    non_determinism_source: S,
+    allowlist: Vec<Range<u32>>,
+) -> (S, Vec<UninitRead>, Option<RunFault>, RiscV32State<C>)
+where
+    [(); { C::SUPPORT_LOAD_LESS_THAN_WORD } as usize]:,
+{
+    let state = RiscV32State::<C>::initial(config.entry_point);
+    let mmu = NoMMU { sapt: 0 };
+
+    let image = read_bin(&config.bin_path);
+    let mut memory = UninitCheckingMemory::new(VectorMemoryImpl::new_for_byte_size(1 << 32)); // use full RAM
+    memory
+        .inner
+        .load_image(config.entry_point, image.iter().copied());
+    memory.mark_initialized(config.entry_point, image.len() as u32);
+    for range in allowlist {
+        memory.allow(range);
+    }
+
+    let mut sim = Simulator::new(config, state, memory, (), mmu, non_determinism_source);
 
     sim.run(|_, _| {}, |_, _| {});
 
-    (sim.non_determinism_source, sim.state)
+    (
+        sim.non_determinism_source,
+        sim.uninit_reads,
+        sim.fault,
+        sim.state,
+    )
 }
 
 // pub fn run_simple_with_entry_point_with_delegation_and_non_determimism_source<
@@ -103,13 +176,13 @@ where
 //     sim.non_determinism_source
 // }
 
-pub fn run_simulator_with_traces(config: SimulatorConfig) -> (StateTracer, ()) {
+pub fn run_simulator_with_traces(config: SimulatorConfig) -> (StateTracer, (), Option<RunFault>) {
     run_simulator_with_traces_for_config(config)
 }
 
 pub fn run_simulator_with_traces_for_config<C: MachineConfig>(
     config: SimulatorConfig,
-) -> (StateTracer<C>, ())
+) -> (StateTracer<C>, (), Option<RunFault>)
 where
     [(); { C::SUPPORT_LOAD_LESS_THAN_WORD } as usize]:,
 {
@@ -141,7 +214,7 @@ where
         },
     );
 
-    (state_tracer, sim.memory_tracer)
+    (state_tracer, sim.memory_tracer, sim.fault)
 }
 
 fn read_bin<P: AsRef<Path>>(path: P) -> Vec<u8> {
@@ -5,13 +5,37 @@ use crate::cycle::MachineConfig;
 use crate::{
     abstractions::{
         memory::MemorySource, non_determinism::NonDeterminismCSRSource, tracer::Tracer,
+        uninit::UninitRead,
     },
     cycle::state::RiscV32State,
+    delegations::{DelegationFault, DelegationRegistry},
     mmu::MMUImplementation,
     runner::DEFAULT_ENTRY_POINT,
 };
 
-use self::diag::Profiler;
+pub use self::annotate::{AnnotateConfig, AnnotatedInstruction, AnnotationReport};
+pub use self::call_trace::{Call, CallTrace, CallTraceConfig};
+pub use self::diag::Symbolizer;
+use self::diag::{Profiler, ProfilerEvents};
+pub use self::heap::{
+    AllocatorFunction, CallSiteAllocations, HeapConfig, HeapReport, HeapTracker, LiveAllocation,
+};
+pub use self::profile::{
+    FunctionShareChange, FunctionStats, FunctionTable, Profile, ProfileDiff, ProfileFrame,
+    ProfilerOutput, ProfilerWeighting,
+};
+pub use self::stack_usage::{
+    FunctionStackUsage, StackOverflow, StackUsage, StackUsageConfig, StackUsageReport,
+};
+pub use self::watch::{WatchAction, WatchHit, WatchKind, Watcher, Watchpoint};
+
+pub mod annotate;
+pub mod call_trace;
+pub mod heap;
+pub mod profile;
+pub mod stack_usage;
+pub(crate) mod unwind;
+pub mod watch;
 
 pub(crate) struct Simulator<MS, TR, MMU, ND, C: MachineConfig = IMStandardIsaConfig>
 where
@@ -29,6 +53,12 @@ where
     cycles: usize,
 
     profiler: Option<Profiler>,
+    watcher: Watcher,
+    delegations: DelegationRegistry,
+    /// The fault the run stopped at, if any.
+    pub(crate) fault: Option<RunFault>,
+    /// The guest's reads of uninitialized memory, with their backtraces.
+    pub(crate) uninit_reads: Vec<UninitRead>,
 }
 
 impl<MS, TR, MMU, ND, C> Simulator<MS, TR, MMU, ND, C>
@@ -39,6 +69,13 @@ where
     ND: NonDeterminismCSRSource<MS>,
     C: MachineConfig,
     [(); { C::SUPPORT_LOAD_LESS_THAN_WORD } as usize]:,
+    // Lets the profiler and the watchpoints observe the events of every cycle along with the
+    // user's tracer.
+    MMU: for<'a> MMUImplementation<
+        MS,
+        (&'a mut TR, Option<&'a mut ProfilerEvents>, &'a mut Watcher),
+        C,
+    >,
 {
     pub(crate) fn new(
         config: SimulatorConfig,
@@ -55,6 +92,10 @@ where
             non_determinism_source,
             state,
             cycles: config.cycles,
+            watcher: Watcher::new(config.watchpoints.clone()),
+            delegations: config.delegations.clone(),
+            fault: None,
+            uninit_reads: Vec::new(),
             profiler: Profiler::new(config),
         }
     }
@@ -66,31 +107,137 @@ where
     {
         let mut previous_pc = self.state.pc;
         let mut end_of_execution_reached = false;
+        let mut stopped_at_watchpoint = false;
 
         for cycle in 0..self.cycles as usize {
-            if let Some(profiler) = self.profiler.as_mut() {
-                profiler.pre_cycle(
+            if let Some(profiler) = self.profiler.as_mut()
+                && let Some(overflow) = profiler.pre_cycle(
                     &mut self.state,
                     &mut self.memory_source,
                     &mut self.memory_tracer,
                     &mut self.mmu,
                     cycle as u32,
-                );
+                )
+            {
+                println!("Stopped after {} cycles at a {}", cycle, overflow);
+                self.fault = Some(RunFault::StackOverflow(overflow));
+                break;
             }
 
             fn_pre(self, cycle);
 
-            RiscV32State::<C>::cycle(
-                &mut self.state,
-                &mut self.memory_source,
-                &mut self.memory_tracer,
-                &mut self.mmu,
-                &mut self.non_determinism_source,
-                cycle as u32,
-            );
+            // Reads made outside of the cycle, e.g. by the profiler, are not the guest's.
+            let _ = self.memory_source.take_uninit_reads();
+
+            let (pc, registers) = (self.state.pc, self.state.registers);
+
+            let counts_events = self
+                .profiler
+                .as_ref()
+                .is_some_and(|profiler| profiler.counts_events());
+
+            if counts_events || !self.watcher.is_empty() {
+                let events = self
+                    .profiler
+                    .as_mut()
+                    .filter(|_| counts_events)
+                    .map(|profiler| &mut profiler.events);
+
+                self.state.cycle_ext(
+                    &mut self.memory_source,
+                    &mut (&mut self.memory_tracer, events, &mut self.watcher),
+                    &mut self.mmu,
+                    &mut self.non_determinism_source,
+                    &mut self.delegations,
+                    cycle as u32,
+                    cycle as u32,
+                );
+            } else {
+                self.state.cycle_ext(
+                    &mut self.memory_source,
+                    &mut self.memory_tracer,
+                    &mut self.mmu,
+                    &mut self.non_determinism_source,
+                    &mut self.delegations,
+                    cycle as u32,
+                    cycle as u32,
+                );
+            }
+
+            // Taken before the profiler and the watchpoints look at the memory.
+            let uninit_reads = self.memory_source.take_uninit_reads();
+
+            if let Some(profiler) = self.profiler.as_mut()
+                && counts_events
+            {
+                profiler.trace_calls(pc, self.state.pc, cycle as u32);
+                profiler.post_cycle(
+                    pc,
+                    &registers,
+                    &mut self.memory_source,
+                    &mut self.memory_tracer,
+                    &mut self.mmu,
+                    cycle as u32,
+                );
+            }
+
+            for mut hit in self.watcher.take_hits() {
+                hit.pc = pc;
+                if let Some(profiler) = self.profiler.as_mut() {
+                    hit.backtrace = profiler.backtrace(
+                        pc,
+                        &registers,
+                        &mut self.memory_source,
+                        &mut self.memory_tracer,
+                        &mut self.mmu,
+                        cycle as u32,
+                    );
+                }
+
+                println!("{}", hit);
+                stopped_at_watchpoint |= hit.action == WatchAction::Stop;
+            }
+
+            for mut read in uninit_reads {
+                if let Some(profiler) = self.profiler.as_mut() {
+                    read.backtrace = profiler.backtrace(
+                        pc,
+                        &registers,
+                        &mut self.memory_source,
+                        &mut self.memory_tracer,
+                        &mut self.mmu,
+                        cycle as u32,
+                    );
+                }
+
+                println!(
+                    "Uninitialized read of {} bytes at 0x{:08x} by pc 0x{:08x} at cycle {}",
+                    read.num_bytes, read.address, read.pc, cycle
+                );
+                for frame in &read.backtrace {
+                    println!("  {}", frame);
+                }
+                self.uninit_reads.push(read);
+            }
 
             fn_post(self, cycle);
 
+            // The guest either took the trap of the fault or, if it doesn't handle exceptions,
+            // retired the instruction without effect. Either way there is no point in going on.
+            if let Some(fault) = self.delegations.take_faults().into_iter().next() {
+                println!(
+                    "Stopped at a delegation fault after {} cycles: {}",
+                    cycle, fault
+                );
+                self.fault = Some(RunFault::Delegation(fault));
+                break;
+            }
+
+            if stopped_at_watchpoint {
+                println!("Stopped at a watchpoint after {} cycles", cycle);
+                break;
+            }
+
             if self.state.pc == previous_pc {
                 end_of_execution_reached = true;
                 println!("Took {} cycles to finish", cycle);
@@ -100,7 +247,7 @@ where
         }
 
         assert!(
-            end_of_execution_reached,
+            end_of_execution_reached || stopped_at_watchpoint || self.fault.is_some(),
             "program failed to each the end of execution over {} cycles",
             self.cycles
         );
@@ -112,11 +259,32 @@ where
     }
 }
 
+/// Why a run stopped before the program reached its end.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RunFault {
+    /// A delegation rejected its input.
+    Delegation(DelegationFault),
+    /// The stack grew past `StackUsageConfig::limit`. The stack usage report is still written.
+    StackOverflow(StackOverflow),
+}
+
+impl std::fmt::Display for RunFault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Delegation(fault) => write!(f, "{}", fault),
+            Self::StackOverflow(overflow) => write!(f, "{}", overflow),
+        }
+    }
+}
+
 pub struct SimulatorConfig {
     pub bin_path: PathBuf,
     pub entry_point: u32,
     pub cycles: usize,
     pub diagnostics: Option<DiagnosticsConfig>,
+    pub watchpoints: Vec<Watchpoint>,
+    /// Delegations available to the guest. Defaults to the builtin ones.
+    pub delegations: DelegationRegistry,
 }
 
 impl SimulatorConfig {
@@ -140,6 +308,8 @@ impl SimulatorConfig {
             entry_point,
             cycles,
             diagnostics,
+            watchpoints: Vec::new(),
+            delegations: DelegationRegistry::builtin(),
         }
     }
 }
@@ -152,7 +322,17 @@ pub struct DiagnosticsConfig {
 
 #[derive(Clone)]
 pub struct ProfilerConfig {
-    output_path: PathBuf,
+    pub outputs: Vec<ProfilerOutput>,
+    /// Every weighting gets its own set of outputs, see `ProfilerOutput::for_weighting`.
+    pub weightings: Vec<ProfilerWeighting>,
+    /// Counts every executed instruction for an annotated hot-spot report.
+    pub annotate: Option<AnnotateConfig>,
+    /// Records every call and return for an ftrace-style call tree.
+    pub call_trace: Option<CallTraceConfig>,
+    /// Tracks the stack pointer for stack usage high-water marks.
+    pub stack_usage: Option<StackUsageConfig>,
+    /// Intercepts the allocator functions for a heap usage and leak report.
+    pub heap: Option<HeapConfig>,
     pub reverse_graph: bool,
     pub frequency_recip: u32,
 }
@@ -164,12 +344,21 @@ impl DiagnosticsConfig {
             profiler_config: None,
         }
     }
+
+    pub fn symbolizer(&self) -> Symbolizer {
+        Symbolizer::new(&self.symbols_path)
+    }
 }
 
 impl ProfilerConfig {
     pub fn new(output_path: PathBuf) -> Self {
         Self {
-            output_path,
+            outputs: vec![ProfilerOutput::Flamegraph(output_path)],
+            weightings: vec![ProfilerWeighting::Cycles],
+            annotate: None,
+            call_trace: None,
+            stack_usage: None,
+            heap: None,
             reverse_graph: false,
             frequency_recip: 100,
         }
@@ -197,13 +386,22 @@ mod diag {
     use object::{File, Object, ObjectSection};
 
     use crate::{
-        abstractions::{mem_read, memory::MemorySource, tracer::Tracer},
+        abstractions::{
+            memory::MemorySource,
+            tracer::{BatchAccessPartialData, Tracer},
+        },
         cycle::{state::RiscV32State, status_registers::TrapReason},
         mmu::MMUImplementation,
         qol::PipeOp as _,
     };
 
-    use super::SimulatorConfig;
+    use super::unwind::{CfiUnwinder, Unwound};
+    use super::{
+        AnnotateConfig, AnnotatedInstruction, AnnotationReport, CallTrace, CallTraceConfig,
+        HeapConfig, HeapReport, HeapTracker, Profile, ProfileFrame, ProfilerOutput,
+        ProfilerWeighting, SimulatorConfig, StackOverflow, StackUsage, StackUsageConfig,
+        StackUsageReport,
+    };
 
     #[derive(Default, Debug)]
     struct ProfilerStats {
@@ -212,19 +410,150 @@ mod diag {
         samples_skipped: usize,
         samples_total: usize,
         samples_service: usize,
+        // Frames that DWARF could not resolve, named from the ELF symbol table.
+        frames_from_symbol_table: usize,
+        // Frames that could not be symbolized at all.
+        frames_unknown: usize,
+        // Samples whose innermost function has no prologue info, and so was assumed traceable.
+        samples_without_prologue_info: usize,
     }
 
+    // Guards against cycles in corrupted stacks.
+    const MAX_STACK_DEPTH: usize = 1024;
+
     pub(crate) struct Profiler {
-        // Safety: DwarfCache references data in symbol info.
+        // Safety: DwarfCache and the unwinder reference data in symbol info.
         dwarf_cache: DwarfCache,
+        unwinder: CfiUnwinder,
         symbol_info: SymbolInfo,
-        output_path: PathBuf,
+        outputs: Vec<ProfilerOutput>,
         frequency_recip: u32,
         reverse_graph: bool,
-        pub stacktraces: StacktraceSet,
+        pub stacktraces: Vec<(ProfilerWeighting, StacktraceSet)>,
+        pub events: ProfilerEvents,
+        annotate: Option<AnnotateConfig>,
+        // Instruction and execution count by pc.
+        instructions: HashMap<u32, (u32, u64)>,
+        call_trace: Option<(CallTraceConfig, CallTrace)>,
+        stack_usage: Option<(StackUsageConfig, StackUsage)>,
+        // Stack at the deepest point of the stack so far.
+        high_water_stack: Option<Stacktrace>,
+        heap: Option<(HeapConfig, HeapTracker<Option<Stacktrace>>)>,
         stats: ProfilerStats,
     }
 
+    /// Counts the events of the current cycle that stacks can be weighted by.
+    #[derive(Default, Debug)]
+    pub(crate) struct ProfilerEvents {
+        memory_loads: usize,
+        memory_stores: usize,
+        non_determinism_reads: usize,
+        non_determinism_writes: usize,
+        delegation_calls: usize,
+        traps: usize,
+        opcode: Option<u32>,
+    }
+
+    impl ProfilerEvents {
+        fn count(&self, weighting: ProfilerWeighting) -> usize {
+            match weighting {
+                ProfilerWeighting::Cycles => 0,
+                ProfilerWeighting::MemoryLoads => self.memory_loads,
+                ProfilerWeighting::MemoryStores => self.memory_stores,
+                ProfilerWeighting::NonDeterminismReads => self.non_determinism_reads,
+                ProfilerWeighting::NonDeterminismWrites => self.non_determinism_writes,
+                ProfilerWeighting::DelegationCalls => self.delegation_calls,
+                ProfilerWeighting::Traps => self.traps,
+            }
+        }
+    }
+
+    impl<C: MachineConfig> Tracer<C> for ProfilerEvents {
+        type AuxData = ();
+
+        fn create_from_initial_state(_state: &RiscV32State<C>, _aux_data: Self::AuxData) -> Self {
+            Self::default()
+        }
+
+        #[inline(always)]
+        fn trace_opcode_read(
+            &mut self,
+            _phys_address: u64,
+            read_value: u32,
+            _proc_cycle: u32,
+            _cycle_timestamp: u32,
+        ) {
+            self.opcode = Some(read_value);
+        }
+
+        #[inline(always)]
+        fn trace_non_determinism_read(
+            &mut self,
+            _read_value: u32,
+            _proc_cycle: u32,
+            _cycle_timestamp: u32,
+        ) {
+            self.non_determinism_reads += 1;
+        }
+
+        #[inline(always)]
+        fn trace_non_determinism_write(
+            &mut self,
+            _written_value: u32,
+            _proc_cycle: u32,
+            _cycle_timestamp: u32,
+        ) {
+            self.non_determinism_writes += 1;
+        }
+
+        #[inline(always)]
+        fn trace_ram_read(
+            &mut self,
+            _phys_address: u64,
+            _read_value: u32,
+            _proc_cycle: u32,
+            _cycle_timestamp: u32,
+        ) {
+            self.memory_loads += 1;
+        }
+
+        #[inline(always)]
+        fn trace_ram_read_write(
+            &mut self,
+            _phys_address: u64,
+            _read_value: u32,
+            _written_value: u32,
+            _proc_cycle: u32,
+            _cycle_timestamp: u32,
+        ) {
+            self.memory_stores += 1;
+        }
+
+        #[inline(always)]
+        fn trace_batch_memory_access(
+            &mut self,
+            _access_id: u32,
+            _phys_address_high: u16,
+            _accesses: &[BatchAccessPartialData],
+            _proc_cycle: u32,
+            _cycle_timestamp: u32,
+        ) {
+            self.delegation_calls += 1;
+        }
+
+        #[inline(always)]
+        fn trace_trap(
+            &mut self,
+            _trap: TrapReason,
+            _pc: u32,
+            _instr: u32,
+            _proc_cycle: u32,
+            _cycle_timestamp: u32,
+        ) {
+            self.traps += 1;
+        }
+    }
+
     impl Profiler {
         pub(crate) fn new(config: SimulatorConfig) -> Option<Self> {
             let dwarf_cache = DwarfCache {
@@ -234,12 +563,40 @@ mod diag {
             if let Some(d) = config.diagnostics
                 && let Some(p) = d.profiler_config
             {
+                let symbol_info = SymbolInfo::new(d.symbols_path);
+                let heap = p.heap.map(|config| {
+                    let entries = config
+                        .functions
+                        .iter()
+                        .flat_map(|(name, function)| {
+                            symbol_info
+                                .symbols
+                                .addresses_of(name)
+                                .map(|address| (address as u32, *function))
+                        })
+                        .collect();
+
+                    (config, HeapTracker::new(entries))
+                });
+
                 Self {
-                    symbol_info: SymbolInfo::new(d.symbols_path),
+                    unwinder: CfiUnwinder::new(&symbol_info.object),
+                    symbol_info,
                     frequency_recip: p.frequency_recip,
                     reverse_graph: p.reverse_graph,
-                    output_path: p.output_path,
-                    stacktraces: StacktraceSet::new(),
+                    outputs: p.outputs,
+                    stacktraces: p
+                        .weightings
+                        .iter()
+                        .map(|weighting| (*weighting, StacktraceSet::new()))
+                        .collect(),
+                    events: ProfilerEvents::default(),
+                    annotate: p.annotate,
+                    instructions: HashMap::new(),
+                    call_trace: p.call_trace.map(|config| (config, CallTrace::new())),
+                    stack_usage: p.stack_usage.map(|config| (config, StackUsage::new())),
+                    high_water_stack: None,
+                    heap,
                     dwarf_cache,
                     stats: ProfilerStats::default(),
                 }
@@ -249,6 +606,8 @@ mod diag {
             }
         }
 
+        /// Samples the state before the cycle. Returns the overflow if the stack grew past its
+        /// limit, the run has to stop then.
         pub(crate) fn pre_cycle<MS, TR, MMU, C>(
             &mut self,
             state: &RiscV32State<C>,
@@ -256,20 +615,114 @@ mod diag {
             memory_tracer: &mut TR,
             mmu: &mut MMU,
             cycle: u32,
-        ) where
+        ) -> Option<StackOverflow>
+        where
             MS: MemorySource,
             TR: Tracer<C>,
             MMU: MMUImplementation<MS, TR, C>,
             C: MachineConfig,
         {
-            if cycle % self.frequency_recip == 0 {
-                self.collect_stacktrace(state, memory_source, memory_tracer, mmu, cycle);
+            let sp = state.registers[2];
+            if let Some((_, stack_usage)) = self.stack_usage.as_mut()
+                && stack_usage.observe(state.pc, sp, cycle)
+            {
+                self.high_water_stack = self.collect_stacktrace(
+                    state.pc,
+                    &state.registers,
+                    memory_source,
+                    memory_tracer,
+                    mmu,
+                    cycle,
+                );
+
+                if let Some((config, _)) = &self.stack_usage
+                    && let Some(limit) = config.limit
+                    && sp < limit
+                {
+                    let report = self.stack_usage_report().unwrap();
+                    return Some(StackOverflow {
+                        sp,
+                        limit,
+                        cycle,
+                        stack: report.high_water_stack,
+                    });
+                }
+            }
+
+            if let Some((_, heap)) = self.heap.as_mut() {
+                heap.observe(state.pc, &state.registers, cycle);
+            }
+            if let Some(function) = self
+                .heap
+                .as_ref()
+                .and_then(|(_, heap)| heap.intercepts(state.pc))
+            {
+                // The stack of the caller, as the allocator has not set up its frame yet.
+                let stack = self.collect_stacktrace(
+                    state.registers[1].wrapping_sub(4),
+                    &state.registers,
+                    memory_source,
+                    memory_tracer,
+                    mmu,
+                    cycle,
+                );
+
+                if let Some((_, heap)) = self.heap.as_mut() {
+                    heap.enter(function, &state.registers, stack);
+                }
+            }
+
+            if let Some(idx) = self
+                .stacktraces
+                .iter()
+                .position(|(weighting, _)| *weighting == ProfilerWeighting::Cycles)
+                && cycle % self.frequency_recip == 0
+                && let Some(stacktrace) = self.collect_stacktrace(
+                    state.pc,
+                    &state.registers,
+                    memory_source,
+                    memory_tracer,
+                    mmu,
+                    cycle,
+                )
+            {
+                self.stacktraces[idx].1.absorb(stacktrace, 1);
             }
+
+            None
         }
 
-        fn collect_stacktrace<MS, TR, MMU, C>(
+        /// Whether stacks are weighted by events other than cycles, or instructions are annotated
+        /// or traced, so the events have to be traced and passed to `post_cycle`.
+        pub(crate) fn counts_events(&self) -> bool {
+            self.annotate.is_some()
+                || self.call_trace.is_some()
+                || self
+                    .stacktraces
+                    .iter()
+                    .any(|(weighting, _)| *weighting != ProfilerWeighting::Cycles)
+        }
+
+        /// Feeds the instruction executed at `pc` during the cycle to the call trace. `next_pc` is
+        /// where the execution continues. Must be called before `post_cycle`.
+        pub(crate) fn trace_calls(&mut self, pc: u32, next_pc: u32, cycle: u32) {
+            if let Some((_, call_trace)) = self.call_trace.as_mut()
+                && let Some(opcode) = self.events.opcode
+                && self.events.traps == 0
+            {
+                call_trace.step(pc, opcode, next_pc, cycle);
+            }
+        }
+
+        /// Attributes the events traced during the cycle to the stack of the instruction at `pc`,
+        /// as it was before the cycle. The stack is unwound from `pc` and `registers` from before
+        /// the cycle, but reads the memory as it is after it. The CFI at `pc` and the frame
+        /// pointer describe the frames before the instruction, so only a store by it into a saved
+        /// register slot of a caller could throw the unwinding off, which compiled code doesn't do.
+        pub(crate) fn post_cycle<MS, TR, MMU, C>(
             &mut self,
-            state: &RiscV32State<C>,
+            pc: u32,
+            registers: &[u32; 32],
             memory_source: &mut MS,
             memory_tracer: &mut TR,
             mmu: &mut MMU,
@@ -280,83 +733,134 @@ mod diag {
             MMU: MMUImplementation<MS, TR, C>,
             C: MachineConfig,
         {
-            self.stats.samples_total += 1;
-
-            let symbol_info = &self.symbol_info;
+            let events = std::mem::take(&mut self.events);
 
-            let mut callstack = Vec::with_capacity(6);
-
-            // Current frame
-            callstack.push(state.pc as u64);
+            if self.annotate.is_some()
+                && let Some(opcode) = events.opcode
+            {
+                self.instructions.entry(pc).or_insert((opcode, 0)).1 += 1;
+            }
 
-            let mut fp = state.registers[8];
+            if self
+                .stacktraces
+                .iter()
+                .all(|(weighting, _)| events.count(*weighting) == 0)
+            {
+                return;
+            }
 
-            if fp == 0 {
-                self.stats.samples_skipped += 1;
+            let Some(stacktrace) =
+                self.collect_stacktrace(pc, registers, memory_source, memory_tracer, mmu, cycle)
+            else {
                 return;
+            };
+
+            for (weighting, stacktraces) in self.stacktraces.iter_mut() {
+                let count = events.count(*weighting);
+                if count > 0 {
+                    stacktraces.absorb(stacktrace.clone(), count);
+                }
             }
+        }
+
+        fn collect_stacktrace<MS, TR, MMU, C>(
+            &mut self,
+            pc: u32,
+            registers: &[u32; 32],
+            memory_source: &mut MS,
+            memory_tracer: &mut TR,
+            mmu: &mut MMU,
+            cycle: u32,
+        ) -> Option<Stacktrace>
+        where
+            MS: MemorySource,
+            TR: Tracer<C>,
+            MMU: MMUImplementation<MS, TR, C>,
+            C: MachineConfig,
+        {
+            self.stats.samples_total += 1;
 
-            loop {
+            let mut read_word = |address: u32| {
                 let mut trap = TrapReason::NoTrap;
 
-                let fpp = mmu.map_virtual_to_physical(
-                    fp,
+                let phys_address = mmu.map_virtual_to_physical(
+                    address,
                     crate::cycle::state::Mode::Machine,
                     crate::abstractions::memory::AccessType::MemLoad,
                     memory_source,
                     memory_tracer,
                     cycle,
+                    cycle,
                     &mut trap,
                 );
 
-                // TODO: remove once the issue with non complying functions is solved.
-                if fpp < 8 {
-                    break;
+                if trap.is_a_trap() {
+                    return None;
                 }
 
-                let addr = mem_read::<_, _, _, false>(
-                    memory_source,
-                    memory_tracer,
-                    fpp - 4,
-                    size_of::<u32>() as u32,
-                    crate::abstractions::memory::AccessType::MemLoad,
-                    cycle,
-                    cycle,
-                    &mut trap,
-                );
+                if phys_address % size_of::<u32>() as u64 != 0 {
+                    return None;
+                }
 
-                let next = mem_read::<_, _, _, false>(
-                    memory_source,
-                    memory_tracer,
-                    fpp - 8,
-                    size_of::<u32>() as u32,
+                // Read behind the guest's back: not announced to the memory, so it isn't checked
+                // for initialization, and not traced.
+                let value = memory_source.get(
+                    phys_address,
                     crate::abstractions::memory::AccessType::MemLoad,
-                    cycle,
-                    cycle,
                     &mut trap,
                 );
 
-                // TODO: Remove once the issue with non complying functions is solved.
-                if addr < 4 {
-                    break;
-                }
-                if next as u64 == fpp {
-                    break;
-                }
-                if addr == 0 {
-                    break;
-                }
+                (!trap.is_a_trap()).then_some(value)
+            };
+
+            let mut callstack = Vec::with_capacity(6);
+
+            // Current frame
+            callstack.push(pc as u64);
+
+            let mut registers = *registers;
+            let current_pc = pc;
+            let mut pc = pc;
+
+            while callstack.len() < MAX_STACK_DEPTH {
+                let first_frame = callstack.len() == 1;
+                let sp = registers[2];
 
-                // Subbing one instruction because the frame's return address point to instruction
-                // that follows the call, not the call itself. In case of inlining this can be
-                // several frames away.
-                let addr = addr - 4;
+                match self
+                    .unwinder
+                    .unwind_frame(pc, &mut registers, first_frame, &mut read_word)
+                {
+                    Unwound::Caller(return_address) => {
+                        // Subbing one instruction because the frame's return address point to
+                        // instruction that follows the call, not the call itself. In case of
+                        // inlining this can be several frames away.
+                        let caller_pc = return_address.wrapping_sub(4);
+
+                        // No progress was made, the CFI must be wrong.
+                        if caller_pc == pc && registers[2] == sp {
+                            break;
+                        }
 
-                callstack.push(addr as u64);
+                        callstack.push(caller_pc as u64);
+                        pc = caller_pc;
+                    }
+                    Unwound::Outermost => break,
+                    Unwound::NoCfi => {
+                        let fp = registers[8];
 
-                fp = next;
+                        if first_frame && fp == 0 {
+                            self.stats.samples_skipped += 1;
+                            return None;
+                        }
+
+                        walk_frame_pointers(fp, &mut callstack, &mut read_word);
+                        break;
+                    }
+                }
             }
 
+            let symbol_info = &self.symbol_info;
+
             let mut stackframes = Vec::with_capacity(8);
 
             for (i, addr) in callstack.iter().enumerate() {
@@ -364,79 +868,289 @@ mod diag {
 
                 let (frames, section_offset) = match r {
                     Some(r) => r,
-                    // None if stackframes.len() != 0 => panic!("Non top frame couldn't be retreived."),
-                    None => break,
+                    None => {
+                        match symbol_info.symbols.lookup(*addr) {
+                            Some(symbol) => {
+                                self.stats.frames_from_symbol_table += 1;
+                                stackframes.push(FrameKey::Symbol(symbol.address));
+                            }
+                            None => {
+                                self.stats.frames_unknown += 1;
+                                stackframes.push(FrameKey::Unknown(*addr));
+                            }
+                        }
+                        continue;
+                    }
                 };
 
                 for frame in frames {
                     let offset = frame.dw_die_offset.unwrap();
-                    stackframes.push(FrameKey {
+                    stackframes.push(FrameKey::Dwarf {
                         section_offset,
                         unit_offset: offset,
                     });
 
-                    if i == 0
-                        && false
-                            == symbol_info.is_address_traceable(
-                                &self.dwarf_cache,
-                                state.pc as u64,
-                                &frame,
-                            )
-                    {
-                        // We're in a service code.
-                        self.stats.samples_service += 1;
-                        return;
+                    if i == 0 {
+                        match symbol_info.is_address_traceable(
+                            &self.dwarf_cache,
+                            current_pc as u64,
+                            &frame,
+                        ) {
+                            Some(true) => {}
+                            Some(false) => {
+                                // We're in a service code.
+                                self.stats.samples_service += 1;
+                                return None;
+                            }
+                            None => self.stats.samples_without_prologue_info += 1,
+                        }
                     }
                 }
             }
 
             if stackframes.len() == 0 {
                 self.stats.samples_failed += 1;
-                return;
+                return None;
             }
             self.stats.samples_success += 1;
 
-            let stacktrace = Stacktrace::new(stackframes);
+            Some(Stacktrace::new(stackframes))
+        }
 
-            self.stacktraces.absorb(stacktrace);
+        /// Resolves the stacktraces collected for the weighting into frames with names and source
+        /// locations.
+        pub(crate) fn profile(&self, weighting: ProfilerWeighting) -> Profile {
+            let mut frames = Vec::new();
+            let mut frame_indexes = HashMap::new();
+
+            let stacktraces = self
+                .stacktraces
+                .iter()
+                .find(|(w, _)| *w == weighting)
+                .map(|(_, stacktraces)| stacktraces);
+
+            let stacks = stacktraces
+                .into_iter()
+                .flat_map(|stacktraces| stacktraces.traces.iter())
+                .map(|(st, c)| {
+                    let stack = st
+                        .frames
+                        .iter()
+                        .rev()
+                        .map(|frame| {
+                            *frame_indexes.entry(frame).or_insert_with(|| {
+                                frames.push(self.resolve_frame(frame));
+                                frames.len() - 1
+                            })
+                        })
+                        .collect::<Vec<_>>();
+
+                    (stack, *c)
+                })
+                .collect();
+
+            Profile {
+                frames,
+                stacks,
+                frequency_recip: match weighting {
+                    ProfilerWeighting::Cycles => self.frequency_recip,
+                    _ => 1,
+                },
+                weighting,
+            }
         }
 
-        pub(crate) fn write_stacktrace(&self) {
-            let file = match std::fs::File::create(&self.output_path) {
-                Err(why) => panic!("couldn't create file {}", why),
-                Ok(file) => file,
-            };
+        fn resolve_frame(&self, frame: &FrameKey) -> ProfileFrame {
+            match frame {
+                FrameKey::Dwarf {
+                    section_offset,
+                    unit_offset,
+                } => {
+                    let info = self
+                        .dwarf_cache
+                        .unit_data
+                        .get(section_offset)
+                        .unwrap()
+                        .frames
+                        .get(unit_offset)
+                        .unwrap();
+
+                    ProfileFrame {
+                        name: info.name.clone(),
+                        file: info.file.clone(),
+                        line: info.line,
+                    }
+                }
+                FrameKey::Symbol(address) => ProfileFrame {
+                    name: self
+                        .symbol_info
+                        .symbols
+                        .lookup(*address)
+                        .unwrap()
+                        .name
+                        .clone(),
+                    file: None,
+                    line: None,
+                },
+                FrameKey::Unknown(address) => ProfileFrame {
+                    name: format!("[unknown 0x{:08x}]", address),
+                    file: None,
+                    line: None,
+                },
+            }
+        }
+
+        /// Joins the instruction counts with function names and source lines.
+        pub(crate) fn annotation_report(&mut self) -> AnnotationReport {
+            let instructions = self
+                .instructions
+                .iter()
+                .map(|(pc, (instruction, count))| {
+                    let address = *pc as u64;
+                    let (file, line) = self
+                        .symbol_info
+                        .source_line(&mut self.dwarf_cache, address)
+                        .unzip();
+
+                    AnnotatedInstruction {
+                        pc: *pc,
+                        instruction: *instruction,
+                        count: *count,
+                        function: self
+                            .symbol_info
+                            .function_name(&mut self.dwarf_cache, address)
+                            .map(str::to_owned),
+                        file,
+                        line,
+                    }
+                })
+                .collect();
+
+            AnnotationReport::new(instructions)
+        }
+
+        /// Names of the frames of the stack of the instruction at `pc`, innermost first.
+        pub(crate) fn backtrace<MS, TR, MMU, C>(
+            &mut self,
+            pc: u32,
+            registers: &[u32; 32],
+            memory_source: &mut MS,
+            memory_tracer: &mut TR,
+            mmu: &mut MMU,
+            cycle: u32,
+        ) -> Vec<String>
+        where
+            MS: MemorySource,
+            TR: Tracer<C>,
+            MMU: MMUImplementation<MS, TR, C>,
+            C: MachineConfig,
+        {
+            self.collect_stacktrace(pc, registers, memory_source, memory_tracer, mmu, cycle)
+                .iter()
+                .flat_map(|stacktrace| stacktrace.frames.iter())
+                .map(|frame| self.resolve_frame(frame).name)
+                .collect()
+        }
+
+        /// Joins the stack depths with function names.
+        pub(crate) fn stack_usage_report(&mut self) -> Option<StackUsageReport> {
+            let high_water_stack = self
+                .high_water_stack
+                .iter()
+                .flat_map(|stacktrace| stacktrace.frames.iter())
+                .map(|frame| self.resolve_frame(frame).name)
+                .collect();
+
+            let (config, stack_usage) = self.stack_usage.as_ref()?;
+            let report = stack_usage.report(config, high_water_stack, |pc| {
+                self.symbol_info
+                    .function_name(&mut self.dwarf_cache, pc as u64)
+                    .map(str::to_owned)
+            });
+
+            Some(report)
+        }
+
+        fn write_stack_usage(&mut self) {
+            let report = self.stack_usage_report().unwrap();
+            let path = self.stack_usage.as_ref().unwrap().0.output_path();
 
-            let mut mapped = Vec::with_capacity(self.stacktraces.traces.len());
+            if let Err(why) = std::fs::write(path, report.to_string()) {
+                panic!("couldn't write {}: {}", path.display(), why)
+            }
+        }
+
+        /// Names the call sites and call stacks of the allocations.
+        pub(crate) fn heap_report(&self) -> Option<HeapReport> {
+            let (_, heap) = self.heap.as_ref()?;
 
-            for (st, c) in &self.stacktraces.traces {
-                let names = st
-                    .frames
+            let mut frames = HashMap::new();
+            let report = heap.report(|stack| {
+                stack
                     .iter()
-                    .rev()
+                    .flat_map(|stacktrace| stacktrace.frames.iter())
                     .map(|frame| {
-                        self.dwarf_cache
-                            .unit_data
-                            .get(&frame.section_offset)
-                            .unwrap()
-                            .frames
-                            .get(&frame.unit_offset)
-                            .unwrap()
-                            .name
-                            .as_str()
+                        frames
+                            .entry(frame.clone())
+                            .or_insert_with(|| self.resolve_frame(frame).name)
+                            .clone()
                     })
-                    .collect::<Vec<_>>();
-                names
-                    .join(";")
-                    .op(|x| format!("{} {}", x, c).to_owned().to(|x| mapped.push(x)));
+                    .collect()
+            });
+
+            Some(report)
+        }
+
+        pub(crate) fn write_stacktrace(&mut self) {
+            if self.stack_usage.is_some() {
+                self.write_stack_usage();
             }
 
-            let mut opts = inferno::flamegraph::Options::default();
+            if let Some(report) = self.heap_report() {
+                let path = self.heap.as_ref().unwrap().0.output_path();
+
+                if let Err(why) = std::fs::write(path, report.to_string()) {
+                    panic!("couldn't write {}: {}", path.display(), why)
+                }
+            }
 
-            opts.reverse_stack_order = self.reverse_graph;
+            if let Some(config) = self.annotate.clone() {
+                let report = self.annotation_report().render(&config);
 
-            inferno::flamegraph::from_lines(&mut opts, mapped.iter().map(|x| x.as_str()), file)
-                .unwrap();
+                if let Err(why) = std::fs::write(config.output_path(), report) {
+                    panic!("couldn't write {}: {}", config.output_path().display(), why)
+                }
+            }
+
+            if let Some((config, call_trace)) = &self.call_trace {
+                let mut names = HashMap::new();
+                let trace = call_trace.render(config, |address| {
+                    names
+                        .entry(address)
+                        .or_insert_with(|| {
+                            self.symbol_info
+                                .function_name(&mut self.dwarf_cache, address as u64)
+                                .map(str::to_owned)
+                                .unwrap_or_else(|| format!("[unknown 0x{:08x}]", address))
+                        })
+                        .clone()
+                });
+
+                if let Err(why) = std::fs::write(config.output_path(), trace) {
+                    panic!("couldn't write {}: {}", config.output_path().display(), why)
+                }
+            }
+
+            for (weighting, _) in &self.stacktraces {
+                let profile = self.profile(*weighting);
+
+                for output in &self.outputs {
+                    let output = output.for_weighting(*weighting);
+                    if let Err(why) = profile.write(&output, self.reverse_graph) {
+                        panic!("couldn't write {}: {}", output.path().display(), why)
+                    }
+                }
+            }
         }
 
         pub(crate) fn print_stats(&self) {
@@ -444,28 +1158,67 @@ mod diag {
         }
     }
 
-    #[derive(Debug, PartialEq, Eq, Hash)]
-    struct FrameKey {
-        section_offset: UnitSectionOffset,
-        unit_offset: UnitOffset<usize>,
+    /// Appends return addresses found by following the frame pointer chain. Only used for code
+    /// that is not covered by CFI, as it requires the guest to be built with frame pointers.
+    fn walk_frame_pointers(
+        mut fp: u32,
+        callstack: &mut Vec<u64>,
+        mut read_word: impl FnMut(u32) -> Option<u32>,
+    ) {
+        while callstack.len() < MAX_STACK_DEPTH {
+            // TODO: remove once the issue with non complying functions is solved.
+            if fp < 8 {
+                break;
+            }
+
+            let (Some(addr), Some(next)) = (read_word(fp - 4), read_word(fp - 8)) else {
+                break;
+            };
+
+            // TODO: Remove once the issue with non complying functions is solved.
+            if addr < 4 {
+                break;
+            }
+            if next == fp {
+                break;
+            }
+
+            callstack.push((addr - 4) as u64);
+
+            fp = next;
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+    enum FrameKey {
+        Dwarf {
+            section_offset: UnitSectionOffset,
+            unit_offset: UnitOffset<usize>,
+        },
+        // Start address of the ELF symbol.
+        Symbol(u64),
+        Unknown(u64),
     }
 
     #[derive(Debug)]
     struct FrameInfo {
-        // Address of one instruction beyond last the prologue instruction.
-        prologue_end: u64,
+        // Address of one instruction beyond last the prologue instruction. Missing for code
+        // without line info, e.g. hand-written assembly.
+        prologue_end: Option<u64>,
         // Address of the first epilogue instruction.
         epilogue_begin: u64,
         #[allow(dead_code)]
         no_return: bool,
         #[allow(dead_code)]
         is_inlined: bool,
-        is_tracked: bool,
         name: String,
+        // Declaration of the function.
+        file: Option<String>,
+        line: Option<u64>,
     }
 
     struct UnitInfo<'a> {
-        line_program_complete: CompleteLineProgram<EndianSlice<'a, RunTimeEndian>, usize>,
+        line_program_complete: Option<CompleteLineProgram<EndianSlice<'a, RunTimeEndian>, usize>>,
         line_sequences: Vec<gimli::LineSequence<EndianSlice<'a, RunTimeEndian>>>,
         frames: HashMap<UnitOffset<usize>, FrameInfo>,
     }
@@ -474,10 +1227,39 @@ mod diag {
         unit_data: HashMap<UnitSectionOffset, UnitInfo<'static>>,
     }
 
+    impl DwarfCache {
+        fn unit_info(
+            &mut self,
+            unit: &gimli::Unit<EndianSlice<'static, RunTimeEndian>>,
+        ) -> &mut UnitInfo<'static> {
+            self.unit_data
+                .entry(unit.header.offset())
+                .or_insert_with(|| {
+                    match unit
+                        .line_program
+                        .clone()
+                        .and_then(|program| program.sequences().ok())
+                    {
+                        Some((line_program, sequences)) => UnitInfo {
+                            line_program_complete: Some(line_program),
+                            line_sequences: sequences,
+                            frames: HashMap::new(),
+                        },
+                        None => UnitInfo {
+                            line_program_complete: None,
+                            line_sequences: Vec::new(),
+                            frames: HashMap::new(),
+                        },
+                    }
+                })
+        }
+    }
+
     #[allow(dead_code)] // Struct has data dependencies
     struct SymbolInfo {
         // Safety: Values must be dropped in the dependency order.
         ctx: Context<EndianSlice<'static, RunTimeEndian>>,
+        symbols: SymbolTable,
         object: object::File<'static>,
         // Holds the slice that all above fields reference.
         mmap: Mmap,
@@ -518,7 +1300,14 @@ mod diag {
 
             let ctx = Context::from_dwarf(dwarf).unwrap();
 
-            SymbolInfo { mmap, object, ctx }
+            let symbols = SymbolTable::new(&object);
+
+            SymbolInfo {
+                mmap,
+                object,
+                symbols,
+                ctx,
+            }
         }
 
         fn is_address_traceable(
@@ -526,16 +1315,14 @@ mod diag {
             cache: &DwarfCache,
             address: u64,
             frame: &Frame<'_, EndianSlice<'_, RunTimeEndian>>,
-        ) -> bool {
+        ) -> Option<bool> {
             let (_dw, unit) = self
                 .ctx
                 .find_dwarf_and_unit(address)
                 .skip_all_loads()
                 .expect("Frame existence implies unit.");
 
-            let mut tracked = false;
-
-            let r = cache
+            cache
                 .unit_data
                 .get(&unit.header.offset())
                 .expect("Unit info should've been created on frame loading.")
@@ -543,21 +1330,9 @@ mod diag {
                 .get(&frame.dw_die_offset.unwrap())
                 .expect("Frame info should've been created on frame loading.")
                 .to(|x| {
-                    if x.is_tracked {
-                        println!("is_traceable:");
-                        println!("address: 0x{:08x}", address);
-                        println!("address: {}", address);
-                        println!("{:#?}", x);
-                        tracked = true;
-                    }
-                    address >= x.prologue_end && address < x.epilogue_begin
-                });
-
-            if tracked {
-                println!("r {}", r);
-            }
-
-            r
+                    x.prologue_end
+                        .map(|prologue_end| address >= prologue_end && address < x.epilogue_begin)
+                })
         }
 
         #[allow(dead_code)]
@@ -683,6 +1458,51 @@ mod diag {
             }
         }
 
+        /// Source file and line of the instruction at the address, from the line program rows.
+        fn source_line(&self, cache: &mut DwarfCache, address: u64) -> Option<(String, u64)> {
+            let (dw, unit) = self.ctx.find_dwarf_and_unit(address).skip_all_loads()?;
+            let unit_info = cache.unit_info(unit);
+
+            let sequence = unit_info
+                .line_sequences
+                .iter()
+                .find(|s| address >= s.start && address < s.end)?;
+            let mut rows = unit_info
+                .line_program_complete
+                .as_ref()?
+                .resume_from(sequence);
+
+            let mut found = None;
+            while let Ok(Some((header, row))) = rows.next_row() {
+                if row.address() > address {
+                    break;
+                }
+                if let Some(line) = row.line() {
+                    found = Some((file_path(dw, unit, header, row.file_index()), line.get()));
+                }
+            }
+
+            let (file, line) = found?;
+            Some((file?, line))
+        }
+
+        /// Name of the innermost function containing the address, from DWARF or the symbol table.
+        fn function_name<'a>(&'a self, cache: &'a mut DwarfCache, address: u64) -> Option<&'a str> {
+            let Some((frames, section_offset)) = self.get_address_frames(cache, address) else {
+                return self
+                    .symbols
+                    .lookup(address)
+                    .map(|symbol| symbol.name.as_str());
+            };
+            let offset = frames.first()?.dw_die_offset?;
+
+            cache
+                .unit_data
+                .get(&section_offset)
+                .and_then(|unit| unit.frames.get(&offset))
+                .map(|frame| frame.name.as_str())
+        }
+
         fn get_address_frames<'a>(
             &'a self,
             cache: &mut DwarfCache,
@@ -691,27 +1511,9 @@ mod diag {
             Vec<Frame<'a, EndianSlice<'a, RunTimeEndian>>>,
             UnitSectionOffset,
         )> {
-            let (_dw, unit, unit_info) =
+            let (dw, unit, unit_info) =
                 if let Some((dw, unit)) = self.ctx.find_dwarf_and_unit(address).skip_all_loads() {
-                    let unit_locator = unit.header.offset();
-
-                    let unit_info = cache.unit_data.entry(unit_locator).or_insert_with(|| {
-                        let (line_program, sequences) = unit
-                            .line_program
-                            .clone()
-                            .unwrap()
-                            .clone()
-                            .sequences()
-                            .unwrap();
-
-                        UnitInfo {
-                            line_program_complete: line_program,
-                            line_sequences: sequences,
-                            frames: HashMap::new(),
-                        }
-                    });
-
-                    (dw, unit, unit_info)
+                    (dw, unit, cache.unit_info(unit))
                 } else {
                     return None;
                 };
@@ -735,110 +1537,75 @@ mod diag {
             let mut result = Vec::with_capacity(8);
 
             while let Ok(Some(frame)) = frames.next() {
-                let mut tracked = false;
-
-                if false
-                    && frame
-                        .function
-                        .as_ref()
-                        .unwrap()
-                        .demangle()
-                        .unwrap()
-                        .contains("talc::talc::Talc<O>::malloc")
-                {
-                    tracked = true;
-                    // panic!("found!!!!!! {}", frame.function.as_ref().unwrap().demangle().unwrap());
-                }
+                // Frames that only carry a location can't be told apart, so they are left for the
+                // symbol table.
+                let (Some(dw_die_offset), Some(function)) =
+                    (frame.dw_die_offset, frame.function.as_ref())
+                else {
+                    continue;
+                };
 
-                unit_info
-                    .frames
-                    .entry(frame.dw_die_offset.unwrap())
-                    .or_insert_with(|| {
-                        let sequence = &unit_info.line_sequences;
-                        for s in sequence {
-                            if address >= s.start && address < s.end {
-                                let mut sm = unit_info.line_program_complete.resume_from(&s);
-
-                                let mut prologue_end = None;
-                                let mut epilogue_begin = None;
-                                let mut no_return = false;
-                                let mut is_inlined = false;
-
-                                while let Ok(Some((_h, r))) = sm.next_row() {
-                                    assert!(r.address() <= s.end);
-
-                                    if r.prologue_end() {
-                                        prologue_end = Some(r.address())
-                                    }
-                                    if r.epilogue_begin() {
-                                        epilogue_begin = Some(r.address())
-                                    }
-                                }
+                unit_info.frames.entry(dw_die_offset).or_insert_with(|| {
+                    let mut prologue_end = None;
+                    let mut epilogue_begin = None;
+                    let mut no_return = false;
+                    let mut is_inlined = false;
 
-                                let cursor = unit
-                                    .entries_at_offset(frame.dw_die_offset.unwrap())
-                                    .unwrap()
-                                    .op(|x| {
-                                        x.next_entry()
-                                            .expect("A unit must exist at the provided offset.");
-                                    });
+                    let sequence = unit_info
+                        .line_sequences
+                        .iter()
+                        .find(|s| address >= s.start && address < s.end);
 
-                                let die = cursor.current().unwrap();
+                    if let Some(s) = sequence
+                        && let Some(line_program) = &unit_info.line_program_complete
+                    {
+                        let mut sm = line_program.resume_from(s);
 
-                                match die.tag() {
-                                    gimli::DW_TAG_inlined_subroutine => is_inlined = true,
-                                    _ => (),
-                                }
+                        while let Ok(Some((_h, r))) = sm.next_row() {
+                            if r.prologue_end() {
+                                prologue_end = Some(r.address())
+                            }
+                            if r.epilogue_begin() {
+                                epilogue_begin = Some(r.address())
+                            }
+                        }
+                    }
 
-                                let mut attrs = die.attrs();
+                    let cursor = unit.entries_at_offset(dw_die_offset).ok().map(|cursor| {
+                        cursor.op(|x| {
+                            let _ = x.next_entry();
+                        })
+                    });
 
-                                while let Ok(Some(attr)) = attrs.next() {
-                                    match attr.name() {
-                                        gimli::DW_AT_noreturn if epilogue_begin.is_some() => {
-                                            panic!(
-                                            "Non returning functions shouln't have an epilogue."
-                                        )
-                                        }
-                                        gimli::DW_AT_noreturn => no_return = true,
-                                        _ => (),
-                                    }
-                                }
+                    if let Some(die) = cursor.as_ref().and_then(|cursor| cursor.current()) {
+                        if die.tag() == gimli::DW_TAG_inlined_subroutine {
+                            is_inlined = true;
+                        }
 
-                                let r = FrameInfo {
-                                    prologue_end: prologue_end.expect(
-                                        format!(
-                                            "A function must have a prologue. 0x{:08x}",
-                                            address
-                                        )
-                                        .as_str(),
-                                    ),
-                                    epilogue_begin: epilogue_begin.unwrap_or_else(|| u64::MAX),
-                                    no_return,
-                                    is_inlined,
-                                    is_tracked: tracked,
-                                    name: frame
-                                        .function
-                                        .as_ref()
-                                        .unwrap()
-                                        .demangle()
-                                        .unwrap()
-                                        .to_string(),
-                                };
-
-                                if tracked {
-                                    println!("{:#?}", r);
-                                }
+                        let mut attrs = die.attrs();
 
-                                return r;
+                        while let Ok(Some(attr)) = attrs.next() {
+                            if attr.name() == gimli::DW_AT_noreturn {
+                                no_return = true;
                             }
                         }
+                    }
 
-                        panic!(
-                            "An line sequence was not found for frame {:?}, addr {}",
-                            frame.function.as_ref().unwrap().demangle(),
-                            address
-                        );
-                    });
+                    let (file, line) = decl_location(dw, unit, dw_die_offset).unwrap_or_default();
+
+                    FrameInfo {
+                        prologue_end,
+                        epilogue_begin: epilogue_begin.unwrap_or(u64::MAX),
+                        no_return,
+                        is_inlined,
+                        file,
+                        line,
+                        name: function
+                            .demangle()
+                            .map(|name| name.to_string())
+                            .unwrap_or_else(|_| format!("[unknown 0x{:08x}]", address)),
+                    }
+                });
 
                 // Safety: The borrow checker assumes that the frame lives for 'const (derived from
                 // `ctx` field in `Self`). The actual lifetime is the lifetime of `self`. So we're
@@ -846,11 +1613,153 @@ mod diag {
                 unsafe { result.push(std::mem::transmute(frame)) };
             }
 
+            if result.is_empty() {
+                return None;
+            }
+
             Some((result, unit.header.offset()))
         }
     }
 
-    #[derive(Debug)]
+    struct SymbolEntry {
+        address: u64,
+        size: u64,
+        name: String,
+    }
+
+    /// Demangled ELF symbols, used for code that is not described by DWARF.
+    struct SymbolTable {
+        // Sorted by address.
+        entries: Vec<SymbolEntry>,
+    }
+
+    impl SymbolTable {
+        fn new(object: &object::File<'static>) -> Self {
+            let entries = object
+                .symbol_map()
+                .symbols()
+                .iter()
+                .map(|symbol| SymbolEntry {
+                    address: symbol.address(),
+                    size: symbol.size(),
+                    name: addr2line::demangle_auto(symbol.name().into(), None).into_owned(),
+                })
+                .collect();
+
+            Self { entries }
+        }
+
+        /// Addresses of the symbols with the name. Rust symbols match with or without their hash.
+        fn addresses_of<'a>(&'a self, name: &'a str) -> impl Iterator<Item = u64> + 'a {
+            self.entries
+                .iter()
+                .filter(move |symbol| {
+                    symbol.name == name
+                        || symbol
+                            .name
+                            .strip_prefix(name)
+                            .is_some_and(|hash| hash.starts_with("::h"))
+                })
+                .map(|symbol| symbol.address)
+        }
+
+        fn lookup(&self, address: u64) -> Option<&SymbolEntry> {
+            let idx = self
+                .entries
+                .partition_point(|symbol| symbol.address <= address)
+                .checked_sub(1)?;
+            let symbol = &self.entries[idx];
+
+            (symbol.size == 0 || address - symbol.address < symbol.size).then_some(symbol)
+        }
+    }
+
+    fn file_path(
+        dw: &gimli::Dwarf<EndianSlice<'_, RunTimeEndian>>,
+        unit: &gimli::Unit<EndianSlice<'_, RunTimeEndian>>,
+        header: &gimli::LineProgramHeader<EndianSlice<'_, RunTimeEndian>>,
+        index: u64,
+    ) -> Option<String> {
+        let file = header.file(index)?;
+
+        let mut path = PathBuf::new();
+        if let Some(dir) = file.directory(header) {
+            path.push(dw.attr_string(unit, dir).ok()?.to_string_lossy().as_ref());
+        }
+        path.push(
+            dw.attr_string(unit, file.path_name())
+                .ok()?
+                .to_string_lossy()
+                .as_ref(),
+        );
+
+        Some(path.display().to_string())
+    }
+
+    /// Source file and line where the function behind the DIE is declared. Inlined and
+    /// out-of-line instances refer to the declaration through their origin.
+    fn decl_location(
+        dw: &gimli::Dwarf<EndianSlice<'_, RunTimeEndian>>,
+        unit: &gimli::Unit<EndianSlice<'_, RunTimeEndian>>,
+        mut offset: UnitOffset<usize>,
+    ) -> Option<(Option<String>, Option<u64>)> {
+        for _ in 0..4 {
+            let die = unit.entry(offset).ok()?;
+
+            if let Some(gimli::AttributeValue::FileIndex(index)) =
+                die.attr_value(gimli::DW_AT_decl_file).ok()?
+            {
+                let header = unit.line_program.as_ref()?.header();
+                let path = file_path(dw, unit, header, index)?;
+
+                let line = die
+                    .attr_value(gimli::DW_AT_decl_line)
+                    .ok()?
+                    .and_then(|line| line.udata_value());
+
+                return Some((Some(path), line));
+            }
+
+            let origin = match die.attr_value(gimli::DW_AT_abstract_origin).ok()? {
+                Some(origin) => Some(origin),
+                None => die.attr_value(gimli::DW_AT_specification).ok()?,
+            };
+
+            match origin {
+                Some(gimli::AttributeValue::UnitRef(origin)) => offset = origin,
+                _ => return None,
+            }
+        }
+
+        None
+    }
+
+    /// Maps addresses of the simulated binary to the names of the functions they belong to
+    pub struct Symbolizer {
+        // Safety: DwarfCache references data in symbol info.
+        dwarf_cache: DwarfCache,
+        symbol_info: SymbolInfo,
+    }
+
+    impl Symbolizer {
+        pub(crate) fn new<P: AsRef<Path>>(symbols_path: P) -> Self {
+            Self {
+                dwarf_cache: DwarfCache {
+                    unit_data: HashMap::new(),
+                },
+                symbol_info: SymbolInfo::new(symbols_path),
+            }
+        }
+
+        /// Name of the innermost (possibly inlined) function that contains the address. Falls
+        /// back to the ELF symbol table for code without debug info.
+        pub fn function_name(&mut self, address: u64) -> Option<&str> {
+            self.symbol_info
+                .function_name(&mut self.dwarf_cache, address)
+        }
+    }
+
+    #[derive(Clone, Debug)]
     struct Stacktrace {
         frames: Vec<FrameKey>,
     }
@@ -890,11 +1799,8 @@ mod diag {
             }
         }
 
-        fn absorb(&mut self, stacktrace: Stacktrace) {
-            self.traces
-                .entry(stacktrace)
-                .and_modify(|x| *x += 1)
-                .or_insert(1);
+        fn absorb(&mut self, stacktrace: Stacktrace, weight: usize) {
+            *self.traces.entry(stacktrace).or_insert(0) += weight;
         }
     }
 }
@@ -0,0 +1,228 @@
+//! Exact per-instruction execution counts joined with disassembly and source lines, in the
+//! spirit of `perf annotate`.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::analysis::decode::decode;
+
+#[derive(Clone, Debug)]
+pub struct AnnotateConfig {
+    output_path: PathBuf,
+    /// Functions to print an annotated listing for, matched by a substring of the name.
+    pub functions: Vec<String>,
+    /// Number of source lines and instructions in the hot-spot tables.
+    pub hottest: usize,
+}
+
+impl AnnotateConfig {
+    pub fn new(output_path: PathBuf) -> Self {
+        Self {
+            output_path,
+            functions: Vec::new(),
+            hottest: 50,
+        }
+    }
+
+    pub fn output_path(&self) -> &Path {
+        &self.output_path
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AnnotatedInstruction {
+    pub pc: u32,
+    pub instruction: u32,
+    pub count: u64,
+    /// Innermost (possibly inlined) function.
+    pub function: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u64>,
+}
+
+impl AnnotatedInstruction {
+    pub fn disassembly(&self) -> String {
+        match decode(self.instruction) {
+            Some(decoded) => decoded.to_string(),
+            None => format!(".word 0x{:08x}", self.instruction),
+        }
+    }
+
+    fn location(&self) -> Option<String> {
+        Some(format!("{}:{}", self.file.as_ref()?, self.line?))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SourceLineCount {
+    pub file: String,
+    pub line: u64,
+    pub count: u64,
+    pub percentage: f64,
+    pub function: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AnnotationReport {
+    pub total_count: u64,
+    /// Every executed instruction, ordered by address.
+    pub instructions: Vec<AnnotatedInstruction>,
+}
+
+impl AnnotationReport {
+    pub fn new(mut instructions: Vec<AnnotatedInstruction>) -> Self {
+        instructions.sort_by_key(|instruction| instruction.pc);
+
+        Self {
+            total_count: instructions.iter().map(|i| i.count).sum(),
+            instructions,
+        }
+    }
+
+    fn percentage(&self, count: u64) -> f64 {
+        100.0 * count as f64 / self.total_count.max(1) as f64
+    }
+
+    /// Source lines by the number of instructions executed for them, most executed first.
+    pub fn hottest_lines(&self, limit: usize) -> Vec<SourceLineCount> {
+        let mut lines = HashMap::<(&str, u64), SourceLineCount>::new();
+
+        for instruction in &self.instructions {
+            let (Some(file), Some(line)) = (&instruction.file, instruction.line) else {
+                continue;
+            };
+
+            lines
+                .entry((file, line))
+                .or_insert_with(|| SourceLineCount {
+                    file: file.clone(),
+                    line,
+                    count: 0,
+                    percentage: 0.0,
+                    function: instruction.function.clone(),
+                })
+                .count += instruction.count;
+        }
+
+        let mut lines = lines
+            .into_values()
+            .map(|mut line| {
+                line.percentage = self.percentage(line.count);
+                line
+            })
+            .collect::<Vec<_>>();
+        lines.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then(a.file.cmp(&b.file))
+                .then(a.line.cmp(&b.line))
+        });
+        lines.truncate(limit);
+
+        lines
+    }
+
+    /// Instructions by execution count, most executed first.
+    pub fn hottest_instructions(&self, limit: usize) -> Vec<&AnnotatedInstruction> {
+        let mut instructions = self.instructions.iter().collect::<Vec<_>>();
+        instructions.sort_by(|a, b| b.count.cmp(&a.count).then(a.pc.cmp(&b.pc)));
+        instructions.truncate(limit);
+
+        instructions
+    }
+
+    /// Listing of the executed instructions of every function whose name contains `function`,
+    /// with the source location printed whenever it changes.
+    pub fn annotate_function(&self, function: &str) -> String {
+        let mut listing = String::new();
+        let mut current_function = None;
+        let mut current_location = None;
+
+        for instruction in &self.instructions {
+            let Some(name) = &instruction.function else {
+                continue;
+            };
+            if !name.contains(function) {
+                continue;
+            }
+
+            if current_function != Some(name) {
+                writeln!(listing, "\n{}:", name).unwrap();
+                current_function = Some(name);
+                current_location = None;
+            }
+
+            let location = instruction.location();
+            if location.is_some() && location != current_location {
+                writeln!(listing, "  {}", location.as_ref().unwrap()).unwrap();
+                current_location = location;
+            }
+
+            writeln!(
+                listing,
+                "{:>14} {:>7.2}%  0x{:08x}  {}",
+                instruction.count,
+                self.percentage(instruction.count),
+                instruction.pc,
+                instruction.disassembly()
+            )
+            .unwrap();
+        }
+
+        listing
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("report is always serializable")
+    }
+
+    /// Hot-spot tables followed by the listings of the configured functions.
+    pub fn render(&self, config: &AnnotateConfig) -> String {
+        let mut report = String::new();
+
+        writeln!(report, "Total instructions: {}", self.total_count).unwrap();
+        writeln!(report, "\nHottest source lines:").unwrap();
+        writeln!(report, "{:>14} {:>8}  location", "count", "%").unwrap();
+        for line in self.hottest_lines(config.hottest) {
+            writeln!(
+                report,
+                "{:>14} {:>7.2}%  {}:{}  {}",
+                line.count,
+                line.percentage,
+                line.file,
+                line.line,
+                line.function.as_deref().unwrap_or("")
+            )
+            .unwrap();
+        }
+
+        writeln!(report, "\nHottest instructions:").unwrap();
+        writeln!(
+            report,
+            "{:>14} {:>8}  {:<10}  instruction",
+            "count", "%", "pc"
+        )
+        .unwrap();
+        for instruction in self.hottest_instructions(config.hottest) {
+            writeln!(
+                report,
+                "{:>14} {:>7.2}%  0x{:08x}  {:<32}  {}",
+                instruction.count,
+                self.percentage(instruction.count),
+                instruction.pc,
+                instruction.disassembly(),
+                instruction.function.as_deref().unwrap_or("")
+            )
+            .unwrap();
+        }
+
+        for function in &config.functions {
+            report.push_str(&self.annotate_function(function));
+        }
+
+        report
+    }
+}
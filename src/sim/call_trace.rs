@@ -0,0 +1,204 @@
+//! Exact call/return trace in the spirit of the ftrace function graph tracer. Calls and returns
+//! are detected from the JAL/JALR semantics of the executed instructions, so unlike sampling it
+//! sees every call, no matter how short.
+//!
+//! A call is `jal ra, ..` or `jalr ra, ..`, a return is `jalr x0, 0(ra)`. Tail calls made with
+//! `jal x0`/`jalr x0` are not calls from the point of view of the shadow stack, so their callee
+//! shows up as a part of the caller.
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+const OPCODE_JAL: u32 = 0b1101111;
+const OPCODE_JALR: u32 = 0b1100111;
+const RA: u32 = 1;
+
+#[derive(Clone, Debug)]
+pub struct CallTraceConfig {
+    output_path: PathBuf,
+    /// Calls nested deeper than this are not printed. The depth is counted from the outermost
+    /// printed call.
+    pub max_depth: Option<usize>,
+    /// Only the calls of functions whose name contains one of these, and everything they call,
+    /// are printed. Everything is printed when empty.
+    pub functions: Vec<String>,
+}
+
+impl CallTraceConfig {
+    pub fn new(output_path: PathBuf) -> Self {
+        Self {
+            output_path,
+            max_depth: None,
+            functions: Vec::new(),
+        }
+    }
+
+    pub fn output_path(&self) -> &Path {
+        &self.output_path
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Call {
+    /// Address of the called function.
+    pub callee: u32,
+    /// Address of the call instruction.
+    pub call_site: u32,
+    /// Depth in the shadow stack, the outermost call is at depth 0.
+    pub depth: usize,
+    /// Cycle of the call instruction.
+    pub entry_cycle: u32,
+    /// Cycle of the return instruction, if the call has returned.
+    pub exit_cycle: Option<u32>,
+}
+
+impl Call {
+    /// Cycles spent in the call, including both the call and the return instructions.
+    pub fn cycles(&self) -> Option<u32> {
+        Some(self.exit_cycle? - self.entry_cycle + 1)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CallTrace {
+    /// Every call in the order of entry.
+    pub calls: Vec<Call>,
+    // Indices of the calls that have not returned yet.
+    shadow_stack: Vec<usize>,
+}
+
+impl CallTrace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Updates the shadow stack with the instruction `opcode` executed at `pc`, after which the
+    /// execution continued at `next_pc`. Must not be called for instructions that trapped.
+    pub fn step(&mut self, pc: u32, opcode: u32, next_pc: u32, cycle: u32) {
+        let rd = (opcode >> 7) & 0x1f;
+        let rs1 = (opcode >> 15) & 0x1f;
+        let funct3 = (opcode >> 12) & 0x7;
+        let imm = opcode >> 20;
+
+        let is_call = match opcode & 0x7f {
+            OPCODE_JAL => rd == RA,
+            OPCODE_JALR => funct3 == 0 && rd == RA,
+            _ => false,
+        };
+        let is_return =
+            opcode & 0x7f == OPCODE_JALR && funct3 == 0 && rd == 0 && rs1 == RA && imm == 0;
+
+        if is_call {
+            self.shadow_stack.push(self.calls.len());
+            self.calls.push(Call {
+                callee: next_pc,
+                call_site: pc,
+                depth: self.shadow_stack.len() - 1,
+                entry_cycle: cycle,
+                exit_cycle: None,
+            });
+        } else if is_return {
+            // Returns that skip frames (e.g. unwinding) close every frame they skip. Returns
+            // that match no frame are from calls made before the trace started.
+            let Some(position) = self
+                .shadow_stack
+                .iter()
+                .rposition(|idx| self.calls[*idx].call_site.wrapping_add(4) == next_pc)
+            else {
+                return;
+            };
+
+            for idx in self.shadow_stack.drain(position..) {
+                self.calls[idx].exit_cycle = Some(cycle);
+            }
+        }
+    }
+
+    /// Indented call tree, with the calls named by `name`.
+    pub fn render(&self, config: &CallTraceConfig, mut name: impl FnMut(u32) -> String) -> String {
+        // The calls that pass the filters, with their printed depth and name.
+        let mut visible = Vec::new();
+        // Depth of the call that matched the symbol filter, while inside it.
+        let mut matched_depth = None;
+
+        for call in &self.calls {
+            if matched_depth.is_some_and(|depth| call.depth <= depth) {
+                matched_depth = None;
+            }
+
+            let callee = name(call.callee);
+            if matched_depth.is_none()
+                && (config.functions.is_empty()
+                    || config.functions.iter().any(|f| callee.contains(f.as_str())))
+            {
+                matched_depth = Some(call.depth);
+            }
+
+            let Some(root_depth) = matched_depth else {
+                continue;
+            };
+            let depth = match config.functions.is_empty() {
+                true => call.depth,
+                false => call.depth - root_depth,
+            };
+            if config.max_depth.is_some_and(|max_depth| depth > max_depth) {
+                continue;
+            }
+
+            visible.push((call, depth, callee));
+        }
+
+        let mut trace = String::new();
+        writeln!(
+            trace,
+            "{:>12} {:>12} {:>12}  function",
+            "entry", "exit", "cycles"
+        )
+        .unwrap();
+
+        let mut open = Vec::<(usize, String)>::new();
+        let close = |trace: &mut String, open: &mut Vec<(usize, String)>, depth: usize| {
+            while let Some((open_depth, _)) = open.last()
+                && *open_depth >= depth
+            {
+                let (open_depth, callee) = open.pop().unwrap();
+                writeln!(
+                    trace,
+                    "{:>38}  {}}} /* {} */",
+                    "",
+                    "  ".repeat(open_depth),
+                    callee
+                )
+                .unwrap();
+            }
+        };
+
+        for (idx, (call, depth, callee)) in visible.iter().enumerate() {
+            close(&mut trace, &mut open, *depth);
+
+            let is_leaf = visible
+                .get(idx + 1)
+                .map_or(true, |(_, next_depth, _)| next_depth <= depth);
+            let stamp = |value: Option<u32>| value.map_or("-".to_owned(), |v| v.to_string());
+
+            writeln!(
+                trace,
+                "{:>12} {:>12} {:>12}  {}{}(){}",
+                call.entry_cycle,
+                stamp(call.exit_cycle),
+                stamp(call.cycles()),
+                "  ".repeat(*depth),
+                callee,
+                if is_leaf { ";" } else { " {" }
+            )
+            .unwrap();
+
+            if !is_leaf {
+                open.push((*depth, callee.clone()));
+            }
+        }
+        close(&mut trace, &mut open, 0);
+
+        trace
+    }
+}
@@ -0,0 +1,373 @@
+//! Guest heap allocation tracking. Calls to the configured allocator functions are intercepted at
+//! their entry point, where the arguments are read, and at their return, where the result is.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+const RA: usize = 1;
+const SP: usize = 2;
+const A0: usize = 10;
+
+/// How an allocator function takes its arguments. Arguments are numbered from `a0`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AllocatorFunction {
+    /// Allocates `size` bytes and returns the pointer.
+    Alloc { size: usize },
+    /// Frees the pointer.
+    Free { ptr: usize },
+    /// Resizes the allocation at the pointer to `size` bytes and returns the new pointer.
+    Realloc { ptr: usize, size: usize },
+}
+
+#[derive(Clone, Debug)]
+pub struct HeapConfig {
+    output_path: PathBuf,
+    /// Allocator functions by symbol name. Rust symbols match with or without their hash.
+    pub functions: Vec<(String, AllocatorFunction)>,
+}
+
+impl HeapConfig {
+    /// Tracks `malloc`, `free` and `realloc`. `calloc` is not tracked, as the size is a product.
+    pub fn libc(output_path: PathBuf) -> Self {
+        Self {
+            output_path,
+            functions: vec![
+                ("malloc".to_owned(), AllocatorFunction::Alloc { size: 0 }),
+                ("free".to_owned(), AllocatorFunction::Free { ptr: 0 }),
+                (
+                    "realloc".to_owned(),
+                    AllocatorFunction::Realloc { ptr: 0, size: 1 },
+                ),
+            ],
+        }
+    }
+
+    /// Tracks the global allocator of Rust programs.
+    pub fn rust(output_path: PathBuf) -> Self {
+        Self {
+            output_path,
+            functions: vec![
+                (
+                    "__rust_alloc".to_owned(),
+                    AllocatorFunction::Alloc { size: 0 },
+                ),
+                (
+                    "__rust_alloc_zeroed".to_owned(),
+                    AllocatorFunction::Alloc { size: 0 },
+                ),
+                (
+                    "__rust_dealloc".to_owned(),
+                    AllocatorFunction::Free { ptr: 0 },
+                ),
+                (
+                    "__rust_realloc".to_owned(),
+                    AllocatorFunction::Realloc { ptr: 0, size: 3 },
+                ),
+            ],
+        }
+    }
+
+    pub fn output_path(&self) -> &Path {
+        &self.output_path
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Allocation<S> {
+    pub address: u32,
+    pub size: u32,
+    /// Address of the instruction that called the allocator.
+    pub call_site: u32,
+    pub cycle: u32,
+    pub stack: S,
+}
+
+#[derive(Clone, Debug)]
+struct PendingCall<S> {
+    function: AllocatorFunction,
+    arguments: [u32; 8],
+    return_address: u32,
+    sp: u32,
+    call_site: u32,
+    stack: S,
+}
+
+#[derive(Clone, Debug)]
+struct CallSiteCounts<S> {
+    allocations: usize,
+    bytes: u64,
+    // Stack of the first allocation made from the call site.
+    stack: S,
+}
+
+/// Follows the heap through the allocator calls. `S` is the call stack type.
+#[derive(Clone, Debug)]
+pub struct HeapTracker<S> {
+    // Allocator functions by entry address.
+    entries: HashMap<u32, AllocatorFunction>,
+    // The outermost allocator call being executed. Calls the allocator makes to itself are not
+    // tracked.
+    pending: Option<PendingCall<S>>,
+    live: HashMap<u32, Allocation<S>>,
+    heap_size: u64,
+    peak: Option<(u64, u32)>,
+    call_sites: HashMap<u32, CallSiteCounts<S>>,
+    allocations: usize,
+    frees: usize,
+    failed_allocations: usize,
+    unknown_frees: usize,
+}
+
+impl<S: Clone> HeapTracker<S> {
+    pub fn new(entries: HashMap<u32, AllocatorFunction>) -> Self {
+        Self {
+            entries,
+            pending: None,
+            live: HashMap::new(),
+            heap_size: 0,
+            peak: None,
+            call_sites: HashMap::new(),
+            allocations: 0,
+            frees: 0,
+            failed_allocations: 0,
+            unknown_frees: 0,
+        }
+    }
+
+    /// The allocator function entered at `pc`, if a new call is to be tracked.
+    pub fn intercepts(&self, pc: u32) -> Option<AllocatorFunction> {
+        match self.pending {
+            Some(_) => None,
+            None => self.entries.get(&pc).copied(),
+        }
+    }
+
+    /// Starts tracking the call of the allocator function that was just entered. `stack` is the
+    /// call stack of the caller.
+    pub fn enter(&mut self, function: AllocatorFunction, registers: &[u32; 32], stack: S) {
+        let return_address = registers[RA];
+
+        self.pending = Some(PendingCall {
+            function,
+            arguments: registers[A0..A0 + 8].try_into().unwrap(),
+            return_address,
+            sp: registers[SP],
+            call_site: return_address.wrapping_sub(4),
+            stack,
+        });
+    }
+
+    /// Completes the pending call if the instruction at `pc` is where it returns to.
+    pub fn observe(&mut self, pc: u32, registers: &[u32; 32], cycle: u32) {
+        let Some(pending) = &self.pending else {
+            return;
+        };
+        // The stack pointer tells a return from the recursive calls apart.
+        if pc != pending.return_address || registers[SP] != pending.sp {
+            return;
+        }
+
+        let pending = self.pending.take().unwrap();
+        let result = registers[A0];
+
+        match pending.function {
+            AllocatorFunction::Alloc { size } => {
+                self.allocate(&pending, result, pending.arguments[size], cycle);
+            }
+            AllocatorFunction::Free { ptr } => self.free(pending.arguments[ptr]),
+            AllocatorFunction::Realloc { ptr, size } => {
+                let ptr = pending.arguments[ptr];
+                let size = pending.arguments[size];
+
+                // The old allocation stays if a reallocation fails.
+                if result != 0 && ptr != 0 {
+                    self.free(ptr);
+                }
+                self.allocate(&pending, result, size, cycle);
+            }
+        }
+    }
+
+    fn allocate(&mut self, call: &PendingCall<S>, address: u32, size: u32, cycle: u32) {
+        if address == 0 {
+            self.failed_allocations += 1;
+            return;
+        }
+
+        self.allocations += 1;
+        self.heap_size += size as u64;
+        if self.peak.is_none_or(|(peak, _)| self.heap_size > peak) {
+            self.peak = Some((self.heap_size, cycle));
+        }
+
+        let counts = self
+            .call_sites
+            .entry(call.call_site)
+            .or_insert_with(|| CallSiteCounts {
+                allocations: 0,
+                bytes: 0,
+                stack: call.stack.clone(),
+            });
+        counts.allocations += 1;
+        counts.bytes += size as u64;
+
+        let allocation = Allocation {
+            address,
+            size,
+            call_site: call.call_site,
+            cycle,
+            stack: call.stack.clone(),
+        };
+        if let Some(previous) = self.live.insert(address, allocation) {
+            // The previous allocation must have been freed by an untracked function.
+            self.heap_size -= previous.size as u64;
+        }
+    }
+
+    fn free(&mut self, address: u32) {
+        if address == 0 {
+            return;
+        }
+
+        match self.live.remove(&address) {
+            Some(allocation) => {
+                self.frees += 1;
+                self.heap_size -= allocation.size as u64;
+            }
+            None => self.unknown_frees += 1,
+        }
+    }
+
+    /// Summarizes the tracked calls. `resolve_stack` turns a call stack into frame names,
+    /// innermost first.
+    pub fn report(&self, mut resolve_stack: impl FnMut(&S) -> Vec<String>) -> HeapReport {
+        let mut call_sites = self
+            .call_sites
+            .iter()
+            .map(|(call_site, counts)| CallSiteAllocations {
+                call_site: *call_site,
+                function: resolve_stack(&counts.stack).into_iter().next(),
+                allocations: counts.allocations,
+                bytes: counts.bytes,
+            })
+            .collect::<Vec<_>>();
+        call_sites.sort_by(|a, b| {
+            b.allocations
+                .cmp(&a.allocations)
+                .then(a.call_site.cmp(&b.call_site))
+        });
+
+        let mut live = self.live.values().collect::<Vec<_>>();
+        live.sort_by_key(|allocation| (allocation.cycle, allocation.address));
+        let live = live
+            .into_iter()
+            .map(|allocation| LiveAllocation {
+                address: allocation.address,
+                size: allocation.size,
+                cycle: allocation.cycle,
+                stack: resolve_stack(&allocation.stack),
+            })
+            .collect();
+
+        HeapReport {
+            peak_bytes: self.peak.map_or(0, |(peak, _)| peak),
+            peak_cycle: self.peak.map(|(_, cycle)| cycle),
+            live_bytes: self.heap_size,
+            allocations: self.allocations,
+            frees: self.frees,
+            failed_allocations: self.failed_allocations,
+            unknown_frees: self.unknown_frees,
+            call_sites,
+            live,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct CallSiteAllocations {
+    pub call_site: u32,
+    /// Innermost function of the call site.
+    pub function: Option<String>,
+    pub allocations: usize,
+    pub bytes: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct LiveAllocation {
+    pub address: u32,
+    pub size: u32,
+    pub cycle: u32,
+    /// Call stack of the allocation, innermost frame first.
+    pub stack: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct HeapReport {
+    pub peak_bytes: u64,
+    pub peak_cycle: Option<u32>,
+    pub live_bytes: u64,
+    pub allocations: usize,
+    pub frees: usize,
+    /// Allocations that returned a null pointer.
+    pub failed_allocations: usize,
+    /// Frees of pointers that were not allocated through the tracked functions.
+    pub unknown_frees: usize,
+    /// Call sites by the number of allocations, most first.
+    pub call_sites: Vec<CallSiteAllocations>,
+    /// Allocations that were never freed, in the order they were made.
+    pub live: Vec<LiveAllocation>,
+}
+
+impl HeapReport {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("report is always serializable")
+    }
+}
+
+impl fmt::Display for HeapReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.peak_cycle {
+            Some(cycle) => writeln!(f, "Peak heap: {} bytes at cycle {}", self.peak_bytes, cycle)?,
+            None => writeln!(f, "Peak heap: 0 bytes")?,
+        }
+        writeln!(
+            f,
+            "Allocations: {}, frees: {}, failed allocations: {}, unknown frees: {}",
+            self.allocations, self.frees, self.failed_allocations, self.unknown_frees
+        )?;
+
+        writeln!(f, "\n{:>12} {:>12}  call site", "allocations", "bytes")?;
+        for call_site in &self.call_sites {
+            writeln!(
+                f,
+                "{:>12} {:>12}  0x{:08x} {}",
+                call_site.allocations,
+                call_site.bytes,
+                call_site.call_site,
+                call_site.function.as_deref().unwrap_or("")
+            )?;
+        }
+
+        writeln!(
+            f,
+            "\nLive at exit: {} bytes in {} allocations",
+            self.live_bytes,
+            self.live.len()
+        )?;
+        for allocation in &self.live {
+            writeln!(
+                f,
+                "  {} bytes at 0x{:08x}, allocated at cycle {}",
+                allocation.size, allocation.address, allocation.cycle
+            )?;
+            for frame in &allocation.stack {
+                writeln!(f, "    {}", frame)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,773 @@
+//! Symbolized profile data that is independent of the DWARF caches, and writers for the output
+//! formats supported by the profiler.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ProfileFrame {
+    pub name: String,
+    pub file: Option<String>,
+    pub line: Option<u64>,
+}
+
+/// Sampled stacks referencing `frames` by index, ordered from the outermost frame to the
+/// innermost one.
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    pub frames: Vec<ProfileFrame>,
+    pub stacks: Vec<(Vec<usize>, usize)>,
+    /// Number of cycles represented by a single sample, 1 for the event weightings.
+    pub frequency_recip: u32,
+    /// The event the samples count.
+    pub weighting: ProfilerWeighting,
+}
+
+#[derive(Clone, Debug)]
+pub enum ProfilerOutput {
+    /// SVG flamegraph rendered by `inferno`.
+    Flamegraph(PathBuf),
+    /// Folded stacks, one `outer;...;inner count` line per unique stack.
+    FoldedStacks(PathBuf),
+    /// Sampled profile in the speedscope file format.
+    Speedscope(PathBuf),
+    /// Uncompressed pprof protobuf profile.
+    Pprof(PathBuf),
+    /// Per-function self and inclusive samples as a text table.
+    FunctionTable(PathBuf),
+    /// Same as `FunctionTable`, as CSV.
+    FunctionTableCsv(PathBuf),
+    /// Same as `FunctionTable`, as JSON.
+    FunctionTableJson(PathBuf),
+    /// Differential flamegraph and the ranked function share changes against a baseline
+    /// profile saved as folded stacks.
+    Differential {
+        baseline: PathBuf,
+        flamegraph: PathBuf,
+        report: PathBuf,
+    },
+}
+
+/// Event by which the profiler weights the collected stacks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfilerWeighting {
+    /// Every `frequency_recip`-th cycle is sampled.
+    #[default]
+    Cycles,
+    MemoryLoads,
+    MemoryStores,
+    NonDeterminismReads,
+    NonDeterminismWrites,
+    DelegationCalls,
+    Traps,
+}
+
+impl ProfilerWeighting {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProfilerWeighting::Cycles => "cycles",
+            ProfilerWeighting::MemoryLoads => "memory_loads",
+            ProfilerWeighting::MemoryStores => "memory_stores",
+            ProfilerWeighting::NonDeterminismReads => "non_determinism_reads",
+            ProfilerWeighting::NonDeterminismWrites => "non_determinism_writes",
+            ProfilerWeighting::DelegationCalls => "delegation_calls",
+            ProfilerWeighting::Traps => "traps",
+        }
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+
+    path.with_file_name(name)
+}
+
+impl ProfilerOutput {
+    /// The output of the given weighting. Cycles keep the configured paths, other weightings
+    /// get their name inserted before the extension, e.g. `flamegraph.traps.svg`.
+    pub fn for_weighting(&self, weighting: ProfilerWeighting) -> ProfilerOutput {
+        if weighting == ProfilerWeighting::Cycles {
+            return self.clone();
+        }
+
+        let suffix = weighting.name();
+        match self {
+            ProfilerOutput::Flamegraph(path) => {
+                ProfilerOutput::Flamegraph(with_suffix(path, suffix))
+            }
+            ProfilerOutput::FoldedStacks(path) => {
+                ProfilerOutput::FoldedStacks(with_suffix(path, suffix))
+            }
+            ProfilerOutput::Speedscope(path) => {
+                ProfilerOutput::Speedscope(with_suffix(path, suffix))
+            }
+            ProfilerOutput::Pprof(path) => ProfilerOutput::Pprof(with_suffix(path, suffix)),
+            ProfilerOutput::FunctionTable(path) => {
+                ProfilerOutput::FunctionTable(with_suffix(path, suffix))
+            }
+            ProfilerOutput::FunctionTableCsv(path) => {
+                ProfilerOutput::FunctionTableCsv(with_suffix(path, suffix))
+            }
+            ProfilerOutput::FunctionTableJson(path) => {
+                ProfilerOutput::FunctionTableJson(with_suffix(path, suffix))
+            }
+            ProfilerOutput::Differential {
+                baseline,
+                flamegraph,
+                report,
+            } => ProfilerOutput::Differential {
+                baseline: with_suffix(baseline, suffix),
+                flamegraph: with_suffix(flamegraph, suffix),
+                report: with_suffix(report, suffix),
+            },
+        }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        match self {
+            ProfilerOutput::Flamegraph(path)
+            | ProfilerOutput::FoldedStacks(path)
+            | ProfilerOutput::Speedscope(path)
+            | ProfilerOutput::Pprof(path)
+            | ProfilerOutput::FunctionTable(path)
+            | ProfilerOutput::FunctionTableCsv(path)
+            | ProfilerOutput::FunctionTableJson(path)
+            | ProfilerOutput::Differential {
+                flamegraph: path, ..
+            } => path,
+        }
+    }
+}
+
+impl Profile {
+    pub fn total_samples(&self) -> usize {
+        self.stacks.iter().map(|(_, count)| count).sum()
+    }
+
+    /// Folded stack lines in a stable order.
+    pub fn folded_lines(&self) -> Vec<String> {
+        let mut lines = self
+            .stacks
+            .iter()
+            .map(|(stack, count)| {
+                let names = stack
+                    .iter()
+                    .map(|idx| self.frames[*idx].name.as_str())
+                    .collect::<Vec<_>>();
+                format!("{} {}", names.join(";"), count)
+            })
+            .collect::<Vec<_>>();
+        lines.sort();
+
+        lines
+    }
+
+    pub fn write(&self, output: &ProfilerOutput, reverse_graph: bool) -> std::io::Result<()> {
+        if let ProfilerOutput::Differential {
+            baseline,
+            flamegraph,
+            report,
+        } = output
+        {
+            let baseline = std::fs::File::open(baseline)?;
+            let baseline = Profile::from_folded(std::io::BufReader::new(baseline))?;
+
+            let file = std::fs::File::create(flamegraph)?;
+            baseline.write_differential_flamegraph(self, std::io::BufWriter::new(file))?;
+
+            return std::fs::write(report, baseline.compare(self).to_string());
+        }
+
+        let file = std::fs::File::create(output.path())?;
+        let mut file = std::io::BufWriter::new(file);
+
+        match output {
+            ProfilerOutput::Flamegraph(_) => self.write_flamegraph(&mut file, reverse_graph)?,
+            ProfilerOutput::FoldedStacks(_) => self.write_folded(&mut file)?,
+            ProfilerOutput::Speedscope(_) => self.write_speedscope(&mut file)?,
+            ProfilerOutput::Pprof(_) => self.write_pprof(&mut file)?,
+            ProfilerOutput::FunctionTable(_) => write!(file, "{}", self.function_table())?,
+            ProfilerOutput::FunctionTableCsv(_) => {
+                file.write_all(self.function_table().to_csv().as_bytes())?
+            }
+            ProfilerOutput::FunctionTableJson(_) => {
+                file.write_all(self.function_table().to_json().as_bytes())?
+            }
+            ProfilerOutput::Differential { .. } => unreachable!(),
+        }
+
+        file.flush()
+    }
+
+    /// Parses folded stack lines, e.g. as written by `write_folded`. Source locations are not
+    /// part of the format, so the frames only have names.
+    pub fn from_folded<R: BufRead>(reader: R) -> std::io::Result<Self> {
+        let mut profile = Profile {
+            frequency_recip: 1,
+            ..Default::default()
+        };
+        let mut frame_indexes = HashMap::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let invalid = || {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("invalid folded stack line: {}", line),
+                )
+            };
+
+            let (stack, count) = line.rsplit_once(' ').ok_or_else(invalid)?;
+            let count = count.parse::<usize>().map_err(|_| invalid())?;
+
+            let stack = stack
+                .split(';')
+                .map(|name| {
+                    *frame_indexes.entry(name.to_owned()).or_insert_with(|| {
+                        profile.frames.push(ProfileFrame {
+                            name: name.to_owned(),
+                            file: None,
+                            line: None,
+                        });
+                        profile.frames.len() - 1
+                    })
+                })
+                .collect();
+
+            profile.stacks.push((stack, count));
+        }
+
+        Ok(profile)
+    }
+
+    pub fn write_flamegraph<W: Write>(
+        &self,
+        writer: W,
+        reverse_graph: bool,
+    ) -> std::io::Result<()> {
+        let lines = self.folded_lines();
+
+        let mut opts = inferno::flamegraph::Options::default();
+        opts.reverse_stack_order = reverse_graph;
+
+        inferno::flamegraph::from_lines(&mut opts, lines.iter().map(|x| x.as_str()), writer)
+            .map_err(std::io::Error::other)
+    }
+
+    /// Renders `after` colored by the change of every stack relative to `self`. The baseline is
+    /// normalized to the same number of samples, so only the shares are compared.
+    pub fn write_differential_flamegraph<W: Write>(
+        &self,
+        after: &Profile,
+        writer: W,
+    ) -> std::io::Result<()> {
+        let before = self.folded_lines().join("\n");
+        let after = after.folded_lines().join("\n");
+
+        let mut folded = Vec::new();
+        let diff_opts = inferno::differential::Options {
+            normalize: true,
+            strip_hex: false,
+        };
+        inferno::differential::from_readers(
+            diff_opts,
+            before.as_bytes(),
+            after.as_bytes(),
+            &mut folded,
+        )?;
+
+        let folded = String::from_utf8(folded).map_err(std::io::Error::other)?;
+        let mut opts = inferno::flamegraph::Options::default();
+
+        inferno::flamegraph::from_lines(&mut opts, folded.lines(), writer)
+            .map_err(std::io::Error::other)
+    }
+
+    /// Functions ranked by how much their share of samples changed from `self` to `after`.
+    pub fn compare(&self, after: &Profile) -> ProfileDiff {
+        let before_table = self.function_table();
+        let after_table = after.function_table();
+
+        let mut changes = BTreeMap::<&str, FunctionShareChange>::new();
+        for (table, is_before) in [(&before_table, true), (&after_table, false)] {
+            for function in &table.functions {
+                let change = changes
+                    .entry(&function.name)
+                    .or_insert_with(|| FunctionShareChange {
+                        name: function.name.clone(),
+                        ..Default::default()
+                    });
+
+                if is_before {
+                    change.before_self_share = function.self_percentage;
+                    change.before_inclusive_share = function.inclusive_percentage;
+                } else {
+                    change.after_self_share = function.self_percentage;
+                    change.after_inclusive_share = function.inclusive_percentage;
+                }
+            }
+        }
+
+        let mut functions = changes
+            .into_values()
+            .map(|mut change| {
+                change.self_share_delta = change.after_self_share - change.before_self_share;
+                change.inclusive_share_delta =
+                    change.after_inclusive_share - change.before_inclusive_share;
+                change
+            })
+            .collect::<Vec<_>>();
+
+        functions.sort_by(|a, b| {
+            b.self_share_delta
+                .abs()
+                .total_cmp(&a.self_share_delta.abs())
+                .then(
+                    b.inclusive_share_delta
+                        .abs()
+                        .total_cmp(&a.inclusive_share_delta.abs()),
+                )
+                .then(a.name.cmp(&b.name))
+        });
+
+        ProfileDiff {
+            before_samples: before_table.total_samples,
+            after_samples: after_table.total_samples,
+            functions,
+        }
+    }
+
+    pub fn write_folded<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        for line in self.folded_lines() {
+            writeln!(writer, "{}", line)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_speedscope<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        #[derive(Serialize)]
+        struct File<'a> {
+            #[serde(rename = "$schema")]
+            schema: &'static str,
+            shared: Shared<'a>,
+            profiles: Vec<SampledProfile<'a>>,
+            exporter: &'static str,
+        }
+
+        #[derive(Serialize)]
+        struct Shared<'a> {
+            frames: Vec<Frame<'a>>,
+        }
+
+        #[derive(Serialize)]
+        struct Frame<'a> {
+            name: &'a str,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            file: Option<&'a str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            line: Option<u64>,
+        }
+
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct SampledProfile<'a> {
+            #[serde(rename = "type")]
+            kind: &'static str,
+            name: &'static str,
+            unit: &'static str,
+            start_value: usize,
+            end_value: usize,
+            samples: Vec<&'a [usize]>,
+            weights: Vec<usize>,
+        }
+
+        let file = File {
+            schema: "https://www.speedscope.app/file-format-schema.json",
+            shared: Shared {
+                frames: self
+                    .frames
+                    .iter()
+                    .map(|frame| Frame {
+                        name: &frame.name,
+                        file: frame.file.as_deref(),
+                        line: frame.line,
+                    })
+                    .collect(),
+            },
+            profiles: vec![SampledProfile {
+                kind: "sampled",
+                name: self.weighting.name(),
+                unit: "none",
+                start_value: 0,
+                end_value: self.total_samples(),
+                samples: self.stacks.iter().map(|(stack, _)| &stack[..]).collect(),
+                weights: self.stacks.iter().map(|(_, count)| *count).collect(),
+            }],
+            exporter: "risc_v_simulator",
+        };
+
+        serde_json::to_writer(writer, &file).map_err(std::io::Error::other)
+    }
+
+    pub fn write_pprof<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        writer.write_all(&self.to_pprof())
+    }
+
+    /// Encodes the profile as a `perftools.profiles.Profile` message. Every frame becomes one
+    /// function and one location, both with the id `frame index + 1`.
+    pub fn to_pprof(&self) -> Vec<u8> {
+        let mut strings = StringTable::default();
+        let mut profile = Vec::new();
+
+        for (kind, unit) in [("samples", "count"), (self.weighting.name(), "count")] {
+            let mut value_type = Vec::new();
+            proto::uint(&mut value_type, 1, strings.index(kind));
+            proto::uint(&mut value_type, 2, strings.index(unit));
+            proto::bytes(&mut profile, 1, &value_type);
+        }
+
+        for (stack, count) in &self.stacks {
+            let location_ids = stack.iter().rev().map(|idx| *idx as u64 + 1);
+            let mut sample = Vec::new();
+            proto::packed(&mut sample, 1, location_ids);
+            proto::packed(
+                &mut sample,
+                2,
+                [*count as u64, *count as u64 * self.frequency_recip as u64],
+            );
+            proto::bytes(&mut profile, 2, &sample);
+        }
+
+        for (idx, frame) in self.frames.iter().enumerate() {
+            let id = idx as u64 + 1;
+
+            let mut line = Vec::new();
+            proto::uint(&mut line, 1, id);
+            proto::uint(&mut line, 2, frame.line.unwrap_or(0));
+
+            let mut location = Vec::new();
+            proto::uint(&mut location, 1, id);
+            proto::bytes(&mut location, 4, &line);
+            proto::bytes(&mut profile, 4, &location);
+
+            let mut function = Vec::new();
+            proto::uint(&mut function, 1, id);
+            proto::uint(&mut function, 2, strings.index(&frame.name));
+            proto::uint(&mut function, 3, strings.index(&frame.name));
+            if let Some(file) = &frame.file {
+                proto::uint(&mut function, 4, strings.index(file));
+            }
+            proto::uint(&mut function, 5, frame.line.unwrap_or(0));
+            proto::bytes(&mut profile, 5, &function);
+        }
+
+        let mut period_type = Vec::new();
+        proto::uint(&mut period_type, 1, strings.index(self.weighting.name()));
+        proto::uint(&mut period_type, 2, strings.index("count"));
+
+        for string in &strings.strings {
+            proto::bytes(&mut profile, 6, string.as_bytes());
+        }
+        proto::bytes(&mut profile, 11, &period_type);
+        proto::uint(&mut profile, 12, self.frequency_recip as u64);
+
+        profile
+    }
+
+    /// Aggregates the samples per function name. Recursive functions are counted once per
+    /// stack for the inclusive numbers.
+    pub fn function_table(&self) -> FunctionTable {
+        let mut functions = BTreeMap::<&str, FunctionStats>::new();
+
+        for (stack, count) in &self.stacks {
+            let mut seen = HashSet::new();
+
+            for (depth, idx) in stack.iter().enumerate() {
+                let frame = &self.frames[*idx];
+                let stats = functions
+                    .entry(&frame.name)
+                    .or_insert_with(|| FunctionStats {
+                        name: frame.name.clone(),
+                        file: frame.file.clone(),
+                        line: frame.line,
+                        ..Default::default()
+                    });
+
+                if seen.insert(&frame.name) {
+                    stats.inclusive_samples += count;
+                }
+                if depth + 1 == stack.len() {
+                    stats.self_samples += count;
+                }
+                if depth > 0 {
+                    let caller = &self.frames[stack[depth - 1]].name;
+                    *stats.callers.entry(caller.clone()).or_default() += count;
+                }
+            }
+        }
+
+        let total_samples = self.total_samples();
+        let percentage = |samples: usize| 100.0 * samples as f64 / total_samples.max(1) as f64;
+
+        // events are counted one by one, only cycles are sampled
+        let cycles = |samples: usize| {
+            (self.weighting == ProfilerWeighting::Cycles)
+                .then_some(samples as u64 * self.frequency_recip as u64)
+        };
+
+        let mut functions = functions
+            .into_values()
+            .map(|mut stats| {
+                stats.self_percentage = percentage(stats.self_samples);
+                stats.inclusive_percentage = percentage(stats.inclusive_samples);
+                stats.self_cycles = cycles(stats.self_samples);
+                stats.inclusive_cycles = cycles(stats.inclusive_samples);
+                stats
+            })
+            .collect::<Vec<_>>();
+
+        functions.sort_by(|a, b| {
+            b.self_samples
+                .cmp(&a.self_samples)
+                .then(b.inclusive_samples.cmp(&a.inclusive_samples))
+                .then(a.name.cmp(&b.name))
+        });
+
+        FunctionTable {
+            total_samples,
+            frequency_recip: self.frequency_recip,
+            weighting: self.weighting,
+            functions,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct FunctionStats {
+    pub name: String,
+    pub file: Option<String>,
+    pub line: Option<u64>,
+    pub self_samples: usize,
+    pub inclusive_samples: usize,
+    pub self_percentage: f64,
+    pub inclusive_percentage: f64,
+    /// Samples scaled by the sampling period, only for the cycles weighting.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub self_cycles: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inclusive_cycles: Option<u64>,
+    /// Samples in which the function was called by the given function.
+    pub callers: BTreeMap<String, usize>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct FunctionTable {
+    pub total_samples: usize,
+    pub frequency_recip: u32,
+    pub weighting: ProfilerWeighting,
+    /// Highest self samples first.
+    pub functions: Vec<FunctionStats>,
+}
+
+impl FunctionTable {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("table is always serializable")
+    }
+
+    /// The cycles columns are only there for the cycles weighting.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "function,self_samples,self_percentage,inclusive_samples,inclusive_percentage,",
+        );
+        if self.weighting == ProfilerWeighting::Cycles {
+            csv.push_str("self_cycles,inclusive_cycles,");
+        }
+        csv.push_str("call_sites\n");
+
+        for function in &self.functions {
+            csv.push_str(&format!(
+                "\"{}\",{},{:.4},{},{:.4},",
+                function.name.replace('"', "\"\""),
+                function.self_samples,
+                function.self_percentage,
+                function.inclusive_samples,
+                function.inclusive_percentage,
+            ));
+            if let (Some(self_cycles), Some(inclusive_cycles)) =
+                (function.self_cycles, function.inclusive_cycles)
+            {
+                csv.push_str(&format!("{},{},", self_cycles, inclusive_cycles));
+            }
+            csv.push_str(&format!("{}\n", function.callers.len()));
+        }
+
+        csv
+    }
+}
+
+impl std::fmt::Display for FunctionTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.weighting == ProfilerWeighting::Cycles {
+            writeln!(
+                f,
+                "Total samples: {}, cycles per sample: {}",
+                self.total_samples, self.frequency_recip
+            )?;
+        } else {
+            writeln!(f, "Total {}: {}", self.weighting.name(), self.total_samples)?;
+        }
+        writeln!(
+            f,
+            "{:>10} {:>8} {:>10} {:>8} {:>14} {:>6}  function",
+            "self", "%", "inclusive", "%", "cycles", "sites"
+        )?;
+        for function in self.functions.iter() {
+            // events have no cycles estimate
+            let cycles = function
+                .inclusive_cycles
+                .map_or_else(|| "-".to_owned(), |cycles| cycles.to_string());
+            writeln!(
+                f,
+                "{:>10} {:>7.2}% {:>10} {:>7.2}% {:>14} {:>6}  {}",
+                function.self_samples,
+                function.self_percentage,
+                function.inclusive_samples,
+                function.inclusive_percentage,
+                cycles,
+                function.callers.len(),
+                function.name
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Shares are percentages of all the samples of the respective profile.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct FunctionShareChange {
+    pub name: String,
+    pub before_self_share: f64,
+    pub after_self_share: f64,
+    pub self_share_delta: f64,
+    pub before_inclusive_share: f64,
+    pub after_inclusive_share: f64,
+    pub inclusive_share_delta: f64,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ProfileDiff {
+    pub before_samples: usize,
+    pub after_samples: usize,
+    /// Largest absolute change of the self share first.
+    pub functions: Vec<FunctionShareChange>,
+}
+
+impl ProfileDiff {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("diff is always serializable")
+    }
+}
+
+impl std::fmt::Display for ProfileDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Samples before: {}, after: {}",
+            self.before_samples, self.after_samples
+        )?;
+        writeln!(
+            f,
+            "{:>8} {:>8} {:>9} {:>9}  function",
+            "self", "before", "after", "incl"
+        )?;
+        for function in self.functions.iter() {
+            writeln!(
+                f,
+                "{:>+7.2}% {:>7.2}% {:>8.2}% {:>+8.2}%  {}",
+                function.self_share_delta,
+                function.before_self_share,
+                function.after_self_share,
+                function.inclusive_share_delta,
+                function.name
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+struct StringTable {
+    strings: Vec<String>,
+    indexes: HashMap<String, u64>,
+}
+
+impl Default for StringTable {
+    fn default() -> Self {
+        // pprof requires the first string to be empty.
+        Self {
+            strings: vec![String::new()],
+            indexes: HashMap::from([(String::new(), 0)]),
+        }
+    }
+}
+
+impl StringTable {
+    fn index(&mut self, string: &str) -> u64 {
+        if let Some(idx) = self.indexes.get(string) {
+            return *idx;
+        }
+
+        let idx = self.strings.len() as u64;
+        self.strings.push(string.to_owned());
+        self.indexes.insert(string.to_owned(), idx);
+
+        idx
+    }
+}
+
+/// Just enough of the protobuf wire format to write pprof profiles.
+mod proto {
+    pub(super) fn varint(buf: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            buf.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        buf.push(value as u8);
+    }
+
+    pub(super) fn uint(buf: &mut Vec<u8>, field: u32, value: u64) {
+        varint(buf, (field as u64) << 3);
+        varint(buf, value);
+    }
+
+    pub(super) fn bytes(buf: &mut Vec<u8>, field: u32, value: &[u8]) {
+        varint(buf, ((field as u64) << 3) | 2);
+        varint(buf, value.len() as u64);
+        buf.extend_from_slice(value);
+    }
+
+    pub(super) fn packed(buf: &mut Vec<u8>, field: u32, values: impl IntoIterator<Item = u64>) {
+        let mut packed = Vec::new();
+        for value in values {
+            varint(&mut packed, value);
+        }
+        bytes(buf, field, &packed);
+    }
+}
@@ -0,0 +1,179 @@
+//! Stack usage high-water marks. The stack pointer is sampled before every instruction, so the
+//! deepest point of the stack is exact and is attributed to the call stack active at that moment.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+#[derive(Clone, Debug)]
+pub struct StackUsageConfig {
+    output_path: PathBuf,
+    /// Initial stack pointer. Defaults to the highest stack pointer seen during the run.
+    pub stack_top: Option<u32>,
+    /// Lowest valid stack pointer. The run stops with `RunFault::StackOverflow` as soon as the
+    /// stack grows past it.
+    pub limit: Option<u32>,
+}
+
+impl StackUsageConfig {
+    pub fn new(output_path: PathBuf) -> Self {
+        Self {
+            output_path,
+            stack_top: None,
+            limit: None,
+        }
+    }
+
+    pub fn output_path(&self) -> &Path {
+        &self.output_path
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct StackUsage {
+    highest_sp: u32,
+    lowest_sp: Option<(u32, u32)>,
+    // Lowest stack pointer by the pc of the instruction about to be executed.
+    lowest_sp_by_pc: HashMap<u32, u32>,
+}
+
+impl StackUsage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the stack pointer before the instruction at `pc` is executed. Returns whether it
+    /// is the deepest the stack has been so far.
+    pub fn observe(&mut self, pc: u32, sp: u32, cycle: u32) -> bool {
+        // The stack pointer is zero until the guest sets up its stack.
+        if sp == 0 {
+            return false;
+        }
+
+        self.highest_sp = self.highest_sp.max(sp);
+
+        let lowest = self.lowest_sp_by_pc.entry(pc).or_insert(sp);
+        *lowest = (*lowest).min(sp);
+
+        match self.lowest_sp {
+            Some((lowest, _)) if lowest <= sp => false,
+            _ => {
+                self.lowest_sp = Some((sp, cycle));
+                true
+            }
+        }
+    }
+
+    /// Joins the stack depths with function names. `high_water_stack` is the call stack at the
+    /// deepest point, innermost frame first.
+    pub fn report(
+        &self,
+        config: &StackUsageConfig,
+        high_water_stack: Vec<String>,
+        mut function_name: impl FnMut(u32) -> Option<String>,
+    ) -> StackUsageReport {
+        let stack_top = config.stack_top.unwrap_or(self.highest_sp);
+        let depth = |sp: u32| stack_top.saturating_sub(sp);
+
+        let mut by_function = HashMap::<String, u32>::new();
+        for (pc, sp) in &self.lowest_sp_by_pc {
+            let name = function_name(*pc).unwrap_or_else(|| format!("[unknown 0x{:08x}]", pc));
+            let max_depth = by_function.entry(name).or_default();
+            *max_depth = (*max_depth).max(depth(*sp));
+        }
+
+        let mut functions = by_function
+            .into_iter()
+            .map(|(name, max_depth)| FunctionStackUsage { name, max_depth })
+            .collect::<Vec<_>>();
+        functions.sort_by(|a, b| b.max_depth.cmp(&a.max_depth).then(a.name.cmp(&b.name)));
+
+        StackUsageReport {
+            stack_top,
+            lowest_sp: self.lowest_sp.map(|(sp, _)| sp),
+            high_water_mark: self.lowest_sp.map_or(0, |(sp, _)| depth(sp)),
+            high_water_cycle: self.lowest_sp.map(|(_, cycle)| cycle),
+            high_water_stack,
+            functions,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FunctionStackUsage {
+    pub name: String,
+    /// Deepest stack use, in bytes from the stack top, while the function was being executed.
+    pub max_depth: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct StackUsageReport {
+    pub stack_top: u32,
+    pub lowest_sp: Option<u32>,
+    /// Deepest stack use in bytes.
+    pub high_water_mark: u32,
+    pub high_water_cycle: Option<u32>,
+    /// Call stack at the deepest point, innermost frame first.
+    pub high_water_stack: Vec<String>,
+    /// Functions by their deepest stack use, deepest first.
+    pub functions: Vec<FunctionStackUsage>,
+}
+
+impl StackUsageReport {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("report is always serializable")
+    }
+}
+
+/// The stack grew past `StackUsageConfig::limit`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StackOverflow {
+    pub sp: u32,
+    pub limit: u32,
+    pub cycle: u32,
+    /// Call stack that crossed the limit, innermost frame first.
+    pub stack: Vec<String>,
+}
+
+impl fmt::Display for StackOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "stack overflow: sp 0x{:08x} crossed the limit 0x{:08x} at cycle {}, in:",
+            self.sp, self.limit, self.cycle
+        )?;
+        for frame in &self.stack {
+            write!(f, "\n  {}", frame)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for StackUsageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Stack top: 0x{:08x}", self.stack_top)?;
+        match (self.lowest_sp, self.high_water_cycle) {
+            (Some(sp), Some(cycle)) => writeln!(
+                f,
+                "High-water mark: {} bytes (sp 0x{:08x} at cycle {})",
+                self.high_water_mark, sp, cycle
+            )?,
+            _ => writeln!(f, "High-water mark: stack was never set up")?,
+        }
+
+        writeln!(f, "\nCall stack at the high-water mark:")?;
+        for frame in &self.high_water_stack {
+            writeln!(f, "  {}", frame)?;
+        }
+
+        writeln!(f, "\n{:>12}  function", "max depth")?;
+        for function in &self.functions {
+            writeln!(f, "{:>12}  {}", function.max_depth, function.name)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,219 @@
+//! Call stack unwinding through the DWARF call frame information (`.eh_frame` and
+//! `.debug_frame`) of the simulated binary. Registers are numbered as in the RISC-V DWARF ABI,
+//! so DWARF register `N` is `xN`.
+
+use addr2line::gimli::{
+    self, BaseAddresses, CfaRule, CieOrFde, DebugFrame, EhFrame, EndianSlice,
+    FrameDescriptionEntry, RegisterRule, RunTimeEndian, UnwindContext, UnwindSection,
+};
+use object::{Object, ObjectSection};
+
+type Reader = EndianSlice<'static, RunTimeEndian>;
+
+const SP: usize = 2;
+
+/// Result of unwinding a single frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Unwound {
+    /// The frame was popped and the caller continues at the given return address.
+    Caller(u32),
+    /// This is the outermost frame, or its caller can not be recovered.
+    Outermost,
+    /// No CFI covers the address, so the frame has to be unwound by other means.
+    NoCfi,
+}
+
+#[derive(Clone, Copy)]
+enum SectionKind {
+    EhFrame,
+    DebugFrame,
+}
+
+pub(crate) struct CfiUnwinder {
+    eh_frame: EhFrame<Reader>,
+    eh_frame_bases: BaseAddresses,
+    debug_frame: DebugFrame<Reader>,
+    debug_frame_bases: BaseAddresses,
+    // Sorted by the initial address.
+    fdes: Vec<(FrameDescriptionEntry<Reader>, SectionKind)>,
+    ctx: Box<UnwindContext<usize>>,
+}
+
+impl CfiUnwinder {
+    /// Collects the CFI of the object. The object data must outlive the unwinder.
+    pub(crate) fn new(object: &object::File<'static>) -> Self {
+        let endian = match object.is_little_endian() {
+            true => RunTimeEndian::Little,
+            false => RunTimeEndian::Big,
+        };
+
+        let section = |name: &str| {
+            object
+                .section_by_name(name)
+                .and_then(|section| Some((section.address(), section.data().ok()?)))
+        };
+
+        let text_address = section(".text").map(|(address, _)| address).unwrap_or(0);
+        let (eh_frame_address, eh_frame) = section(".eh_frame").unwrap_or((0, &[]));
+        let (_, debug_frame) = section(".debug_frame").unwrap_or((0, &[]));
+
+        Self::from_sections(
+            endian,
+            if object.is_64() { 8 } else { 4 },
+            text_address,
+            (eh_frame_address, eh_frame),
+            debug_frame,
+        )
+    }
+
+    pub(crate) fn from_sections(
+        endian: RunTimeEndian,
+        address_size: u8,
+        text_address: u64,
+        eh_frame: (u64, &'static [u8]),
+        debug_frame: &'static [u8],
+    ) -> Self {
+        let (eh_frame_address, eh_frame) = eh_frame;
+
+        let mut eh_frame = EhFrame::new(eh_frame, endian);
+        eh_frame.set_address_size(address_size);
+        let eh_frame_bases = BaseAddresses::default()
+            .set_eh_frame(eh_frame_address)
+            .set_text(text_address);
+
+        let mut debug_frame = DebugFrame::new(debug_frame, endian);
+        debug_frame.set_address_size(address_size);
+        let debug_frame_bases = BaseAddresses::default().set_text(text_address);
+
+        let mut fdes = Vec::new();
+        collect_fdes(&eh_frame, &eh_frame_bases, SectionKind::EhFrame, &mut fdes);
+        collect_fdes(
+            &debug_frame,
+            &debug_frame_bases,
+            SectionKind::DebugFrame,
+            &mut fdes,
+        );
+        fdes.sort_by_key(|(fde, _)| fde.initial_address());
+
+        Self {
+            eh_frame,
+            eh_frame_bases,
+            debug_frame,
+            debug_frame_bases,
+            fdes,
+            ctx: Box::new(UnwindContext::new()),
+        }
+    }
+
+    fn fde_for_address(&self, pc: u32) -> Option<usize> {
+        let pc = pc as u64;
+        let idx = self
+            .fdes
+            .partition_point(|(fde, _)| fde.initial_address() <= pc)
+            .checked_sub(1)?;
+
+        self.fdes[idx].0.contains(pc).then_some(idx)
+    }
+
+    /// Pops the frame that `pc` belongs to. `registers` hold the register values of that frame
+    /// and are updated to the values of the caller. `first_frame` tells if the frame is the one
+    /// being currently executed, in which case the return address may still be in `ra`.
+    /// `read_word` loads a word from the virtual address space of the program.
+    pub(crate) fn unwind_frame(
+        &mut self,
+        pc: u32,
+        registers: &mut [u32; 32],
+        first_frame: bool,
+        mut read_word: impl FnMut(u32) -> Option<u32>,
+    ) -> Unwound {
+        let Some(idx) = self.fde_for_address(pc) else {
+            return Unwound::NoCfi;
+        };
+        let (fde, kind) = &self.fdes[idx];
+
+        let row = match kind {
+            SectionKind::EhFrame => fde.unwind_info_for_address(
+                &self.eh_frame,
+                &self.eh_frame_bases,
+                &mut self.ctx,
+                pc as u64,
+            ),
+            SectionKind::DebugFrame => fde.unwind_info_for_address(
+                &self.debug_frame,
+                &self.debug_frame_bases,
+                &mut self.ctx,
+                pc as u64,
+            ),
+        };
+        let Ok(row) = row else {
+            return Unwound::NoCfi;
+        };
+
+        let cfa = match row.cfa() {
+            CfaRule::RegisterAndOffset { register, offset } => {
+                match registers.get(register.0 as usize) {
+                    Some(value) => (*value as i64 + offset) as u32,
+                    None => return Unwound::Outermost,
+                }
+            }
+            // Expressions are not emitted for RISC-V by the toolchains we care about.
+            CfaRule::Expression(_) => return Unwound::Outermost,
+        };
+
+        let return_address_register = fde.cie().return_address_register().0 as usize;
+        let mut caller = *registers;
+        let mut return_address = None;
+
+        for (idx, value) in caller.iter_mut().enumerate().skip(1) {
+            let rule = row.register(gimli::Register(idx as u16));
+            let recovered = match rule {
+                RegisterRule::Offset(offset) => read_word((cfa as i64 + offset) as u32),
+                RegisterRule::ValOffset(offset) => Some((cfa as i64 + offset) as u32),
+                RegisterRule::Register(other) => registers.get(other.0 as usize).copied(),
+                // A missing rule for the return address means it was not spilled yet, which
+                // is only possible for the frame that is being executed.
+                RegisterRule::Undefined | RegisterRule::SameValue
+                    if idx == return_address_register && !first_frame =>
+                {
+                    None
+                }
+                RegisterRule::Undefined | RegisterRule::SameValue => Some(*value),
+                _ => None,
+            };
+
+            if idx == return_address_register {
+                return_address = recovered;
+            }
+            if let Some(recovered) = recovered {
+                *value = recovered;
+            }
+        }
+        caller[SP] = cfa;
+
+        match return_address {
+            Some(0) | None => Unwound::Outermost,
+            Some(return_address) => {
+                *registers = caller;
+                Unwound::Caller(return_address)
+            }
+        }
+    }
+}
+
+fn collect_fdes<S: UnwindSection<Reader>>(
+    section: &S,
+    bases: &BaseAddresses,
+    kind: SectionKind,
+    fdes: &mut Vec<(FrameDescriptionEntry<Reader>, SectionKind)>,
+) {
+    let mut entries = section.entries(bases);
+
+    // Malformed CFI only makes the unwinder fall back, so parsing errors are not fatal.
+    while let Ok(Some(entry)) = entries.next() {
+        if let CieOrFde::Fde(partial) = entry
+            && let Ok(fde) = partial.parse(S::cie_from_offset)
+        {
+            fdes.push((fde, kind));
+        }
+    }
+}
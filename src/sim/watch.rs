@@ -0,0 +1,276 @@
+//! Memory watchpoints. Accesses are observed at word granularity through the tracer hooks of
+//! loads, stores and delegation batch accesses, so a store to any byte of a watched word is a
+//! write, while a change is only reported when one of the watched bytes changes.
+
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::abstractions::tracer::{BatchAccessPartialData, Tracer};
+use crate::cycle::state::RiscV32State;
+use crate::cycle::MachineConfig;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    /// A write that changes the value of a watched byte.
+    Change,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchAction {
+    Log,
+    /// Logs and stops the run after the cycle of the access.
+    Stop,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub range: Range<u32>,
+    pub kind: WatchKind,
+    pub action: WatchAction,
+}
+
+impl Watchpoint {
+    // Mask of the bytes of the word at `address` that are watched.
+    fn mask(&self, address: u32) -> u32 {
+        (0..4)
+            .filter(|byte| self.range.contains(&(address + byte)))
+            .fold(0, |mask, byte| mask | 0xff << (byte * 8))
+    }
+}
+
+/// Parses `<read|write|change>:<start>(..<end>|+<len>)[:stop]`, with addresses in decimal or
+/// `0x` prefixed hex, e.g. `change:0x1000+4:stop`. Without a length a single word is watched.
+impl FromStr for Watchpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+
+        let kind = match parts.next() {
+            Some("read") => WatchKind::Read,
+            Some("write") => WatchKind::Write,
+            Some("change") => WatchKind::Change,
+            _ => return Err(format!("unknown watchpoint kind in `{}`", s)),
+        };
+
+        let range = parts
+            .next()
+            .ok_or_else(|| format!("missing address range in `{}`", s))?;
+        let range = if let Some((start, end)) = range.split_once("..") {
+            parse_address(start)?..parse_address(end)?
+        } else if let Some((start, len)) = range.split_once('+') {
+            let start = parse_address(start)?;
+            start..end_of_range(s, start, parse_address(len)?)?
+        } else {
+            let start = parse_address(range)?;
+            start..end_of_range(s, start, 4)?
+        };
+        if range.is_empty() {
+            return Err(format!("empty address range in `{}`", s));
+        }
+
+        let action = match parts.next() {
+            None => WatchAction::Log,
+            Some("stop") => WatchAction::Stop,
+            Some(action) => return Err(format!("unknown watchpoint action `{}`", action)),
+        };
+        if parts.next().is_some() {
+            return Err(format!("trailing input in `{}`", s));
+        }
+
+        Ok(Self {
+            range,
+            kind,
+            action,
+        })
+    }
+}
+
+fn end_of_range(s: &str, start: u32, len: u32) -> Result<u32, String> {
+    start
+        .checked_add(len)
+        .ok_or_else(|| format!("address range past the end of memory in `{}`", s))
+}
+
+fn parse_address(s: &str) -> Result<u32, String> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .map_err(|_| format!("invalid address `{}`", s))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WatchHit {
+    /// Index of the watchpoint that was hit.
+    pub watchpoint: usize,
+    pub kind: WatchKind,
+    pub action: WatchAction,
+    /// Address of the accessed word.
+    pub address: u32,
+    pub old_value: u32,
+    /// Value after the access, if it was a write.
+    pub new_value: Option<u32>,
+    pub cycle: u32,
+    /// Address of the instruction that made the access. Filled by the simulator.
+    pub pc: u32,
+    /// Call stack of the instruction, innermost frame first. Filled by the simulator when the
+    /// profiler is enabled.
+    pub backtrace: Vec<String>,
+}
+
+impl fmt::Display for WatchHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Watchpoint {} ({:?}) hit at cycle {} by pc 0x{:08x}: word 0x{:08x}",
+            self.watchpoint, self.kind, self.cycle, self.pc, self.address
+        )?;
+        match self.new_value {
+            Some(new_value) => write!(f, " 0x{:08x} -> 0x{:08x}", self.old_value, new_value)?,
+            None => write!(f, " = 0x{:08x}", self.old_value)?,
+        }
+        for frame in &self.backtrace {
+            write!(f, "\n  {}", frame)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Tracer that checks the memory accesses against the watchpoints.
+#[derive(Clone, Debug, Default)]
+pub struct Watcher {
+    watchpoints: Vec<Watchpoint>,
+    hits: Vec<WatchHit>,
+}
+
+impl Watcher {
+    pub fn new(watchpoints: Vec<Watchpoint>) -> Self {
+        Self {
+            watchpoints,
+            hits: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watchpoints.is_empty()
+    }
+
+    /// Hits since the last call.
+    pub fn take_hits(&mut self) -> Vec<WatchHit> {
+        std::mem::take(&mut self.hits)
+    }
+
+    fn access(&mut self, address: u32, old_value: u32, new_value: Option<u32>, cycle: u32) {
+        for (idx, watchpoint) in self.watchpoints.iter().enumerate() {
+            let mask = watchpoint.mask(address);
+            if mask == 0 {
+                continue;
+            }
+
+            let hit = match (watchpoint.kind, new_value) {
+                (WatchKind::Read, None) => true,
+                (WatchKind::Write, Some(_)) => true,
+                (WatchKind::Change, Some(new_value)) => (old_value ^ new_value) & mask != 0,
+                _ => false,
+            };
+
+            if hit {
+                self.hits.push(WatchHit {
+                    watchpoint: idx,
+                    kind: watchpoint.kind,
+                    action: watchpoint.action,
+                    address,
+                    old_value,
+                    new_value,
+                    cycle,
+                    pc: 0,
+                    backtrace: Vec::new(),
+                });
+            }
+        }
+    }
+
+    fn batch_access(&mut self, base: u32, accesses: &[BatchAccessPartialData], cycle: u32) {
+        for (idx, access) in accesses.iter().enumerate() {
+            let address = base + idx as u32 * 4;
+
+            match *access {
+                BatchAccessPartialData::Read { read_value } => {
+                    self.access(address, read_value, None, cycle)
+                }
+                BatchAccessPartialData::Write {
+                    read_value,
+                    written_value,
+                } => {
+                    self.access(address, read_value, None, cycle);
+                    self.access(address, read_value, Some(written_value), cycle);
+                }
+            }
+        }
+    }
+}
+
+impl<C: MachineConfig> Tracer<C> for Watcher {
+    type AuxData = Vec<Watchpoint>;
+
+    fn create_from_initial_state(_state: &RiscV32State<C>, aux_data: Self::AuxData) -> Self {
+        Self::new(aux_data)
+    }
+
+    #[inline(always)]
+    fn trace_ram_read(
+        &mut self,
+        phys_address: u64,
+        read_value: u32,
+        proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+        self.access(phys_address as u32, read_value, None, proc_cycle);
+    }
+
+    #[inline(always)]
+    fn trace_ram_read_write(
+        &mut self,
+        phys_address: u64,
+        read_value: u32,
+        written_value: u32,
+        proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+        self.access(
+            phys_address as u32,
+            read_value,
+            Some(written_value),
+            proc_cycle,
+        );
+    }
+
+    #[inline(always)]
+    fn trace_batch_memory_access(
+        &mut self,
+        _access_id: u32,
+        phys_address_high: u16,
+        accesses: &[BatchAccessPartialData],
+        proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+        self.batch_access((phys_address_high as u32) << 16, accesses, proc_cycle);
+    }
+
+    #[inline(always)]
+    fn trace_batch_memory_access_at(
+        &mut self,
+        _access_id: u32,
+        phys_address: u64,
+        accesses: &[BatchAccessPartialData],
+        proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+        self.batch_access(phys_address as u32, accesses, proc_cycle);
+    }
+}
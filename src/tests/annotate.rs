@@ -0,0 +1,70 @@
+use crate::sim::{AnnotateConfig, AnnotatedInstruction, AnnotationReport};
+
+fn instruction(
+    pc: u32,
+    instruction: u32,
+    count: u64,
+    function: &str,
+    line: u64,
+) -> AnnotatedInstruction {
+    AnnotatedInstruction {
+        pc,
+        instruction,
+        count,
+        function: Some(function.to_owned()),
+        file: Some("src/main.rs".to_owned()),
+        line: Some(line),
+    }
+}
+
+fn report() -> AnnotationReport {
+    AnnotationReport::new(vec![
+        // addi x1, x1, 1
+        instruction(0x108, 0x00108093, 40, "hash", 12),
+        // add x3, x1, x2
+        instruction(0x104, 0x002081b3, 40, "hash", 11),
+        instruction(0x100, 0x002081b3, 10, "hash", 11),
+        instruction(0x200, 0xffffffff, 10, "main", 3),
+    ])
+}
+
+#[test]
+fn test_hottest_lines_and_instructions() {
+    let report = report();
+    assert_eq!(report.total_count, 100);
+    assert_eq!(report.instructions[0].pc, 0x100);
+
+    let lines = report.hottest_lines(2);
+    assert_eq!(lines.len(), 2);
+    assert_eq!((lines[0].line, lines[0].count), (11, 50));
+    assert_eq!(lines[0].percentage, 50.0);
+    assert_eq!((lines[1].line, lines[1].count), (12, 40));
+
+    let pcs = report
+        .hottest_instructions(3)
+        .iter()
+        .map(|instruction| instruction.pc)
+        .collect::<Vec<_>>();
+    assert_eq!(pcs, [0x104, 0x108, 0x100]);
+}
+
+#[test]
+fn test_annotated_listing() {
+    let report = report();
+    assert_eq!(report.instructions[1].disassembly(), "add x3, x1, x2");
+    assert_eq!(report.instructions[3].disassembly(), ".word 0xffffffff");
+
+    let listing = report.annotate_function("hash");
+    assert!(!listing.contains("main:"));
+    let lines = listing.lines().collect::<Vec<_>>();
+    assert_eq!(lines[1], "hash:");
+    assert_eq!(lines[2], "  src/main.rs:11");
+    assert!(lines[4].ends_with("0x00000104  add x3, x1, x2"));
+    assert_eq!(lines[5], "  src/main.rs:12");
+
+    let mut config = AnnotateConfig::new("annotate.txt".into());
+    config.functions.push("main".to_owned());
+    let rendered = report.render(&config);
+    assert!(rendered.starts_with("Total instructions: 100"));
+    assert!(rendered.contains("\nmain:\n"));
+}
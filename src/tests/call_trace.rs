@@ -0,0 +1,96 @@
+use crate::sim::{CallTrace, CallTraceConfig};
+
+// Offsets are irrelevant, the trace follows the pc the execution continued at.
+fn jal(rd: u32) -> u32 {
+    rd << 7 | 0b1101111
+}
+
+fn jalr(rd: u32, rs1: u32) -> u32 {
+    rs1 << 15 | rd << 7 | 0b1100111
+}
+
+fn name(address: u32) -> String {
+    match address {
+        0x100 => "main".to_owned(),
+        0x200 => "hash".to_owned(),
+        0x300 => "memcpy".to_owned(),
+        _ => format!("[unknown 0x{:08x}]", address),
+    }
+}
+
+// main calls hash, which calls memcpy twice, then main calls memcpy and never returns.
+fn trace() -> CallTrace {
+    let call = jal(1);
+    let indirect_call = jalr(1, 5);
+    let ret = jalr(0, 1);
+    let jump = jal(0);
+
+    let mut trace = CallTrace::new();
+    for (pc, opcode, next_pc, cycle) in [
+        (0x000, call, 0x100, 0),
+        (0x104, call, 0x200, 2),
+        (0x204, indirect_call, 0x300, 3),
+        (0x300, ret, 0x208, 5),
+        // a tail call is not a call
+        (0x208, jump, 0x400, 6),
+        (0x208, call, 0x300, 7),
+        (0x300, ret, 0x20c, 8),
+        (0x20c, ret, 0x108, 10),
+        (0x108, call, 0x300, 11),
+        // a return to nowhere on the stack is ignored
+        (0x300, ret, 0x500, 12),
+    ] {
+        trace.step(pc, opcode, next_pc, cycle);
+    }
+
+    trace
+}
+
+#[test]
+fn test_shadow_stack() {
+    let trace = trace();
+
+    let calls = trace
+        .calls
+        .iter()
+        .map(|call| (call.callee, call.depth, call.exit_cycle))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        calls,
+        [
+            (0x100, 0, None),
+            (0x200, 1, Some(10)),
+            (0x300, 2, Some(5)),
+            (0x300, 2, Some(8)),
+            (0x300, 1, None),
+        ]
+    );
+    assert_eq!(trace.calls[1].cycles(), Some(9));
+}
+
+#[test]
+fn test_call_tree() {
+    let trace = trace();
+    let mut config = CallTraceConfig::new("trace.txt".into());
+
+    let tree = trace.render(&config, name);
+    let lines = tree.lines().skip(1).map(str::trim).collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        [
+            "0            -            -  main() {",
+            "2           10            9    hash() {",
+            "3            5            3      memcpy();",
+            "7            8            2      memcpy();",
+            "} /* hash */",
+            "11            -            -    memcpy();",
+            "} /* main */",
+        ]
+    );
+
+    config.functions.push("hash".to_owned());
+    config.max_depth = Some(0);
+    let tree = trace.render(&config, name);
+    let lines = tree.lines().skip(1).map(str::trim).collect::<Vec<_>>();
+    assert_eq!(lines, ["2           10            9  hash();"]);
+}
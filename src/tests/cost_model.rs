@@ -0,0 +1,149 @@
+use super::*;
+use crate::abstractions::tracer::Tracer;
+use crate::analysis::cost_model::{CostModel, CostTracer, InstructionClass, ItemCost};
+
+fn run_with_cost_model(
+    program: &[u32],
+    registers: &[(usize, u32)],
+    memory_size: usize,
+    model: CostModel,
+) -> CostTracer {
+    let mut state = RiscV32State::<IMStandardIsaConfig>::initial(INITIAL_PC);
+    for (reg, value) in registers {
+        state.registers[*reg] = *value;
+    }
+    let mut memory = VectorMemoryImpl::new_for_byte_size(memory_size);
+    for (idx, insn) in program.iter().enumerate() {
+        memory.populate(
+            INITIAL_PC + idx as u32 * (core::mem::size_of::<u32>() as u32),
+            *insn,
+        );
+    }
+    let mut tracer = CostTracer::create_from_initial_state(&state, model);
+    let mut mmu = NoMMU::default();
+    for cycle in 0..program.len() as u32 {
+        state.cycle(&mut memory, &mut tracer, &mut mmu, &mut ZeroedSource, cycle);
+    }
+
+    tracer
+}
+
+#[test]
+fn test_cost_breakdown() {
+    let program = [
+        0x02000093, // addi x1, x0, 32
+        0x0000a103, // lw x2, 0(x1)
+        0x0020a223, // sw x2, 4(x1)
+        0x021101b3, // mul x3, x2, x1
+    ];
+    let model = CostModel {
+        alu: 1,
+        load: 2,
+        store: 3,
+        mul_div: 5,
+        memory_access: 10,
+        ..CostModel::default()
+    };
+    let report = run_with_cost_model(&program, &[], 64, model).report(None);
+
+    assert_eq!(
+        report.instructions[&InstructionClass::Alu],
+        ItemCost { count: 1, cost: 1 }
+    );
+    assert_eq!(
+        report.instructions[&InstructionClass::Load],
+        ItemCost { count: 1, cost: 2 }
+    );
+    assert_eq!(
+        report.instructions[&InstructionClass::Store],
+        ItemCost { count: 1, cost: 3 }
+    );
+    assert_eq!(
+        report.instructions[&InstructionClass::MulDiv],
+        ItemCost { count: 1, cost: 5 }
+    );
+    // load reads once, store reads the old value and writes the new one in a single access
+    assert_eq!(report.memory_accesses, ItemCost { count: 2, cost: 20 });
+    assert_eq!(report.total_cost, 1 + 2 + 3 + 5 + 20);
+    assert!(report.per_function.is_empty());
+
+    let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+    assert_eq!(json["total_cost"], 31);
+    assert_eq!(json["instructions"]["Store"]["cost"], 3);
+}
+
+#[test]
+fn test_cost_per_function() {
+    use crate::analysis::cost_model::FunctionCost;
+    use crate::sim::DiagnosticsConfig;
+
+    let program = [
+        0x02000093, // addi x1, x0, 32
+        0x0000a103, // lw x2, 0(x1)
+        0x0020a223, // sw x2, 4(x1)
+        0x021101b3, // mul x3, x2, x1
+    ];
+    let model = CostModel {
+        alu: 1,
+        load: 2,
+        store: 3,
+        mul_div: 5,
+        memory_access: 10,
+        ..CostModel::default()
+    };
+    let symbols_path = write_symbols(
+        "cost_per_function",
+        &[
+            ("load", 0x0, 0x8),
+            ("store", 0x8, 0xc),
+            ("multiply", 0xc, 0x10),
+        ],
+    );
+    let mut symbolizer = DiagnosticsConfig::new(symbols_path.clone()).symbolizer();
+    let report = run_with_cost_model(&program, &[], 64, model).report(Some(&mut symbolizer));
+    std::fs::remove_file(symbols_path).unwrap();
+
+    let function = |name: &str, cost| FunctionCost {
+        name: name.to_owned(),
+        cost,
+    };
+    // ties are ordered by name
+    assert_eq!(
+        report.per_function,
+        [
+            function("load", 1 + 2 + 10),
+            function("store", 3 + 10),
+            function("multiply", 5),
+        ]
+    );
+    assert_eq!(
+        report
+            .per_function
+            .iter()
+            .map(|function| function.cost)
+            .sum::<u64>(),
+        report.total_cost
+    );
+}
+
+#[cfg(feature = "delegation")]
+#[test]
+fn test_delegation_cost() {
+    use crate::delegations::blake2s::{BLAKE2S_ABI_NUM_MEM_ACCESSES, BLAKE2S_ACCESS_ID};
+
+    let program = [csrrw(0, BLAKE2S_ACCESS_ID, 1)];
+    let mut model = CostModel {
+        batch_access: 2,
+        ..CostModel::default()
+    };
+    model
+        .delegation_invocation_overrides
+        .insert(BLAKE2S_ACCESS_ID, 100);
+    let report = run_with_cost_model(&program, &[(1, 1 << 16)], 1 << 17, model).report(None);
+
+    let blake = &report.delegations["blake2s"];
+    assert_eq!(blake.invocations, 1);
+    assert_eq!(blake.batch_accesses, BLAKE2S_ABI_NUM_MEM_ACCESSES as u64);
+    assert_eq!(blake.cost, 100 + 2 * BLAKE2S_ABI_NUM_MEM_ACCESSES as u64);
+    assert_eq!(report.total_cost, blake.cost + 1);
+}
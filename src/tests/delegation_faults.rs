@@ -0,0 +1,232 @@
+use crate::delegations::ec::{EC_OP_ADD, SECP256K1_ACCESS_ID};
+use crate::delegations::keccak::KECCAK_F1600_ACCESS_ID;
+use crate::delegations::memory_copy::{
+    MEMORY_COPY_ACCESS_ID, MEMORY_COPY_MAX_WORDS, MEMORY_OP_COPY, MEMORY_OP_FILL,
+};
+use crate::delegations::u256::{U256_ACCESS_ID, U256_OP_MUL_MOD};
+use crate::delegations::{DelegationError, DelegationFault};
+use crate::runner::run_simple_with_entry_point_and_non_determimism_source_for_config;
+use crate::sim::{RunFault, SimulatorConfig};
+
+use super::*;
+
+const TRAP_HANDLER: u32 = 0x40;
+const DATA: u32 = DELEGATION_WORDS;
+
+struct Outcome {
+    state: RiscV32State<MachineWithTraps>,
+    words: Vec<u32>,
+    faults: Vec<DelegationFault>,
+}
+
+// Runs `csrrw x0, csr, x1` with `rs1_value` in x1 and `words` stored at `DATA`, in a memory of
+// `memory_words` words past `DATA`.
+fn run_trapping(csr: u32, rs1_value: u32, words: &[u32], memory_words: usize) -> Outcome {
+    let mut state = RiscV32State::<MachineWithTraps>::initial(INITIAL_PC);
+    state.machine_mode_trap_data.setup.tvec = TRAP_HANDLER;
+    state.registers[1] = rs1_value;
+
+    let mut registry = DelegationRegistry::builtin();
+    let words = run_delegation_on(&mut state, csr, words, memory_words, &mut (), &mut registry);
+
+    Outcome {
+        state,
+        words,
+        faults: registry.take_faults(),
+    }
+}
+
+// Checks the guest took `trap` and the memory was left unchanged.
+fn assert_trapped(outcome: &Outcome, words: &[u32], trap: TrapReason) -> DelegationError {
+    assert_eq!(outcome.state.pc, TRAP_HANDLER);
+    assert_eq!(
+        outcome.state.machine_mode_trap_data.handling.cause,
+        trap.as_register_value()
+    );
+    assert_eq!(outcome.words, words);
+    assert_eq!(outcome.faults.len(), 1);
+
+    outcome.faults[0].error.clone()
+}
+
+fn u256_words(op: u32, modulus: u32) -> Vec<u32> {
+    let mut words = vec![0u32; 26];
+    words[0] = 3;
+    words[8] = 5;
+    words[16] = modulus;
+    words[24] = op;
+    words
+}
+
+#[test]
+fn test_misaligned_offset_traps() {
+    let words = vec![7u32; 50];
+    let outcome = run_trapping(KECCAK_F1600_ACCESS_ID, DATA + 4, &words, 50);
+
+    let error = assert_trapped(&outcome, &words, TrapReason::LoadAddressMisaligned);
+    assert_eq!(
+        error,
+        DelegationError::Misaligned {
+            address: DATA + 4,
+            trap: TrapReason::LoadAddressMisaligned
+        }
+    );
+    assert_eq!(
+        outcome.faults[0],
+        DelegationFault {
+            csr_index: KECCAK_F1600_ACCESS_ID,
+            delegation: "keccak_f1600".to_string(),
+            rs1_value: DATA + 4,
+            cycle: 0,
+            error,
+        }
+    );
+}
+
+#[test]
+fn test_out_of_range_memory_traps_before_writing() {
+    // keccak accesses 50 words, but only 4 of them exist
+    let words = vec![7u32; 4];
+    let outcome = run_trapping(KECCAK_F1600_ACCESS_ID, DATA, &words, 4);
+
+    let error = assert_trapped(&outcome, &words, TrapReason::LoadAccessFault);
+    assert_eq!(
+        error,
+        DelegationError::AccessFault {
+            address: DATA + 16,
+            trap: TrapReason::LoadAccessFault
+        }
+    );
+}
+
+#[test]
+fn test_unknown_u256_operation_traps() {
+    let words = u256_words(0b11, 7);
+    let outcome = run_trapping(U256_ACCESS_ID, DATA, &words, 26);
+
+    let error = assert_trapped(&outcome, &words, TrapReason::IllegalInstruction);
+    assert_eq!(
+        error,
+        DelegationError::InvalidArgument("unknown u256 operation 0x00000003".to_string())
+    );
+}
+
+#[test]
+fn test_zero_modulus_traps() {
+    let words = u256_words(U256_OP_MUL_MOD, 0);
+    let outcome = run_trapping(U256_ACCESS_ID, DATA, &words, 26);
+
+    let error = assert_trapped(&outcome, &words, TrapReason::IllegalInstruction);
+    assert!(matches!(error, DelegationError::InvalidArgument(_)));
+}
+
+#[test]
+fn test_adding_a_point_to_its_negation_traps() {
+    let mut words = vec![0u32; 33];
+    words[0] = 1;
+    words[8] = 2;
+    words[16] = 1;
+    words[24] = 3;
+    words[32] = EC_OP_ADD;
+    let outcome = run_trapping(SECP256K1_ACCESS_ID, DATA, &words, 33);
+
+    assert_trapped(&outcome, &words, TrapReason::IllegalInstruction);
+    assert_eq!(
+        outcome.faults[0].to_string(),
+        "Delegation secp256k1 (CSR 0x7c7) with rs1 0x00010000 trapped at cycle 0: \
+         secp256k1 addition of a point to its negation"
+    );
+}
+
+#[test]
+fn test_unreduced_coordinate_traps() {
+    let mut words = vec![u32::MAX; 33];
+    words[32] = EC_OP_ADD;
+    let outcome = run_trapping(SECP256K1_ACCESS_ID, DATA, &words, 33);
+
+    assert_trapped(&outcome, &words, TrapReason::IllegalInstruction);
+}
+
+#[test]
+fn test_memory_copy_out_of_range_source_traps() {
+    // the source range runs past the end of the memory
+    let words = vec![MEMORY_OP_COPY, DATA + 16, DATA + 24, 4, 0, 0, 0, 0];
+    let outcome = run_trapping(MEMORY_COPY_ACCESS_ID, DATA, &words, 8);
+
+    let error = assert_trapped(&outcome, &words, TrapReason::LoadAccessFault);
+    assert_eq!(
+        error,
+        DelegationError::AccessFault {
+            address: DATA + 32,
+            trap: TrapReason::LoadAccessFault
+        }
+    );
+}
+
+#[test]
+fn test_memory_copy_unaligned_destination_traps() {
+    let words = vec![MEMORY_OP_FILL, DATA + 18, 7, 1, 0, 0];
+    let outcome = run_trapping(MEMORY_COPY_ACCESS_ID, DATA, &words, 6);
+
+    let error = assert_trapped(&outcome, &words, TrapReason::StoreOrAMOAddressMisaligned);
+    assert_eq!(
+        error,
+        DelegationError::Misaligned {
+            address: DATA + 18,
+            trap: TrapReason::StoreOrAMOAddressMisaligned
+        }
+    );
+}
+
+#[test]
+fn test_memory_copy_over_the_limit_traps() {
+    let words = vec![MEMORY_OP_FILL, DATA + 16, 7, MEMORY_COPY_MAX_WORDS + 1];
+    let outcome = run_trapping(MEMORY_COPY_ACCESS_ID, DATA, &words, 4);
+
+    assert_trapped(&outcome, &words, TrapReason::IllegalInstruction);
+}
+
+#[test]
+fn test_valid_delegation_does_not_fault() {
+    let words = u256_words(U256_OP_MUL_MOD, 7);
+    let outcome = run_trapping(U256_ACCESS_ID, DATA, &words, 26);
+
+    assert_eq!(outcome.state.pc, INITIAL_PC + 4);
+    assert!(outcome.faults.is_empty());
+    assert_eq!(outcome.words[0], 1);
+}
+
+#[test]
+fn test_run_stops_at_delegation_fault() {
+    // lui x1, 0x10; addi x1, x1, 4; csrrw x0, keccak, x1; j .
+    let program = [
+        0x000100b7,
+        0x00408093,
+        csrrw(0, KECCAK_F1600_ACCESS_ID, 1),
+        0x0000006f,
+    ];
+    let bin_path = write_bin("delegation_fault", &program);
+
+    let config = SimulatorConfig::new(bin_path.clone(), INITIAL_PC, 16, None);
+    let (_, fault, state) = run_simple_with_entry_point_and_non_determimism_source_for_config::<
+        _,
+        IMStandardIsaConfig,
+    >(config, ZeroedSource);
+    std::fs::remove_file(bin_path).unwrap();
+
+    assert_eq!(
+        fault,
+        Some(RunFault::Delegation(DelegationFault {
+            csr_index: KECCAK_F1600_ACCESS_ID,
+            delegation: "keccak_f1600".to_string(),
+            rs1_value: DATA + 4,
+            cycle: 2,
+            error: DelegationError::Misaligned {
+                address: DATA + 4,
+                trap: TrapReason::LoadAddressMisaligned
+            },
+        }))
+    );
+    // the instruction retired without effect, and the run stopped right after it
+    assert_eq!(state.pc, INITIAL_PC + 12);
+}
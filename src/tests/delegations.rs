@@ -0,0 +1,139 @@
+use std::sync::Arc;
+
+use super::*;
+use crate::abstractions::memory::{AccessType, MemorySource};
+use crate::abstractions::tracer::BatchAccessPartialData;
+use crate::cycle::status_registers::TrapReason;
+use crate::delegations::{
+    memory_offset, DelegationContext, DelegationError, DelegationHandler, DelegationRegistry,
+    DELEGATION_DISCOVERY_CSR,
+};
+
+const INCREMENT_CSR: u32 = 0x7d0;
+const WORD_ADDRESS: u32 = 1 << 16;
+
+// Increments the word at the offset written to the CSR.
+#[derive(Debug)]
+struct Increment;
+
+impl DelegationHandler for Increment {
+    fn name(&self) -> &str {
+        "increment"
+    }
+
+    fn num_words(&self) -> usize {
+        1
+    }
+
+    fn execute(
+        &self,
+        context: &mut dyn DelegationContext,
+        rs1_value: u32,
+    ) -> Result<(), DelegationError> {
+        let mem_offset = memory_offset(rs1_value)?;
+        let read_value = context.read(mem_offset as u64)?;
+        context.write(mem_offset as u64, read_value + 1)?;
+        context.trace_batch_memory_access(
+            (mem_offset >> 16) as u16,
+            &[BatchAccessPartialData::Write {
+                read_value,
+                written_value: read_value + 1,
+            }],
+        );
+
+        Ok(())
+    }
+}
+
+// Runs `csrrw x0, increment, x1` on the word at `WORD_ADDRESS`, then reads the first discovery window into
+// x2. Returns the word and x2.
+fn run_with_registry(mut registry: DelegationRegistry) -> (u32, u32) {
+    let mut state = RiscV32State::<IMStandardIsaConfig>::initial(INITIAL_PC);
+    state.registers[1] = WORD_ADDRESS;
+    let mut memory = VectorMemoryImpl::new_for_byte_size(2 * WORD_ADDRESS as usize);
+    memory.populate(INITIAL_PC, csrrw(0, INCREMENT_CSR, 1));
+    memory.populate(INITIAL_PC + 4, csrrw(2, DELEGATION_DISCOVERY_CSR, 0));
+    memory.populate(WORD_ADDRESS, 41);
+    let mut mmu = NoMMU::default();
+
+    for cycle in 0..2 {
+        state.pc = INITIAL_PC + cycle * 4;
+        state.cycle_ext(
+            &mut memory,
+            &mut (),
+            &mut mmu,
+            &mut ZeroedSource,
+            &mut registry,
+            cycle,
+            cycle,
+        );
+    }
+
+    let mut trap = TrapReason::NoTrap;
+    let word = memory.get(WORD_ADDRESS as u64, AccessType::MemLoad, &mut trap);
+    (word, state.registers[2])
+}
+
+#[test]
+fn test_registered_delegation_is_executed_and_discovered() {
+    let mut registry = DelegationRegistry::new();
+    registry.register(INCREMENT_CSR, Arc::new(Increment));
+    assert_eq!(registry.name(INCREMENT_CSR), Some("increment"));
+
+    assert_eq!(run_with_registry(registry), (42, 1 << 16));
+}
+
+#[test]
+#[should_panic(expected = "Simulator encountered an exception")]
+fn test_unregistered_delegation_traps() {
+    run_with_registry(DelegationRegistry::new());
+}
+
+#[test]
+fn test_discovery_windows() {
+    let mut registry = DelegationRegistry::new();
+    registry.register(0x7c1, Arc::new(Increment));
+    registry.register(0x7e3, Arc::new(Increment));
+
+    assert_eq!(registry.discovery_mask(0), 1 << 1);
+    assert_eq!(registry.discovery_mask(1), 1 << 3);
+    assert_eq!(registry.discovery_mask(2), 0);
+    assert_eq!(registry.discovery_mask(u32::MAX), 0);
+}
+
+#[test]
+#[should_panic(expected = "can't be used for a delegation")]
+fn test_discovery_csr_cannot_be_registered() {
+    DelegationRegistry::new().register(DELEGATION_DISCOVERY_CSR, Arc::new(Increment));
+}
+
+#[test]
+fn test_builtin_delegations() {
+    use crate::delegations::keccak::KECCAK_F1600_ACCESS_ID;
+
+    let builtin = DelegationRegistry::builtin();
+    assert_eq!(builtin.name(KECCAK_F1600_ACCESS_ID), Some("keccak_f1600"));
+
+    #[cfg(feature = "delegation")]
+    {
+        use crate::delegations::blake2s::BLAKE2S_ACCESS_ID;
+
+        assert_eq!(builtin.name(BLAKE2S_ACCESS_ID), Some("blake2s"));
+        assert_eq!(builtin.discovery_mask(0), 0b11111111110);
+    }
+    #[cfg(not(feature = "delegation"))]
+    assert_eq!(builtin.discovery_mask(0), 0b11111110000);
+}
+
+#[test]
+#[cfg(not(feature = "delegation"))]
+#[should_panic(expected = "Simulator encountered an exception")]
+fn test_cycle_runs_without_delegations() {
+    use crate::delegations::keccak::KECCAK_F1600_ACCESS_ID;
+
+    let mut state = RiscV32State::<IMStandardIsaConfig>::initial(INITIAL_PC);
+    let mut memory = VectorMemoryImpl::new_for_byte_size(16);
+    memory.populate(INITIAL_PC, csrrw(0, KECCAK_F1600_ACCESS_ID, 1));
+    let mut mmu = NoMMU::default();
+    state.cycle(&mut memory, &mut (), &mut mmu, &mut ZeroedSource, 0);
+}
@@ -0,0 +1,92 @@
+use super::*;
+use crate::delegations::ec::*;
+
+fn u256(hex: &str) -> [u32; 8] {
+    let hex = format!("{:0>64}", hex);
+    core::array::from_fn(|idx| {
+        let end = 64 - idx * 8;
+        u32::from_str_radix(&hex[end - 8..end], 16).unwrap()
+    })
+}
+
+fn point(x: &str, y: &str) -> AffinePoint {
+    (u256(x), u256(y))
+}
+
+// Runs the operation of the curve delegation at `access_id` in the guest.
+fn run_in_guest(access_id: u32, op: u32, p1: &AffinePoint, p2: &AffinePoint) -> AffinePoint {
+    let words: Vec<u32> =
+        p1.0.iter()
+            .chain(&p1.1)
+            .chain(&p2.0)
+            .chain(&p2.1)
+            .chain([&op])
+            .copied()
+            .collect();
+    let words = run_delegation(
+        access_id,
+        &words,
+        &mut (),
+        &mut DelegationRegistry::builtin(),
+    );
+
+    (
+        words[..8].try_into().unwrap(),
+        words[8..16].try_into().unwrap(),
+    )
+}
+
+#[test]
+fn test_secp256k1_vectors() {
+    let g = point(
+        "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        "483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8",
+    );
+    let g2 = point(
+        "c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+        "1ae168fea63dc339a3c58419466ceaeef7f632653266d0e1236431a950cfe52a",
+    );
+    let g3 = point(
+        "f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+        "388f7b0f632de8140fe337e62a37f3566500a99934c2231b6cb9fd7584b8e672",
+    );
+    assert!(SECP256K1.is_on_curve(&g));
+
+    assert_eq!(run_in_guest(SECP256K1_ACCESS_ID, EC_OP_DOUBLE, &g, &g), g2);
+    assert_eq!(run_in_guest(SECP256K1_ACCESS_ID, EC_OP_ADD, &g, &g2), g3);
+    assert_eq!(run_in_guest(SECP256K1_ACCESS_ID, EC_OP_ADD, &g2, &g), g3);
+    // adding a point to itself doubles it
+    assert_eq!(run_in_guest(SECP256K1_ACCESS_ID, EC_OP_ADD, &g, &g), g2);
+}
+
+#[test]
+fn test_bn254_g1_vectors() {
+    let g = point("1", "2");
+    let g2 = point(
+        "030644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd3",
+        "15ed738c0e0a7c92e7845f96b2ae9c0a68a6a449e3538fc7ff3ebf7a5a18a2c4",
+    );
+    assert!(BN254_G1.is_on_curve(&g));
+
+    assert_eq!(run_in_guest(BN254_G1_ACCESS_ID, EC_OP_DOUBLE, &g, &g), g2);
+
+    // 4G both as 2 * 2G and as 3G + G
+    let g3 = run_in_guest(BN254_G1_ACCESS_ID, EC_OP_ADD, &g2, &g);
+    assert!(BN254_G1.is_on_curve(&g3));
+    assert_eq!(
+        run_in_guest(BN254_G1_ACCESS_ID, EC_OP_ADD, &g3, &g),
+        run_in_guest(BN254_G1_ACCESS_ID, EC_OP_DOUBLE, &g2, &g),
+    );
+}
+
+#[test]
+#[should_panic(expected = "adding a point to its negation")]
+fn test_sum_at_infinity_is_rejected() {
+    let g = point("1", "2");
+    let minus_g = (
+        g.0,
+        u256("30644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd45"),
+    );
+
+    BN254_G1.add_points(&g, &minus_g);
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use crate::sim::{AllocatorFunction, HeapTracker};
+
+const MALLOC: u32 = 0x1000;
+const FREE: u32 = 0x2000;
+const REALLOC: u32 = 0x3000;
+
+fn tracker() -> HeapTracker<&'static str> {
+    HeapTracker::new(HashMap::from([
+        (MALLOC, AllocatorFunction::Alloc { size: 0 }),
+        (FREE, AllocatorFunction::Free { ptr: 0 }),
+        (REALLOC, AllocatorFunction::Realloc { ptr: 0, size: 1 }),
+    ]))
+}
+
+// Calls the allocator function at `entry` from `call_site` and returns `result` from it.
+fn call(
+    tracker: &mut HeapTracker<&'static str>,
+    entry: u32,
+    call_site: u32,
+    arguments: [u32; 2],
+    result: u32,
+    cycle: u32,
+) {
+    let mut registers = [0u32; 32];
+    registers[1] = call_site + 4;
+    registers[2] = 0x8000;
+    registers[10] = arguments[0];
+    registers[11] = arguments[1];
+
+    let function = tracker.intercepts(entry).unwrap();
+    tracker.enter(function, &registers, "main");
+
+    // the allocator calling itself is not tracked
+    assert!(tracker.intercepts(MALLOC).is_none());
+    let mut nested = registers;
+    nested[2] = 0x7ff0;
+    tracker.observe(call_site + 4, &nested, cycle);
+
+    registers[10] = result;
+    tracker.observe(call_site + 4, &registers, cycle);
+    assert!(tracker.intercepts(MALLOC).is_some());
+}
+
+#[test]
+fn test_heap_tracking() {
+    let mut tracker = tracker();
+
+    call(&mut tracker, MALLOC, 0x100, [16, 0], 0x10000, 1);
+    call(&mut tracker, MALLOC, 0x100, [32, 0], 0x10010, 2);
+    call(&mut tracker, MALLOC, 0x200, [64, 0], 0, 3);
+    call(&mut tracker, REALLOC, 0x300, [0x10000, 48], 0x10030, 4);
+    call(&mut tracker, FREE, 0x400, [0x10010, 0], 0, 5);
+    call(&mut tracker, FREE, 0x400, [0x20000, 0], 0, 6);
+
+    let report = tracker.report(|stack| vec![stack.to_string()]);
+
+    assert_eq!(report.peak_bytes, 80);
+    assert_eq!(report.peak_cycle, Some(4));
+    assert_eq!(report.live_bytes, 48);
+    assert_eq!(report.allocations, 3);
+    assert_eq!(report.frees, 2);
+    assert_eq!(report.failed_allocations, 1);
+    assert_eq!(report.unknown_frees, 1);
+
+    let call_sites = report
+        .call_sites
+        .iter()
+        .map(|call_site| (call_site.call_site, call_site.allocations, call_site.bytes))
+        .collect::<Vec<_>>();
+    assert_eq!(call_sites, [(0x100, 2, 48), (0x300, 1, 48)]);
+    assert_eq!(report.call_sites[0].function.as_deref(), Some("main"));
+
+    assert_eq!(report.live.len(), 1);
+    assert_eq!(report.live[0].address, 0x10030);
+    assert_eq!(report.live[0].size, 48);
+    assert_eq!(report.live[0].stack, ["main"]);
+    assert!(report
+        .to_string()
+        .contains("Live at exit: 48 bytes in 1 allocations"));
+}
@@ -0,0 +1,103 @@
+use super::*;
+use crate::abstractions::tracer::Tracer;
+use crate::analysis::decode::decode;
+use crate::analysis::instruction_mix::{CsrKind, InstructionMixTracer, IsaFeature};
+
+fn mix_of(program: &[&str], registers: &[(usize, u32)]) -> InstructionMixTracer {
+    let mut state = RiscV32State::<IMStandardIsaConfig>::initial(INITIAL_PC);
+    for (reg, value) in registers {
+        state.registers[*reg] = *value;
+    }
+    let mut memory = VectorMemoryImpl::new_for_byte_size(64);
+    let mut empty_hash: HashMap<String, u32> = HashMap::new();
+    for (idx, instr) in program.iter().enumerate() {
+        let address = INITIAL_PC + idx as u32 * (core::mem::size_of::<u32>() as u32);
+        let encoding = lib_rv32_asm::assemble_ir(instr, &mut empty_hash, address)
+            .unwrap()
+            .unwrap();
+        memory.populate(address, encoding);
+    }
+    let mut tracer = InstructionMixTracer::create_from_initial_state(&state, ());
+    let mut mmu = NoMMU::default();
+    for cycle in 0..program.len() as u32 {
+        state.cycle(&mut memory, &mut tracer, &mut mmu, &mut ZeroedSource, cycle);
+    }
+
+    tracer
+}
+
+#[test]
+fn test_base_isa_runs_on_every_config() {
+    let report = mix_of(&["addi x1, x0, 5", "add x2, x1, x1", "add x3, x2, x1"], &[]).report();
+
+    assert_eq!(report.total_retired, 3);
+    assert_eq!(report.by_mnemonic["add"], 2);
+    assert_eq!(report.by_mnemonic["addi"], 1);
+    assert!(report.by_feature.is_empty());
+    assert_eq!(
+        report.sufficient_configs,
+        vec!["ReducedIMIsaConfig", "IMStandardIsaConfig"]
+    );
+}
+
+#[test]
+fn test_signed_operations_require_standard_config() {
+    let report = mix_of(
+        &[
+            "sra x3, x1, x2",
+            "mulh x3, x1, x2",
+            "div x3, x1, x2",
+            "divu x3, x1, x2",
+        ],
+        &[(1, 0x80000000), (2, 3)],
+    )
+    .report();
+
+    assert_eq!(report.total_retired, 4);
+    assert_eq!(report.by_feature[&IsaFeature::Sra], 1);
+    assert_eq!(report.by_feature[&IsaFeature::SignedMul], 1);
+    assert_eq!(report.by_feature[&IsaFeature::SignedDiv], 1);
+    assert_eq!(report.sufficient_configs, vec!["IMStandardIsaConfig"]);
+
+    let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+    assert_eq!(json["by_mnemonic"]["divu"], 1);
+    assert_eq!(json["by_feature"]["SignedDiv"], 1);
+    assert_eq!(json["sufficient_configs"][0], "IMStandardIsaConfig");
+
+    let table = report.to_string();
+    assert!(table.contains("Sufficient configs: IMStandardIsaConfig"));
+}
+
+#[test]
+fn test_non_determinism_csr_is_counted() {
+    // csrrw x1, 0x7c0, x0
+    let instr = (0x7c0 << 20) | (0b001 << 12) | (1 << 7) | 0b1110011;
+    let mut state = RiscV32State::<IMStandardIsaConfig>::initial(INITIAL_PC);
+    let mut memory = VectorMemoryImpl::new_for_byte_size(16);
+    memory.populate(INITIAL_PC, instr);
+    let mut tracer = InstructionMixTracer::create_from_initial_state(&state, ());
+    let mut mmu = NoMMU::default();
+    state.cycle(&mut memory, &mut tracer, &mut mmu, &mut ZeroedSource, 0);
+
+    let report = tracer.report();
+    assert_eq!(report.by_mnemonic["csrrw"], 1);
+    assert_eq!(report.by_csr_kind[&CsrKind::NonDeterminism], 1);
+    assert!(report.by_feature.is_empty());
+}
+
+#[test]
+fn test_disassembly() {
+    let cases = [
+        (0x00500093, "addi x1, x0, 5"),
+        (0xffc12183, "lw x3, -4(x2)"),
+        (0x00312423, "sw x3, 8(x2)"),
+        (0xfe208ee3, "beq x1, x2, -4"),
+        (0x123450b7, "lui x1, 0x12345"),
+        (0x4020d1b3, "sra x3, x1, x2"),
+        (0x7c0010f3, "csrrw x1, 0x7c0, x0"),
+    ];
+    for (instr, expected) in cases {
+        assert_eq!(decode(instr).unwrap().to_string(), expected);
+    }
+    assert!(decode(0).is_none());
+}
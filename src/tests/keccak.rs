@@ -0,0 +1,85 @@
+use std::fmt::Write;
+
+use super::*;
+use crate::analysis::cost_model::{CostModel, CostTracer};
+use crate::delegations::keccak::{
+    keccak_f1600, KECCAK_F1600_ABI_NUM_MEM_ACCESSES, KECCAK_F1600_ACCESS_ID,
+};
+
+// Keccak-256 absorbs 136 bytes per permutation.
+const RATE: usize = 136;
+
+// Keccak-256 of a message that fits in one block, with the permutation done by the guest.
+fn keccak256_in_guest(message: &[u8]) -> ([u8; 32], CostTracer) {
+    assert!(message.len() < RATE);
+    let mut block = [0u8; 200];
+    block[..message.len()].copy_from_slice(message);
+    block[message.len()] ^= 0x01;
+    block[RATE - 1] ^= 0x80;
+
+    let words: Vec<u32> = block
+        .chunks(4)
+        .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+        .collect();
+    let mut tracer = CostTracer::create_from_initial_state(
+        &RiscV32State::<IMStandardIsaConfig>::initial(INITIAL_PC),
+        CostModel::default(),
+    );
+    let words = run_delegation(
+        KECCAK_F1600_ACCESS_ID,
+        &words,
+        &mut tracer,
+        &mut DelegationRegistry::builtin(),
+    );
+
+    let mut digest = [0u8; 32];
+    for (bytes, word) in digest.chunks_mut(4).zip(words) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+
+    (digest, tracer)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        write!(hex, "{:02x}", byte).unwrap();
+        hex
+    })
+}
+
+#[test]
+fn test_permutation_of_zero_state() {
+    let mut state = [0u64; 25];
+    keccak_f1600(&mut state);
+
+    assert_eq!(state[0], 0xf1258f7940e1dde7);
+    assert_eq!(state[1], 0x84d5ccf933c0478a);
+}
+
+#[test]
+fn test_keccak256_vectors() {
+    let (digest, _) = keccak256_in_guest(b"");
+    assert_eq!(
+        hex(&digest),
+        "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+    );
+
+    let (digest, _) = keccak256_in_guest(b"abc");
+    assert_eq!(
+        hex(&digest),
+        "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45"
+    );
+}
+
+#[test]
+fn test_permutation_is_traced_as_batch_access() {
+    let (_, tracer) = keccak256_in_guest(b"abc");
+    let report = tracer.report(None);
+
+    let keccak = &report.delegations["keccak_f1600"];
+    assert_eq!(keccak.invocations, 1);
+    assert_eq!(
+        keccak.batch_accesses,
+        KECCAK_F1600_ABI_NUM_MEM_ACCESSES as u64
+    );
+}
@@ -0,0 +1,169 @@
+use super::*;
+use crate::abstractions::tracer::BatchAccessPartialData;
+use crate::analysis::cost_model::{CostModel, CostTracer};
+use crate::delegations::memory_copy::{MEMORY_COPY_ACCESS_ID, MEMORY_OP_COPY, MEMORY_OP_FILL};
+
+const DESCRIPTOR: u32 = DELEGATION_WORDS;
+const BUFFER: u32 = DESCRIPTOR + 0x100;
+const BUFFER_WORDS: usize = 16;
+
+// Batch accesses by base address, in order.
+#[derive(Debug, Default)]
+struct BatchLog {
+    batches: Vec<(u32, Vec<BatchAccessPartialData>)>,
+}
+
+impl Tracer<IMStandardIsaConfig> for BatchLog {
+    type AuxData = ();
+
+    fn create_from_initial_state(
+        _state: &RiscV32State<IMStandardIsaConfig>,
+        _aux_data: Self::AuxData,
+    ) -> Self {
+        Self::default()
+    }
+
+    fn trace_batch_memory_access(
+        &mut self,
+        access_id: u32,
+        phys_address_high: u16,
+        accesses: &[BatchAccessPartialData],
+        _proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+        assert_eq!(access_id, MEMORY_COPY_ACCESS_ID);
+        self.batches
+            .push(((phys_address_high as u32) << 16, accesses.to_vec()));
+    }
+
+    fn trace_batch_memory_access_at(
+        &mut self,
+        access_id: u32,
+        phys_address: u64,
+        accesses: &[BatchAccessPartialData],
+        _proc_cycle: u32,
+        _cycle_timestamp: u32,
+    ) {
+        assert_eq!(access_id, MEMORY_COPY_ACCESS_ID);
+        self.batches.push((phys_address as u32, accesses.to_vec()));
+    }
+}
+
+// Runs `csrrw x0, memory_copy, x1` on `descriptor` with `buffer` stored at `BUFFER`, and returns
+// the buffer afterwards.
+fn run_memory_op<TR: Tracer<IMStandardIsaConfig>>(
+    descriptor: [u32; 4],
+    buffer: &[u32; BUFFER_WORDS],
+    tracer: &mut TR,
+) -> [u32; BUFFER_WORDS] {
+    let buffer_offset = (BUFFER - DESCRIPTOR) as usize / 4;
+    let mut words = descriptor.to_vec();
+    words.resize(buffer_offset, 0);
+    words.extend_from_slice(buffer);
+
+    let words = run_delegation(
+        MEMORY_COPY_ACCESS_ID,
+        &words,
+        tracer,
+        &mut DelegationRegistry::builtin(),
+    );
+    words[buffer_offset..].try_into().unwrap()
+}
+
+fn counting_buffer() -> [u32; BUFFER_WORDS] {
+    std::array::from_fn(|idx| idx as u32 + 1)
+}
+
+#[test]
+fn test_copy() {
+    let descriptor = [MEMORY_OP_COPY, BUFFER + 32, BUFFER, 4];
+    let mut log = BatchLog::default();
+    let buffer = run_memory_op(descriptor, &counting_buffer(), &mut log);
+
+    assert_eq!(buffer[..12], [1, 2, 3, 4, 5, 6, 7, 8, 1, 2, 3, 4]);
+    assert_eq!(buffer[12..], [13, 14, 15, 16]);
+
+    let reads = |values: &[u32]| -> Vec<BatchAccessPartialData> {
+        values
+            .iter()
+            .map(|&read_value| BatchAccessPartialData::Read { read_value })
+            .collect()
+    };
+    let writes: Vec<_> = (0..4)
+        .map(|idx| BatchAccessPartialData::Write {
+            read_value: idx + 9,
+            written_value: idx + 1,
+        })
+        .collect();
+    assert_eq!(
+        log.batches,
+        vec![
+            (DESCRIPTOR, reads(&descriptor)),
+            (BUFFER, reads(&[1, 2, 3, 4])),
+            (BUFFER + 32, writes),
+        ]
+    );
+}
+
+#[test]
+fn test_overlapping_copy_behaves_as_memmove() {
+    let forward = run_memory_op(
+        [MEMORY_OP_COPY, BUFFER + 8, BUFFER, 8],
+        &counting_buffer(),
+        &mut (),
+    );
+    assert_eq!(forward[..10], [1, 2, 1, 2, 3, 4, 5, 6, 7, 8]);
+
+    let backward = run_memory_op(
+        [MEMORY_OP_COPY, BUFFER, BUFFER + 8, 8],
+        &counting_buffer(),
+        &mut (),
+    );
+    assert_eq!(backward[..10], [3, 4, 5, 6, 7, 8, 9, 10, 9, 10]);
+}
+
+#[test]
+fn test_fill() {
+    let mut log = BatchLog::default();
+    let buffer = run_memory_op(
+        [MEMORY_OP_FILL, BUFFER + 4, 0xdeadbeef, 3],
+        &counting_buffer(),
+        &mut log,
+    );
+
+    assert_eq!(buffer[..5], [1, 0xdeadbeef, 0xdeadbeef, 0xdeadbeef, 5]);
+    // the value is not read from memory
+    assert_eq!(log.batches.len(), 2);
+    assert_eq!(log.batches[1].0, BUFFER + 4);
+}
+
+#[test]
+fn test_empty_range_is_a_no_op() {
+    let mut log = BatchLog::default();
+    let buffer = run_memory_op(
+        [MEMORY_OP_COPY, BUFFER + 4, BUFFER, 0],
+        &counting_buffer(),
+        &mut log,
+    );
+
+    assert_eq!(buffer, counting_buffer());
+    assert_eq!(log.batches.len(), 1);
+}
+
+#[test]
+fn test_copy_is_traced_as_one_invocation() {
+    let mut tracer = CostTracer::create_from_initial_state(
+        &RiscV32State::<IMStandardIsaConfig>::initial(0),
+        CostModel::default(),
+    );
+    run_memory_op(
+        [MEMORY_OP_COPY, BUFFER + 32, BUFFER, 8],
+        &counting_buffer(),
+        &mut tracer,
+    );
+    let report = tracer.report(None);
+
+    let memory_copy = &report.delegations["memory_copy"];
+    assert_eq!(memory_copy.invocations, 1);
+    assert_eq!(memory_copy.batch_accesses, 4 + 8 + 8);
+}
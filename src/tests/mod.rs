@@ -1,21 +1,49 @@
-use crate::cycle::IMStandardIsaConfig;
+use crate::abstractions::memory::{AccessType, MemorySource};
+use crate::abstractions::tracer::Tracer;
+use crate::cycle::status_registers::TrapReason;
+use crate::cycle::{IMStandardIsaConfig, MachineConfig};
+use crate::delegations::DelegationRegistry;
 use crate::{
     abstractions::{memory::VectorMemoryImpl, non_determinism::ZeroedSource},
     cycle::state::RiscV32State,
     mmu::NoMMU,
 };
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 mod add;
 mod addi;
+mod annotate;
 mod beq;
+mod call_trace;
+mod cost_model;
+mod delegation_faults;
+mod delegations;
+mod ec;
+mod heap;
+mod instruction_mix;
+mod keccak;
+mod memory_copy;
 mod mul;
 mod mulh;
 mod mulhu;
+mod poseidon2;
+mod profile;
+mod property;
+mod reference_model;
 mod rem;
+mod sha256;
 mod slt;
 mod sltu;
 mod sra;
+mod stack_usage;
+mod tracers;
+mod u256;
+mod uninit;
+mod unwind;
+mod watch;
 
 const INITIAL_PC: u32 = 0;
 
@@ -104,3 +132,207 @@ fn test_reg_imm_op_64(op_name: &str, expected: u64, op1: u64, imm: u16) {
     // truncate
     test_reg_imm_op(op_name, expected as u32, op1 as u32, imm)
 }
+
+// Rng of the randomized tests. The seed is printed, which the test harness only shows for failing
+// tests, and a failure is reproduced by setting it in `RANDOM_TEST_SEED`.
+fn seeded_rng() -> StdRng {
+    let seed = match std::env::var("RANDOM_TEST_SEED") {
+        Ok(seed) => seed.parse().expect("RANDOM_TEST_SEED must be a u64"),
+        Err(_) => rand::random(),
+    };
+    println!("RANDOM_TEST_SEED={}", seed);
+
+    StdRng::seed_from_u64(seed)
+}
+
+// Writes the program to a binary in the temporary directory, for the runners.
+fn write_bin(name: &str, program: &[u32]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("{}_{}.bin", name, std::process::id()));
+    let bytes: Vec<u8> = program.iter().flat_map(|word| word.to_le_bytes()).collect();
+    std::fs::write(&path, bytes).unwrap();
+
+    path
+}
+
+// Writes a little endian RV32 ELF that only has a symbol table, with the functions given as
+// `(name, address, size)`.
+fn write_symbols(name: &str, functions: &[(&str, u32, u32)]) -> PathBuf {
+    let u16 = |value: u16| value.to_le_bytes().to_vec();
+    let u32 = |value: u32| value.to_le_bytes().to_vec();
+
+    let shstrtab = b"\0.text\0.symtab\0.strtab\0.shstrtab\0".to_vec();
+    let mut strtab = vec![0u8];
+    let mut symtab = vec![0u8; 16];
+    for (name, address, size) in functions {
+        symtab.extend(u32(strtab.len() as u32));
+        symtab.extend(u32(*address));
+        symtab.extend(u32(*size));
+        // global function in .text
+        symtab.extend([0x12, 0]);
+        symtab.extend(u16(1));
+        strtab.extend(name.bytes().chain([0]));
+    }
+    let text_size = functions
+        .iter()
+        .map(|(_, address, size)| address + size)
+        .max();
+
+    let symtab_offset = 52;
+    let strtab_offset = symtab_offset + symtab.len() as u32;
+    let shstrtab_offset = strtab_offset + strtab.len() as u32;
+    let shstrtab_size = shstrtab.len() as u32;
+    let section_headers_offset = (shstrtab_offset + shstrtab_size).next_multiple_of(4);
+
+    let mut elf = b"\x7fELF\x01\x01\x01".to_vec();
+    elf.resize(16, 0);
+    // executable for RISC-V, without program headers
+    elf.extend([u16(2), u16(243)].concat());
+    elf.extend([u32(1), u32(0), u32(0), u32(section_headers_offset), u32(0)].concat());
+    elf.extend([u16(52), u16(0), u16(0), u16(40), u16(5), u16(4)].concat());
+    elf.extend(symtab);
+    elf.extend(strtab);
+    elf.extend(shstrtab);
+    elf.resize(section_headers_offset as usize, 0);
+
+    // name, type, flags, address, offset, size, link, info, alignment, entry size
+    let sections = [
+        [0; 10],
+        [1, 8, 6, 0, 0, text_size.unwrap_or(0), 0, 0, 4, 0],
+        [
+            7,
+            2,
+            0,
+            0,
+            symtab_offset,
+            strtab_offset - symtab_offset,
+            3,
+            1,
+            4,
+            16,
+        ],
+        [
+            15,
+            3,
+            0,
+            0,
+            strtab_offset,
+            shstrtab_offset - strtab_offset,
+            0,
+            0,
+            1,
+            0,
+        ],
+        [23, 3, 0, 0, shstrtab_offset, shstrtab_size, 0, 0, 1, 0],
+    ];
+    for field in sections.iter().flatten() {
+        elf.extend(u32(*field));
+    }
+
+    let path = std::env::temp_dir().join(format!("{}_{}.elf", name, std::process::id()));
+    std::fs::write(&path, elf).unwrap();
+
+    path
+}
+
+// Same ISA as `C`, but traps are handled instead of aborting the simulation, so
+// we can check the exact trap reason.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+struct WithTraps<C: MachineConfig>(std::marker::PhantomData<C>);
+
+impl<C: MachineConfig> MachineConfig for WithTraps<C> {
+    const SUPPORT_SIGNED_MUL: bool = C::SUPPORT_SIGNED_MUL;
+    const SUPPORT_SIGNED_DIV: bool = C::SUPPORT_SIGNED_DIV;
+    const SUPPORT_SIGNED_LOAD: bool = C::SUPPORT_SIGNED_LOAD;
+    const SUPPORT_LOAD_LESS_THAN_WORD: bool = C::SUPPORT_LOAD_LESS_THAN_WORD;
+    const SUPPORT_SRA: bool = C::SUPPORT_SRA;
+    const SUPPORT_ROT: bool = C::SUPPORT_ROT;
+    const SUPPORT_MOPS: bool = C::SUPPORT_MOPS;
+    const HANDLE_EXCEPTIONS: bool = true;
+    const SUPPORT_STANDARD_CSRS: bool = C::SUPPORT_STANDARD_CSRS;
+    const SUPPORT_ONLY_CSRRW: bool = C::SUPPORT_ONLY_CSRRW;
+}
+
+// The IM ISA with traps handled and the machine trap CSRs implemented, for tests that set up
+// their own trap handler.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+struct MachineWithTraps;
+
+impl MachineConfig for MachineWithTraps {
+    const SUPPORT_SIGNED_MUL: bool = true;
+    const SUPPORT_SIGNED_DIV: bool = true;
+    const SUPPORT_SIGNED_LOAD: bool = true;
+    const SUPPORT_LOAD_LESS_THAN_WORD: bool = true;
+    const SUPPORT_SRA: bool = true;
+    const SUPPORT_ROT: bool = false;
+    const SUPPORT_MOPS: bool = false;
+    const HANDLE_EXCEPTIONS: bool = true;
+    const SUPPORT_STANDARD_CSRS: bool = true;
+    const SUPPORT_ONLY_CSRRW: bool = false;
+}
+
+fn csrrw(rd: u32, csr: u32, rs1: u32) -> u32 {
+    (csr << 20) | (rs1 << 15) | (0b001 << 12) | (rd << 7) | 0b1110011
+}
+
+// Delegation words are stored at a multiple of 64 KiB, as the ABI requires.
+const DELEGATION_WORDS: u32 = 1 << 16;
+
+// Runs `csrrw x0, csr, x1` with `words` stored at `DELEGATION_WORDS` and x1 pointing to them, and
+// returns the words afterwards. The delegation must accept the words.
+fn run_delegation<TR: Tracer<IMStandardIsaConfig>>(
+    csr: u32,
+    words: &[u32],
+    tracer: &mut TR,
+    registry: &mut DelegationRegistry,
+) -> Vec<u32> {
+    let mut state = RiscV32State::<IMStandardIsaConfig>::initial(INITIAL_PC);
+    state.registers[1] = DELEGATION_WORDS;
+    let words = run_delegation_on(&mut state, csr, words, words.len(), tracer, registry);
+    if let Some(fault) = registry.take_faults().first() {
+        panic!("{}", fault);
+    }
+
+    words
+}
+
+// Same as `run_delegation`, but on a given state, whose x1 is left as is, and in a memory of
+// `memory_words` words past `DELEGATION_WORDS`.
+fn run_delegation_on<C: MachineConfig, TR: Tracer<C>>(
+    state: &mut RiscV32State<C>,
+    csr: u32,
+    words: &[u32],
+    memory_words: usize,
+    tracer: &mut TR,
+    registry: &mut DelegationRegistry,
+) -> Vec<u32>
+where
+    [(); { C::SUPPORT_LOAD_LESS_THAN_WORD } as usize]:,
+{
+    let mut memory =
+        VectorMemoryImpl::new_for_byte_size(DELEGATION_WORDS as usize + memory_words * 4);
+    memory.populate(INITIAL_PC, csrrw(0, csr, 1));
+    for (idx, word) in words.iter().enumerate() {
+        memory.populate(DELEGATION_WORDS + idx as u32 * 4, *word);
+    }
+    state.pc = INITIAL_PC;
+    state.cycle_ext(
+        &mut memory,
+        tracer,
+        &mut NoMMU::default(),
+        &mut ZeroedSource,
+        registry,
+        0,
+        0,
+    );
+
+    let mut trap = TrapReason::NoTrap;
+    (0..words.len() as u64)
+        .map(|idx| {
+            memory.get(
+                DELEGATION_WORDS as u64 + idx * 4,
+                AccessType::MemLoad,
+                &mut trap,
+            )
+        })
+        .collect()
+}
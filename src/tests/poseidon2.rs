@@ -0,0 +1,147 @@
+use std::sync::Arc;
+
+use super::*;
+use crate::analysis::cost_model::{CostModel, CostTracer};
+use crate::delegations::poseidon2::*;
+
+fn run_in_guest(
+    input: [u32; POSEIDON2_WIDTH],
+    registry: &mut DelegationRegistry,
+) -> ([u32; POSEIDON2_WIDTH], CostTracer) {
+    let mut tracer = CostTracer::create_from_initial_state(
+        &RiscV32State::<IMStandardIsaConfig>::initial(INITIAL_PC),
+        CostModel::default(),
+    );
+    let output = run_delegation(POSEIDON2_ACCESS_ID, &input, &mut tracer, registry);
+
+    (output.try_into().unwrap(), tracer)
+}
+
+fn pow(mut base: u32, mut exponent: u32) -> u32 {
+    let mut result = 1u64;
+    while exponent != 0 {
+        if exponent & 1 != 0 {
+            result = result * base as u64 % M31_MODULUS as u64;
+        }
+        base = (base as u64 * base as u64 % M31_MODULUS as u64) as u32;
+        exponent >>= 1;
+    }
+
+    result as u32
+}
+
+#[test]
+fn test_linear_layers() {
+    // the first column of M4, doubled in its own chunk by the circulant
+    let mut state = [0u32; POSEIDON2_WIDTH];
+    state[0] = 1;
+    external_linear_layer(&mut state, &POSEIDON2_PAPER_M4);
+    assert_eq!(state, [10, 8, 2, 2, 5, 4, 1, 1, 5, 4, 1, 1, 5, 4, 1, 1]);
+
+    let mut state = [0u32; POSEIDON2_WIDTH];
+    state[0] = 1;
+    external_linear_layer(&mut state, &POSEIDON2_CIRCULANT_M4);
+    assert_eq!(state, [4, 2, 2, 6, 2, 1, 1, 3, 2, 1, 1, 3, 2, 1, 1, 3]);
+
+    // sum of the state plus the diagonal, which is -2 for the first element
+    let mut state = [1u32; POSEIDON2_WIDTH];
+    internal_linear_layer(&mut state, &POSEIDON2_INTERNAL_DIAGONAL);
+    assert_eq!(state[0], 14);
+    assert_eq!(state[1], 17);
+    assert_eq!(state[15], 16 + (1 << 16));
+}
+
+#[test]
+fn test_zero_state_with_zero_constants_is_fixed() {
+    let constants = Poseidon2Constants {
+        external_matrix: POSEIDON2_PAPER_M4,
+        internal_diagonal: POSEIDON2_INTERNAL_DIAGONAL,
+        initial_external: [[0; POSEIDON2_WIDTH]; POSEIDON2_HALF_FULL_ROUNDS],
+        internal: [0; POSEIDON2_PARTIAL_ROUNDS],
+        terminal_external: [[0; POSEIDON2_WIDTH]; POSEIDON2_HALF_FULL_ROUNDS],
+    };
+    let mut state = [0u32; POSEIDON2_WIDTH];
+    poseidon2_permutation(&mut state, &constants);
+
+    assert_eq!(state, [0; POSEIDON2_WIDTH]);
+}
+
+#[test]
+fn test_sbox_is_a_permutation() {
+    // 5 * 1717986917 = 1 mod (p - 1)
+    for x in [0, 1, 2, 12345, M31_MODULUS - 1] {
+        assert_eq!(pow(pow(x, 5), 1717986917), x);
+    }
+}
+
+fn registry_with(constants: Poseidon2Constants) -> DelegationRegistry {
+    let mut registry = DelegationRegistry::new();
+    registry.register(POSEIDON2_ACCESS_ID, Arc::new(Poseidon2 { constants }));
+
+    registry
+}
+
+#[test]
+fn test_plonky3_round_constants() {
+    // the first outputs of `rand_xoshiro`'s `Xoroshiro128Plus::seed_from_u64(1)` are 1341504397,
+    // 4105921379 and 1735190347
+    let constants = Poseidon2Constants::plonky3();
+    assert_eq!(
+        constants.initial_external[0][..3],
+        [1341504397 >> 1, 4105921379 >> 1, 1735190347 >> 1]
+    );
+    assert_eq!(constants.external_matrix, POSEIDON2_CIRCULANT_M4);
+}
+
+#[test]
+fn test_builtin_vector() {
+    let input = core::array::from_fn(|idx| idx as u32);
+    let mut registry = DelegationRegistry::builtin();
+    let mut expected = input;
+    poseidon2_permutation(&mut expected, &Poseidon2Constants::plonky3());
+
+    let (output, tracer) = run_in_guest(input, &mut registry);
+    assert_eq!(output, expected);
+    assert_eq!(output, PLONKY3_VECTOR);
+
+    let report = tracer.report(None);
+    assert_eq!(
+        report.delegations["poseidon2_m31"].batch_accesses,
+        POSEIDON2_ABI_NUM_MEM_ACCESSES as u64
+    );
+
+    // the prime itself is read as zero
+    let mut non_canonical = input;
+    non_canonical[0] = M31_MODULUS;
+    assert_eq!(run_in_guest(non_canonical, &mut registry).0, output);
+}
+
+#[test]
+fn test_constants_are_pluggable() {
+    let input = [7u32; POSEIDON2_WIDTH];
+    let reference = run_in_guest(input, &mut DelegationRegistry::builtin()).0;
+
+    let mut round_constant = Poseidon2Constants::plonky3();
+    round_constant.internal[0] ^= 1;
+    let mut external_matrix = Poseidon2Constants::plonky3();
+    external_matrix.external_matrix = POSEIDON2_PAPER_M4;
+    let mut internal_diagonal = Poseidon2Constants::plonky3();
+    internal_diagonal.internal_diagonal[1] = 2;
+
+    for constants in [round_constant, external_matrix, internal_diagonal] {
+        let (output, _) = run_in_guest(input, &mut registry_with(constants.clone()));
+
+        let mut expected = input;
+        poseidon2_permutation(&mut expected, &constants);
+        assert_eq!(output, expected);
+        assert_ne!(output, reference);
+    }
+}
+
+// Permutation of [0, 1, .., 15] with `Poseidon2Constants::plonky3`, computed by this
+// implementation. It still has to be checked against Plonky3's own `Poseidon2Mersenne31::<16>`
+// built from the same seed.
+const PLONKY3_VECTOR: [u32; POSEIDON2_WIDTH] = [
+    1498115607, 1849121381, 1654568640, 1005364393, 1584879234, 541502788, 1316438945, 975126390,
+    2011985775, 295237031, 248450693, 1522502666, 788611421, 1557878960, 67783709, 868717078,
+];
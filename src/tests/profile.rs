@@ -0,0 +1,250 @@
+use crate::sim::{Profile, ProfileFrame, ProfilerWeighting};
+
+fn frame(name: &str, line: Option<u64>) -> ProfileFrame {
+    ProfileFrame {
+        name: name.to_owned(),
+        file: line.map(|_| "src/main.rs".to_owned()),
+        line,
+    }
+}
+
+fn profile() -> Profile {
+    Profile {
+        frames: vec![
+            frame("main", Some(3)),
+            frame("hash", Some(10)),
+            frame("memcpy", None),
+        ],
+        stacks: vec![(vec![0, 1, 2], 5), (vec![0, 1], 2), (vec![0], 1)],
+        frequency_recip: 100,
+        weighting: ProfilerWeighting::Cycles,
+    }
+}
+
+#[test]
+fn test_folded_stacks() {
+    let mut folded = Vec::new();
+    profile().write_folded(&mut folded).unwrap();
+
+    assert_eq!(
+        String::from_utf8(folded).unwrap(),
+        "main 1\nmain;hash 2\nmain;hash;memcpy 5\n"
+    );
+}
+
+#[test]
+fn test_speedscope() {
+    let mut json = Vec::new();
+    profile().write_speedscope(&mut json).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+
+    let frames = &json["shared"]["frames"];
+    assert_eq!(frames[1]["name"], "hash");
+    assert_eq!(frames[1]["file"], "src/main.rs");
+    assert_eq!(frames[1]["line"], 10);
+    assert!(frames[2].get("file").is_none());
+
+    let profile = &json["profiles"][0];
+    assert_eq!(profile["type"], "sampled");
+    assert_eq!(profile["name"], "cycles");
+    assert_eq!(profile["endValue"], 8);
+    assert_eq!(profile["samples"][0], serde_json::json!([0, 1, 2]));
+    assert_eq!(profile["weights"], serde_json::json!([5, 2, 1]));
+}
+
+#[test]
+fn test_pprof() {
+    let pprof = profile().to_pprof();
+
+    // the first sample type is `samples`/`count`, interned right after the empty string
+    assert_eq!(&pprof[..6], &[0x0a, 0x04, 0x08, 0x01, 0x10, 0x02]);
+    // the deepest sample lists its locations from the innermost frame
+    let sample = [
+        0x12, 0x0a, 0x0a, 0x03, 0x03, 0x02, 0x01, 0x12, 0x03, 0x05, 0xf4, 0x03,
+    ];
+    assert!(pprof.windows(sample.len()).any(|w| w == sample));
+    for name in ["main", "hash", "memcpy", "src/main.rs"] {
+        assert!(pprof.windows(name.len()).any(|w| w == name.as_bytes()));
+    }
+}
+
+#[test]
+fn test_function_table() {
+    let mut profile = profile();
+    // recursion must not inflate the inclusive numbers
+    profile.stacks.push((vec![0, 1, 1], 2));
+
+    let table = profile.function_table();
+    assert_eq!(table.total_samples, 10);
+
+    let names = table
+        .functions
+        .iter()
+        .map(|function| function.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["memcpy", "hash", "main"]);
+
+    let hash = &table.functions[1];
+    assert_eq!(hash.self_samples, 4);
+    assert_eq!(hash.inclusive_samples, 9);
+    assert_eq!(hash.inclusive_cycles, Some(900));
+    assert_eq!(hash.self_percentage, 40.0);
+    assert_eq!(hash.callers.len(), 2);
+    assert_eq!(hash.callers["main"], 9);
+    assert_eq!(hash.callers["hash"], 2);
+
+    let csv = table.to_csv();
+    let mut lines = csv.lines();
+    assert!(lines.next().unwrap().starts_with("function,self_samples"));
+    assert_eq!(
+        lines.next().unwrap(),
+        "\"memcpy\",5,50.0000,5,50.0000,500,500,1"
+    );
+}
+
+#[test]
+fn test_event_weighting_outputs() {
+    let profile = Profile {
+        frequency_recip: 1,
+        weighting: ProfilerWeighting::MemoryLoads,
+        ..profile()
+    };
+
+    let mut json = Vec::new();
+    profile.write_speedscope(&mut json).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!(json["profiles"][0]["name"], "memory_loads");
+
+    // the sample and period types name the event, and nothing is counted in cycles
+    let pprof = profile.to_pprof();
+    assert!(pprof.windows(12).any(|w| w == b"memory_loads"));
+    assert!(!pprof.windows(6).any(|w| w == b"cycles"));
+
+    let table = profile.function_table();
+    assert!(table
+        .functions
+        .iter()
+        .all(|function| function.self_cycles.or(function.inclusive_cycles).is_none()));
+    let csv = table.to_csv();
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        "function,self_samples,self_percentage,inclusive_samples,inclusive_percentage,call_sites"
+    );
+    assert_eq!(lines.next().unwrap(), "\"memcpy\",5,62.5000,5,62.5000,1");
+    assert!(table.to_string().starts_with("Total memory_loads: 8\n"));
+    assert!(!table.to_json().contains("cycles\":"));
+}
+
+#[test]
+fn test_folded_roundtrip_and_diff() {
+    let mut folded = Vec::new();
+    profile().write_folded(&mut folded).unwrap();
+    let before = Profile::from_folded(&folded[..]).unwrap();
+    assert_eq!(before.folded_lines(), profile().folded_lines());
+
+    // `memcpy` got cheaper, everything else is unchanged
+    let after =
+        Profile::from_folded("main 1\nmain;hash 2\nmain;hash;memcpy 1\n".as_bytes()).unwrap();
+
+    let diff = before.compare(&after);
+    assert_eq!(diff.before_samples, 8);
+    assert_eq!(diff.after_samples, 4);
+    assert_eq!(diff.functions[0].name, "memcpy");
+    assert_eq!(diff.functions[0].before_self_share, 62.5);
+    assert_eq!(diff.functions[0].after_self_share, 25.0);
+    assert_eq!(diff.functions[0].self_share_delta, -37.5);
+    assert_eq!(diff.functions[1].name, "hash");
+    assert_eq!(diff.functions[1].self_share_delta, 25.0);
+
+    let mut svg = Vec::new();
+    before
+        .write_differential_flamegraph(&after, &mut svg)
+        .unwrap();
+    assert!(String::from_utf8(svg).unwrap().contains("memcpy"));
+
+    assert!(Profile::from_folded("main;hash\n".as_bytes()).is_err());
+}
+
+#[test]
+fn test_outputs_per_weighting() {
+    use crate::sim::{ProfilerOutput, ProfilerWeighting};
+    use std::path::PathBuf;
+
+    let output = ProfilerOutput::Flamegraph(PathBuf::from("out/flamegraph.svg"));
+    assert_eq!(
+        output.for_weighting(ProfilerWeighting::Cycles).path(),
+        &PathBuf::from("out/flamegraph.svg")
+    );
+    assert_eq!(
+        output.for_weighting(ProfilerWeighting::MemoryLoads).path(),
+        &PathBuf::from("out/flamegraph.memory_loads.svg")
+    );
+
+    let output = ProfilerOutput::FoldedStacks(PathBuf::from("stacks"));
+    assert_eq!(
+        output.for_weighting(ProfilerWeighting::Traps).path(),
+        &PathBuf::from("stacks.traps")
+    );
+}
+
+#[test]
+fn test_stacks_weighted_by_memory_loads() {
+    use super::{write_bin, write_symbols};
+    use crate::abstractions::non_determinism::ZeroedSource;
+    use crate::runner::run_simple_with_entry_point_and_non_determimism_source;
+    use crate::sim::{
+        DiagnosticsConfig, ProfilerConfig, ProfilerOutput, ProfilerWeighting, SimulatorConfig,
+    };
+
+    // `main` loads a word and calls `callee`, which loads two words and restores two registers
+    // from its frame. Both keep frame pointers, and `main`'s frame record is zeroed.
+    let program = [
+        0x00010137, // lui sp, 0x10
+        0x10010413, // addi s0, sp, 0x100
+        0x10002503, // lw a0, 0x100(zero)
+        0x014000ef, // jal ra, callee
+        0x0000006f, // j .
+        0x00000013, // nop
+        0x00000013, // nop
+        0x00000013, // nop
+        0xff010113, // callee: addi sp, sp, -16
+        0x00112623, // sw ra, 12(sp)
+        0x00812423, // sw s0, 8(sp)
+        0x01010413, // addi s0, sp, 16
+        0x10002583, // lw a1, 0x100(zero)
+        0x10402603, // lw a2, 0x104(zero)
+        0x00c12083, // lw ra, 12(sp)
+        0x00812403, // lw s0, 8(sp)
+        0x01010113, // addi sp, sp, 16
+        0x00008067, // ret
+    ];
+    let bin_path = write_bin("profile_memory_loads", &program);
+    let symbols_path = write_symbols(
+        "profile_memory_loads",
+        &[("main", 0x00, 0x20), ("callee", 0x20, 0x28)],
+    );
+    let folded_path = std::env::temp_dir().join(format!(
+        "profile_memory_loads_{}.folded",
+        std::process::id()
+    ));
+
+    let mut profiler_config = ProfilerConfig::new(folded_path.clone());
+    profiler_config.outputs = vec![ProfilerOutput::FoldedStacks(folded_path.clone())];
+    profiler_config.weightings = vec![ProfilerWeighting::MemoryLoads];
+    let mut diagnostics = DiagnosticsConfig::new(symbols_path.clone());
+    diagnostics.profiler_config = Some(profiler_config);
+    let config = SimulatorConfig::new(bin_path.clone(), 0, 64, Some(diagnostics));
+    let (_, fault, _) =
+        run_simple_with_entry_point_and_non_determimism_source(config, ZeroedSource);
+    assert_eq!(fault, None);
+
+    let output =
+        ProfilerOutput::FoldedStacks(folded_path).for_weighting(ProfilerWeighting::MemoryLoads);
+    let folded = std::fs::read_to_string(output.path()).unwrap();
+    for path in [bin_path, symbols_path, output.path().clone()] {
+        std::fs::remove_file(path).unwrap();
+    }
+
+    assert_eq!(folded, "main 1\nmain;callee 4\n");
+}
@@ -0,0 +1,406 @@
+use super::reference_model::{self, IsaFeatures, Outcome, MERSENNE31_MODULUS, ORACLE_CSR};
+use super::{seeded_rng, WithTraps};
+use crate::abstractions::csr_processor::NoExtraCSRs;
+use crate::abstractions::memory::VectorMemoryImpl;
+use crate::abstractions::non_determinism::ZeroedSource;
+use crate::cycle::state::{Mode, RiscV32State};
+use crate::cycle::status_registers::{MStatusRegister, TrapReason};
+use crate::cycle::{IMStandardIsaConfig, MachineConfig, ReducedIMIsaConfig};
+use crate::mmu::NoMMU;
+use rand::Rng;
+
+const MEMORY_SIZE: u32 = 1 << 12;
+const SAMPLES_PER_FORM: usize = 64;
+
+#[derive(Clone, Copy, Debug)]
+enum Form {
+    Lui,
+    Auipc,
+    Jal,
+    Jalr,
+    Branch { funct3: u32 },
+    Load { funct3: u32 },
+    Store { funct3: u32 },
+    OpImm { funct3: u32 },
+    ShiftImm { funct3: u32, funct7: u32 },
+    Op { funct3: u32, funct7: u32 },
+    Csr { funct3: u32, csr: Option<u32> },
+    Mop { funct7: u32 },
+    // opcodes that the machine doesn't implement at all
+    Unsupported { opcode: u32 },
+}
+
+fn all_forms() -> Vec<Form> {
+    let mut forms = vec![Form::Lui, Form::Auipc, Form::Jal, Form::Jalr];
+    for funct3 in 0..8 {
+        forms.push(Form::Branch { funct3 });
+        forms.push(Form::Load { funct3 });
+        forms.push(Form::Store { funct3 });
+        forms.push(Form::Csr {
+            funct3,
+            csr: Some(ORACLE_CSR),
+        });
+        forms.push(Form::Csr { funct3, csr: None });
+        if funct3 != 1 && funct3 != 5 {
+            forms.push(Form::OpImm { funct3 });
+        }
+        for funct7 in [0b0000000, 0b0000001, 0b0100000, 0b0110000] {
+            forms.push(Form::Op { funct3, funct7 });
+            if funct3 == 1 || funct3 == 5 {
+                forms.push(Form::ShiftImm { funct3, funct7 });
+            }
+        }
+    }
+    for funct7 in 0..(1 << 7) {
+        if funct7 & 0b1000001 == 0b1000001 {
+            forms.push(Form::Mop { funct7 });
+        }
+    }
+    // a malformed MOP encoding
+    forms.push(Form::Mop { funct7: 0b1000000 });
+    for opcode in [0b0001111, 0b0101111, 0b0000111, 0b1010011, 0b0011011] {
+        forms.push(Form::Unsupported { opcode });
+    }
+
+    forms
+}
+
+struct Sample {
+    instr: u32,
+    pc: u32,
+    // register values to force after the random state is created
+    overrides: Vec<(u32, u32)>,
+}
+
+fn r_type(opcode: u32, rd: u32, funct3: u32, rs1: u32, rs2: u32, funct7: u32) -> u32 {
+    (funct7 << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+}
+
+fn i_type(opcode: u32, rd: u32, funct3: u32, rs1: u32, imm: u32) -> u32 {
+    ((imm & 0xfff) << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+}
+
+fn s_type(funct3: u32, rs1: u32, rs2: u32, imm: u32) -> u32 {
+    ((imm >> 5 & 0x7f) << 25)
+        | (rs2 << 20)
+        | (rs1 << 15)
+        | (funct3 << 12)
+        | ((imm & 0x1f) << 7)
+        | 0b0100011
+}
+
+fn b_type(funct3: u32, rs1: u32, rs2: u32, imm: u32) -> u32 {
+    ((imm >> 12 & 1) << 31)
+        | ((imm >> 5 & 0x3f) << 25)
+        | (rs2 << 20)
+        | (rs1 << 15)
+        | (funct3 << 12)
+        | ((imm >> 1 & 0xf) << 8)
+        | ((imm >> 11 & 1) << 7)
+        | 0b1100011
+}
+
+fn j_type(rd: u32, imm: u32) -> u32 {
+    ((imm >> 20 & 1) << 31)
+        | ((imm >> 1 & 0x3ff) << 21)
+        | ((imm >> 11 & 1) << 20)
+        | ((imm >> 12 & 0xff) << 12)
+        | (rd << 7)
+        | 0b1101111
+}
+
+// Picks a base register and immediate such that the effective address is inside of the memory.
+fn memory_operand<R: Rng>(rng: &mut R, overrides: &mut Vec<(u32, u32)>) -> (u32, u32) {
+    let rs1 = rng.gen_range(0..32);
+    let address = rng.gen_range(0..MEMORY_SIZE - 4);
+    if rs1 == 0 {
+        // x0 as a base, so immediate is the address
+        return (0, address & 0x7ff);
+    }
+    let imm = rng.gen_range(0..(1 << 12));
+    let signed_imm = ((imm << 20) as i32 >> 20) as u32;
+    overrides.push((rs1, address.wrapping_sub(signed_imm)));
+
+    (rs1, imm)
+}
+
+fn sample<R: Rng>(rng: &mut R, form: Form) -> Sample {
+    let pc = rng.gen_range(0..MEMORY_SIZE / 4) * 4;
+    let rd = rng.gen_range(0..32);
+    let rs1 = rng.gen_range(0..32);
+    let rs2 = rng.gen_range(0..32);
+    let mut overrides = vec![];
+
+    let instr = match form {
+        Form::Lui => (rng.gen::<u32>() & 0xfffff000) | (rd << 7) | 0b0110111,
+        Form::Auipc => (rng.gen::<u32>() & 0xfffff000) | (rd << 7) | 0b0010111,
+        // we want to see both aligned and misaligned targets, so imm[1] is random
+        Form::Jal => j_type(rd, rng.gen::<u32>() & 0x1ffffe),
+        Form::Jalr => i_type(0b1100111, rd, 0, rs1, rng.gen()),
+        Form::Branch { funct3 } => {
+            // make equality comparisons succeed from time to time
+            if rng.gen_bool(0.25) && rs1 != 0 && rs2 != 0 {
+                let value = rng.gen();
+                overrides.push((rs1, value));
+                overrides.push((rs2, value));
+            }
+            b_type(funct3, rs1, rs2, rng.gen::<u32>() & 0x1ffe)
+        }
+        Form::Load { funct3 } => {
+            let (rs1, imm) = memory_operand(rng, &mut overrides);
+            i_type(0b0000011, rd, funct3, rs1, imm)
+        }
+        Form::Store { funct3 } => {
+            let (rs1, imm) = memory_operand(rng, &mut overrides);
+            s_type(funct3, rs1, rs2, imm)
+        }
+        Form::OpImm { funct3 } => i_type(0b0010011, rd, funct3, rs1, rng.gen()),
+        Form::ShiftImm { funct3, funct7 } => i_type(
+            0b0010011,
+            rd,
+            funct3,
+            rs1,
+            (funct7 << 5) | rng.gen_range(0..32),
+        ),
+        Form::Op { funct3, funct7 } => r_type(0b0110011, rd, funct3, rs1, rs2, funct7),
+        Form::Csr { funct3, csr } => {
+            // stay in the machine-level custom CSRs range to not hit privilege checks
+            let csr = csr.unwrap_or_else(|| rng.gen_range(0x7c1..0x800));
+            i_type(0b1110011, rd, funct3, rs1, csr)
+        }
+        Form::Mop { funct7 } => {
+            // MOPs are defined over canonical field elements
+            for reg in [rs1, rs2] {
+                if reg != 0 {
+                    overrides.push((reg, rng.gen_range(0..MERSENNE31_MODULUS)));
+                }
+            }
+            r_type(0b1110011, rd, 0b100, rs1, rs2, funct7)
+        }
+        Form::Unsupported { opcode } => (rng.gen::<u32>() & !0x7f) | opcode,
+    };
+
+    Sample {
+        instr,
+        pc,
+        overrides,
+    }
+}
+
+fn prepare<C: MachineConfig>(
+    rng: &mut impl Rng,
+    sample: &Sample,
+) -> (RiscV32State<C>, VectorMemoryImpl)
+where
+    [(); { C::SUPPORT_LOAD_LESS_THAN_WORD } as usize]:,
+{
+    let mut state = RiscV32State::<C>::new_random(sample.pc, false, false);
+    for (reg, value) in sample.overrides.iter() {
+        state.registers[*reg as usize] = *value;
+    }
+
+    let mut memory = VectorMemoryImpl::new_for_byte_size(MEMORY_SIZE as usize);
+    for word in memory.inner.iter_mut() {
+        *word = rng.gen();
+    }
+    memory.populate(sample.pc, sample.instr);
+
+    (state, memory)
+}
+
+fn expected_state<C: MachineConfig>(
+    initial: &RiscV32State<C>,
+    sample: &Sample,
+    outcome: Outcome,
+) -> RiscV32State<C> {
+    let mut expected = *initial;
+    // sapt is mirrored from the MMU at the end of every cycle
+    expected.sapt = 0;
+    match outcome {
+        Outcome::Retired {
+            rd, value, next_pc, ..
+        } => {
+            if rd != 0 {
+                expected.registers[rd as usize] = value;
+            }
+            expected.pc = next_pc;
+        }
+        Outcome::Trap(reason) => {
+            let trap_data = &mut expected.machine_mode_trap_data;
+            trap_data.handling.cause = reason.as_register_value();
+            trap_data.handling.tval = sample.instr;
+            trap_data.handling.epc = sample.pc;
+            let mie = MStatusRegister::mie_aligned_bit(trap_data.state.status);
+            MStatusRegister::set_mpie_to_value(&mut trap_data.state.status, mie);
+            expected.pc = trap_data.setup.tvec;
+            expected.extra_flags.set_mode(Mode::Machine);
+        }
+    }
+
+    expected
+}
+
+fn expected_memory_image(initial: &VectorMemoryImpl, outcome: Outcome) -> Vec<u32> {
+    let mut memory = initial.inner.clone();
+    if let Outcome::Retired {
+        store: Some(store), ..
+    } = outcome
+    {
+        let word = &mut memory[(store.address / 4) as usize];
+        let shift = (store.address % 4) * 8;
+        let mask = match store.num_bytes {
+            4 => u32::MAX,
+            num_bytes => ((1u32 << (num_bytes * 8)) - 1) << shift,
+        };
+        *word = (*word & !mask) | ((store.value << shift) & mask);
+    }
+
+    memory
+}
+
+fn check_form<C: MachineConfig>(rng: &mut impl Rng, form: Form)
+where
+    [(); { C::SUPPORT_LOAD_LESS_THAN_WORD } as usize]:,
+    [(); { <WithTraps<C> as MachineConfig>::SUPPORT_LOAD_LESS_THAN_WORD } as usize]:,
+{
+    let isa = IsaFeatures::of::<C>();
+
+    for _ in 0..SAMPLES_PER_FORM {
+        let sample = sample(rng, form);
+
+        // the machine as configured aborts the simulation on any trap
+        let (mut state, mut memory) = prepare::<C>(rng, &sample);
+        let outcome =
+            reference_model::execute(&isa, sample.instr, sample.pc, &state.registers, |address| {
+                memory.inner[(address / 4) as usize]
+            });
+        let expected = expected_state(&state, &sample, outcome);
+        let expected_memory = expected_memory_image(&memory, outcome);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            state.cycle_ext(
+                &mut memory,
+                &mut (),
+                &mut NoMMU::default(),
+                &mut ZeroedSource,
+                &mut NoExtraCSRs,
+                0,
+                0,
+            );
+        }));
+        match outcome {
+            Outcome::Trap(reason) => assert!(
+                result.is_err(),
+                "expected {:?} for {:?}, opcode 0x{:08x}, but it was executed",
+                reason,
+                form,
+                sample.instr
+            ),
+            Outcome::Retired { .. } => {
+                assert!(
+                    result.is_ok(),
+                    "unexpected trap for {:?}, opcode 0x{:08x}",
+                    form,
+                    sample.instr
+                );
+                assert_eq!(
+                    state, expected,
+                    "state mismatch for {:?}, opcode 0x{:08x}",
+                    form, sample.instr
+                );
+                assert!(
+                    memory.inner == expected_memory,
+                    "memory mismatch for {:?}, opcode 0x{:08x}",
+                    form,
+                    sample.instr
+                );
+            }
+        }
+
+        // same ISA, but trap reason and trap handling are observable
+        let (mut state, mut memory) = prepare::<WithTraps<C>>(rng, &sample);
+        let outcome =
+            reference_model::execute(&isa, sample.instr, sample.pc, &state.registers, |address| {
+                memory.inner[(address / 4) as usize]
+            });
+        let expected = expected_state(&state, &sample, outcome);
+        let expected_memory = expected_memory_image(&memory, outcome);
+
+        state.cycle_ext(
+            &mut memory,
+            &mut (),
+            &mut NoMMU::default(),
+            &mut ZeroedSource,
+            &mut NoExtraCSRs,
+            0,
+            0,
+        );
+        assert_eq!(
+            state, expected,
+            "state mismatch for {:?}, opcode 0x{:08x}, expected outcome {:?}",
+            form, sample.instr, outcome
+        );
+        assert!(
+            memory.inner == expected_memory,
+            "memory mismatch for {:?}, opcode 0x{:08x}",
+            form,
+            sample.instr
+        );
+    }
+}
+
+fn check_all_forms<C: MachineConfig>()
+where
+    [(); { C::SUPPORT_LOAD_LESS_THAN_WORD } as usize]:,
+    [(); { <WithTraps<C> as MachineConfig>::SUPPORT_LOAD_LESS_THAN_WORD } as usize]:,
+{
+    let mut rng = seeded_rng();
+    for form in all_forms() {
+        check_form::<C>(&mut rng, form);
+    }
+}
+
+#[test]
+fn test_random_instructions_standard_isa() {
+    check_all_forms::<IMStandardIsaConfig>();
+}
+
+#[test]
+fn test_random_instructions_reduced_isa() {
+    check_all_forms::<ReducedIMIsaConfig>();
+}
+
+#[test]
+fn test_reference_model_traps() {
+    // a few fixed points, so the reference model itself doesn't drift
+    let registers = [0u32; 32];
+    let standard = IsaFeatures::of::<IMStandardIsaConfig>();
+    let reduced = IsaFeatures::of::<ReducedIMIsaConfig>();
+
+    // lb x1, 1(x0)
+    let lb = i_type(0b0000011, 1, 0, 0, 1);
+    assert!(matches!(
+        reference_model::execute(&standard, lb, 0, &registers, |_| 0x0000ff00),
+        Outcome::Retired {
+            value: 0xffffffff,
+            ..
+        }
+    ));
+    assert_eq!(
+        reference_model::execute(&reduced, lb, 0, &registers, |_| 0),
+        Outcome::Trap(TrapReason::LoadAddressMisaligned)
+    );
+
+    // mulh x1, x2, x3
+    let mulh = r_type(0b0110011, 1, 1, 2, 3, 1);
+    assert_eq!(
+        reference_model::execute(&reduced, mulh, 0, &registers, |_| 0),
+        Outcome::Trap(TrapReason::IllegalInstruction)
+    );
+
+    // jal x0, 2
+    assert_eq!(
+        reference_model::execute(&standard, j_type(0, 2), 0, &registers, |_| 0),
+        Outcome::Trap(TrapReason::InstructionAddressMisaligned)
+    );
+}
@@ -0,0 +1,298 @@
+// Straightforward reference semantics for a single instruction. It is intentionally
+// written without reusing anything from `cycle/state.rs` or `cycle/opcode_formats`,
+// so that property tests compare two independent implementations.
+
+use crate::cycle::status_registers::TrapReason;
+use crate::cycle::MachineConfig;
+
+pub const MERSENNE31_MODULUS: u32 = 0x7fffffff;
+pub const ORACLE_CSR: u32 = 0x7c0;
+
+#[derive(Clone, Copy, Debug)]
+pub struct IsaFeatures {
+    pub signed_mul: bool,
+    pub signed_div: bool,
+    pub signed_load: bool,
+    pub sub_word_access: bool,
+    pub sra: bool,
+    pub rot: bool,
+    pub mops: bool,
+}
+
+impl IsaFeatures {
+    pub fn of<C: MachineConfig>() -> Self {
+        Self {
+            signed_mul: C::SUPPORT_SIGNED_MUL,
+            signed_div: C::SUPPORT_SIGNED_DIV,
+            signed_load: C::SUPPORT_SIGNED_LOAD,
+            sub_word_access: C::SUPPORT_LOAD_LESS_THAN_WORD,
+            sra: C::SUPPORT_SRA,
+            rot: C::SUPPORT_ROT,
+            mops: C::SUPPORT_MOPS,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Store {
+    pub address: u32,
+    pub num_bytes: u32,
+    pub value: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Retired {
+        rd: u32,
+        value: u32,
+        next_pc: u32,
+        store: Option<Store>,
+    },
+    Trap(TrapReason),
+}
+
+fn field(instr: u32, lowest_bit: u32, num_bits: u32) -> u32 {
+    (instr >> lowest_bit) & ((1u32 << num_bits) - 1)
+}
+
+fn imm_i(instr: u32) -> u32 {
+    ((instr as i32) >> 20) as u32
+}
+
+fn imm_s(instr: u32) -> u32 {
+    ((((instr as i32) >> 25) << 5) as u32) | field(instr, 7, 5)
+}
+
+fn imm_b(instr: u32) -> u32 {
+    let sign = (((instr as i32) >> 31) as u32) << 12;
+    sign | (field(instr, 7, 1) << 11) | (field(instr, 25, 6) << 5) | (field(instr, 8, 4) << 1)
+}
+
+fn imm_u(instr: u32) -> u32 {
+    instr & 0xfffff000
+}
+
+fn imm_j(instr: u32) -> u32 {
+    let sign = (((instr as i32) >> 31) as u32) << 20;
+    sign | (field(instr, 12, 8) << 12) | (field(instr, 20, 1) << 11) | (field(instr, 21, 10) << 1)
+}
+
+fn jump(rd: u32, pc: u32, target: u32) -> Outcome {
+    if target % 4 != 0 {
+        return Outcome::Trap(TrapReason::InstructionAddressMisaligned);
+    }
+
+    Outcome::Retired {
+        rd,
+        value: pc.wrapping_add(4),
+        next_pc: target,
+        store: None,
+    }
+}
+
+fn write_rd(rd: u32, value: u32, pc: u32) -> Outcome {
+    Outcome::Retired {
+        rd,
+        value,
+        next_pc: pc.wrapping_add(4),
+        store: None,
+    }
+}
+
+fn access_is_supported(isa: &IsaFeatures, address: u32, num_bytes: u32) -> bool {
+    if isa.sub_word_access {
+        // only accesses that do not cross a word boundary are served
+        (address % 4) + num_bytes <= 4 && address % num_bytes == 0
+    } else {
+        num_bytes == 4 && address % 4 == 0
+    }
+}
+
+fn alu(isa: &IsaFeatures, funct3: u32, funct7: u32, a: u32, b: u32, is_reg: bool) -> Option<u32> {
+    let shamt = b & 31;
+    let value = match (funct3, funct7) {
+        (0, 0b0100000) if is_reg => a.wrapping_sub(b),
+        (0, _) => a.wrapping_add(b),
+        (1, 0) => a << shamt,
+        (1, 0b0110000) if isa.rot && !is_reg => a.rotate_left(shamt),
+        (2, _) => ((a as i32) < (b as i32)) as u32,
+        (3, _) => (a < b) as u32,
+        (4, _) => a ^ b,
+        (5, 0) => a >> shamt,
+        (5, 0b0100000) if isa.sra => ((a as i32) >> shamt) as u32,
+        (5, 0b0110000) if isa.rot && !is_reg => a.rotate_right(shamt),
+        (6, _) => a | b,
+        (7, _) => a & b,
+        _ => return None,
+    };
+
+    // register-register forms additionally fix funct7 for every funct3
+    if is_reg && !(funct7 == 0 || (funct7 == 0b0100000 && (funct3 == 0 || funct3 == 5))) {
+        return None;
+    }
+
+    Some(value)
+}
+
+fn mul_div(isa: &IsaFeatures, funct3: u32, a: u32, b: u32) -> Option<u32> {
+    let sa = a as i32 as i64;
+    let sb = b as i32 as i64;
+    let ua = a as u64;
+    let ub = b as u64;
+    let value = match funct3 {
+        0 => (ua * ub) as u32,
+        1 if isa.signed_mul => ((sa * sb) >> 32) as u32,
+        2 if isa.signed_mul => ((sa * ub as i64) >> 32) as u32,
+        3 => ((ua * ub) >> 32) as u32,
+        4 if isa.signed_div => match b {
+            0 => u32::MAX,
+            _ => (sa / sb) as i32 as u32,
+        },
+        5 => a.checked_div(b).unwrap_or(u32::MAX),
+        6 if isa.signed_div => match b {
+            0 => a,
+            _ => (sa % sb) as i32 as u32,
+        },
+        7 => a.checked_rem(b).unwrap_or(a),
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+fn mop(funct7: u32, a: u32, b: u32) -> Option<u32> {
+    let modulus = MERSENNE31_MODULUS as u64;
+    let (a, b) = (a as u64, b as u64);
+    let mop_number = ((funct7 >> 1) & 0b11) | ((funct7 >> 5) & 1);
+    let value = match mop_number {
+        0 => (a + b) % modulus,
+        1 => (a + modulus - b) % modulus,
+        2 => (a * b) % modulus,
+        _ => return None,
+    };
+
+    Some(value as u32)
+}
+
+/// Executes `instr` located at `pc` in machine mode with an oracle that always answers zero
+/// and no custom CSRs. `load_word` returns the word at the given aligned address.
+pub fn execute(
+    isa: &IsaFeatures,
+    instr: u32,
+    pc: u32,
+    registers: &[u32; 32],
+    load_word: impl Fn(u32) -> u32,
+) -> Outcome {
+    use TrapReason::*;
+
+    let opcode = field(instr, 0, 7);
+    let rd = field(instr, 7, 5);
+    let funct3 = field(instr, 12, 3);
+    let rs1 = registers[field(instr, 15, 5) as usize];
+    let rs2 = registers[field(instr, 20, 5) as usize];
+    let funct7 = field(instr, 25, 7);
+
+    match opcode {
+        0b0110111 => write_rd(rd, imm_u(instr), pc),
+        0b0010111 => write_rd(rd, pc.wrapping_add(imm_u(instr)), pc),
+        0b1101111 => jump(rd, pc, pc.wrapping_add(imm_j(instr))),
+        0b1100111 => jump(rd, pc, rs1.wrapping_add(imm_i(instr)) & !1),
+        0b1100011 => {
+            let taken = match funct3 {
+                0 => rs1 == rs2,
+                1 => rs1 != rs2,
+                4 => (rs1 as i32) < (rs2 as i32),
+                5 => (rs1 as i32) >= (rs2 as i32),
+                6 => rs1 < rs2,
+                7 => rs1 >= rs2,
+                _ => return Outcome::Trap(IllegalInstruction),
+            };
+            if taken {
+                jump(0, pc, pc.wrapping_add(imm_b(instr)))
+            } else {
+                write_rd(0, 0, pc)
+            }
+        }
+        0b0000011 => {
+            let address = rs1.wrapping_add(imm_i(instr));
+            let num_bytes = match funct3 {
+                0 | 4 => 1,
+                1 | 5 => 2,
+                2 => 4,
+                _ => return Outcome::Trap(IllegalInstruction),
+            };
+            if !access_is_supported(isa, address, num_bytes) {
+                return Outcome::Trap(LoadAddressMisaligned);
+            }
+            let word = load_word(address & !3) >> ((address % 4) * 8);
+            let value = match funct3 {
+                0 if isa.signed_load => word as u8 as i8 as i32 as u32,
+                1 if isa.signed_load => word as u16 as i16 as i32 as u32,
+                2 => word,
+                4 => word as u8 as u32,
+                5 => word as u16 as u32,
+                _ => return Outcome::Trap(IllegalInstruction),
+            };
+            write_rd(rd, value, pc)
+        }
+        0b0100011 => {
+            let address = rs1.wrapping_add(imm_s(instr));
+            let num_bytes = match funct3 {
+                0 => 1,
+                1 => 2,
+                2 => 4,
+                _ => return Outcome::Trap(IllegalInstruction),
+            };
+            if !access_is_supported(isa, address, num_bytes) {
+                return Outcome::Trap(StoreOrAMOAddressMisaligned);
+            }
+            Outcome::Retired {
+                rd: 0,
+                value: 0,
+                next_pc: pc.wrapping_add(4),
+                store: Some(Store {
+                    address,
+                    num_bytes,
+                    value: rs2,
+                }),
+            }
+        }
+        0b0010011 => {
+            // shift amounts are encoded in the lower 5 bits of the immediate, and funct7
+            // selects the kind of shift
+            let funct7 = if funct3 == 1 || funct3 == 5 {
+                funct7
+            } else {
+                0
+            };
+            match alu(isa, funct3, funct7, rs1, imm_i(instr), false) {
+                Some(value) => write_rd(rd, value, pc),
+                None => Outcome::Trap(IllegalInstruction),
+            }
+        }
+        0b0110011 => {
+            let value = if funct7 == 1 {
+                mul_div(isa, funct3, rs1, rs2)
+            } else {
+                alu(isa, funct3, funct7, rs1, rs2, true)
+            };
+            match value {
+                Some(value) => write_rd(rd, value, pc),
+                None => Outcome::Trap(IllegalInstruction),
+            }
+        }
+        0b1110011 => {
+            let csr = field(instr, 20, 12);
+            match funct3 {
+                // only CSRRW into the oracle is supported, and our oracle always answers 0
+                1 if csr == ORACLE_CSR => write_rd(rd, 0, pc),
+                4 if isa.mops && funct7 & 0b1000001 == 0b1000001 => match mop(funct7, rs1, rs2) {
+                    Some(value) => write_rd(rd, value, pc),
+                    None => Outcome::Trap(IllegalInstruction),
+                },
+                _ => Outcome::Trap(IllegalInstruction),
+            }
+        }
+        _ => Outcome::Trap(IllegalInstruction),
+    }
+}
@@ -0,0 +1,66 @@
+use super::*;
+use crate::analysis::cost_model::{CostModel, CostTracer};
+use crate::delegations::sha256::{SHA256_ABI_NUM_MEM_ACCESSES, SHA256_ACCESS_ID, SHA256_IV};
+
+// SHA-256 of the message, with every block compressed by the guest.
+fn sha256_in_guest(message: &[u8]) -> ([u32; 8], CostTracer) {
+    let mut padded = message.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend_from_slice(&(message.len() as u64 * 8).to_be_bytes());
+
+    let mut tracer = CostTracer::create_from_initial_state(
+        &RiscV32State::<IMStandardIsaConfig>::initial(INITIAL_PC),
+        CostModel::default(),
+    );
+    let mut registry = DelegationRegistry::builtin();
+    let mut words = SHA256_IV.to_vec();
+    for block in padded.chunks(64) {
+        words.truncate(8);
+        words.extend(
+            block
+                .chunks(4)
+                .map(|word| u32::from_be_bytes(word.try_into().unwrap())),
+        );
+        words = run_delegation(SHA256_ACCESS_ID, &words, &mut tracer, &mut registry);
+    }
+
+    (words[..8].try_into().unwrap(), tracer)
+}
+
+// FIPS 180-2, appendix B.1 and B.2.
+#[test]
+fn test_nist_vectors() {
+    let (digest, _) = sha256_in_guest(b"abc");
+    assert_eq!(
+        digest,
+        [
+            0xba7816bf, 0x8f01cfea, 0x414140de, 0x5dae2223, 0xb00361a3, 0x96177a9c, 0xb410ff61,
+            0xf20015ad
+        ]
+    );
+
+    let (digest, _) = sha256_in_guest(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq");
+    assert_eq!(
+        digest,
+        [
+            0x248d6a61, 0xd20638b8, 0xe5c02693, 0x0c3e6039, 0xa33ce459, 0x64ff2167, 0xf6ecedd4,
+            0x19db06c1
+        ]
+    );
+}
+
+#[test]
+fn test_every_access_is_traced() {
+    let (_, tracer) = sha256_in_guest(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq");
+    let report = tracer.report(None);
+
+    let sha256 = &report.delegations["sha256"];
+    assert_eq!(sha256.invocations, 2);
+    assert_eq!(
+        sha256.batch_accesses,
+        2 * SHA256_ABI_NUM_MEM_ACCESSES as u64
+    );
+}
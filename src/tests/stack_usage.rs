@@ -0,0 +1,106 @@
+use crate::sim::{StackUsage, StackUsageConfig};
+
+fn function_name(pc: u32) -> Option<String> {
+    match pc {
+        0x100..0x200 => Some("main".to_owned()),
+        0x200..0x300 => Some("hash".to_owned()),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_high_water_mark() {
+    let mut stack_usage = StackUsage::new();
+
+    // the stack is not set up yet
+    assert!(!stack_usage.observe(0x0, 0, 0));
+    assert!(stack_usage.observe(0x100, 0x1000, 1));
+    assert!(stack_usage.observe(0x104, 0xff0, 2));
+    assert!(stack_usage.observe(0x200, 0xfc0, 3));
+    assert!(!stack_usage.observe(0x108, 0xff0, 4));
+    assert!(stack_usage.observe(0x400, 0xfb0, 5));
+    assert!(!stack_usage.observe(0x204, 0xfc0, 6));
+
+    let config = StackUsageConfig::new("stack.txt".into());
+    let report = stack_usage.report(
+        &config,
+        vec!["[unknown 0x00000400]".to_owned()],
+        function_name,
+    );
+
+    assert_eq!(report.stack_top, 0x1000);
+    assert_eq!(report.lowest_sp, Some(0xfb0));
+    assert_eq!(report.high_water_mark, 0x50);
+    assert_eq!(report.high_water_cycle, Some(5));
+
+    let functions = report
+        .functions
+        .iter()
+        .map(|function| (function.name.as_str(), function.max_depth))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        functions,
+        [
+            ("[unknown 0x00000400]", 0x50),
+            ("hash", 0x40),
+            ("main", 0x10)
+        ]
+    );
+
+    let mut config = config;
+    config.stack_top = Some(0x1010);
+    let report = stack_usage.report(&config, Vec::new(), function_name);
+    assert_eq!(report.high_water_mark, 0x60);
+    assert!(report
+        .to_string()
+        .contains("High-water mark: 96 bytes (sp 0x00000fb0 at cycle 5)"));
+}
+
+#[test]
+fn test_run_stops_at_stack_overflow() {
+    use super::{write_bin, write_symbols};
+    use crate::abstractions::non_determinism::ZeroedSource;
+    use crate::runner::run_simple_with_entry_point_and_non_determimism_source;
+    use crate::sim::{DiagnosticsConfig, ProfilerConfig, RunFault, SimulatorConfig, StackOverflow};
+
+    let program = [
+        0x00010137, // lui sp, 0x10
+        0x00010413, // mv s0, sp
+        0xff010113, // addi sp, sp, -16
+        0xff010113, // addi sp, sp, -16
+        0x0000006f, // j .
+    ];
+    let bin_path = write_bin("stack_overflow", &program);
+    let symbols_path = write_symbols("stack_overflow", &[("main", 0x00, 0x14)]);
+    let report_path =
+        std::env::temp_dir().join(format!("stack_overflow_{}.txt", std::process::id()));
+
+    let mut stack_usage = StackUsageConfig::new(report_path.clone());
+    stack_usage.limit = Some(0xffec);
+    let mut profiler_config = ProfilerConfig::new(report_path.clone());
+    profiler_config.outputs = Vec::new();
+    profiler_config.stack_usage = Some(stack_usage);
+    let mut diagnostics = DiagnosticsConfig::new(symbols_path.clone());
+    diagnostics.profiler_config = Some(profiler_config);
+    let config = SimulatorConfig::new(bin_path.clone(), 0, 16, Some(diagnostics));
+    let (_, fault, state) =
+        run_simple_with_entry_point_and_non_determimism_source(config, ZeroedSource);
+
+    let report = std::fs::read_to_string(&report_path).unwrap();
+    for path in [bin_path, symbols_path, report_path] {
+        std::fs::remove_file(path).unwrap();
+    }
+
+    assert_eq!(
+        fault,
+        Some(RunFault::StackOverflow(StackOverflow {
+            sp: 0xffe0,
+            limit: 0xffec,
+            cycle: 4,
+            stack: vec!["main".to_owned()],
+        }))
+    );
+    // stopped before the instruction at the overflow
+    assert_eq!(state.pc, 0x10);
+    assert!(report.contains("High-water mark: 32 bytes (sp 0x0000ffe0 at cycle 4)"));
+}
@@ -0,0 +1,482 @@
+use super::*;
+use crate::abstractions::tracer::{DynTracer, Tracer};
+use crate::cycle::state::{Mode, NON_DETERMINISM_CSR};
+use crate::cycle::status_registers::TrapReason;
+use std::cell::Cell;
+use std::rc::Rc;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Counts {
+    cycles: u32,
+    opcode_reads: u32,
+    rs1_reads: u32,
+    rs2_reads: u32,
+    rd_writes: u32,
+}
+
+#[derive(Debug, Default)]
+struct CountingTracer {
+    counts: Counts,
+}
+
+impl Tracer<IMStandardIsaConfig> for CountingTracer {
+    type AuxData = ();
+
+    fn create_from_initial_state(
+        _state: &RiscV32State<IMStandardIsaConfig>,
+        _aux_data: Self::AuxData,
+    ) -> Self {
+        Self::default()
+    }
+
+    fn at_cycle_start(&mut self, _current_state: &RiscV32State<IMStandardIsaConfig>) {
+        self.counts.cycles += 1;
+    }
+
+    fn trace_opcode_read(&mut self, _: u64, _: u32, _: u32, _: u32) {
+        self.counts.opcode_reads += 1;
+    }
+
+    fn trace_rs1_read(&mut self, _: u32, _: u32, _: u32, _: u32) {
+        self.counts.rs1_reads += 1;
+    }
+
+    fn trace_rs2_read(&mut self, _: u32, _: u32, _: u32, _: u32) {
+        self.counts.rs2_reads += 1;
+    }
+
+    fn trace_rd_write(&mut self, _: u32, _: u32, _: u32, _: u32, _: u32) {
+        self.counts.rd_writes += 1;
+    }
+}
+
+// Boxed tracers can not be inspected after the run, so this one reports through a shared cell
+#[derive(Debug)]
+struct SharedRdWriteCounter {
+    rd_writes: Rc<Cell<u32>>,
+}
+
+impl Tracer<IMStandardIsaConfig> for SharedRdWriteCounter {
+    type AuxData = Rc<Cell<u32>>;
+
+    fn create_from_initial_state(
+        _state: &RiscV32State<IMStandardIsaConfig>,
+        aux_data: Self::AuxData,
+    ) -> Self {
+        Self {
+            rd_writes: aux_data,
+        }
+    }
+
+    fn trace_rd_write(&mut self, _: u32, _: u32, _: u32, _: u32, _: u32) {
+        self.rd_writes.set(self.rd_writes.get() + 1);
+    }
+}
+
+const PROGRAM: &[&str] = &[
+    "addi x1, x0, 5",
+    "addi x2, x1, 3",
+    "add x3, x1, x2",
+    "sub x4, x3, x1",
+];
+
+fn run_program<TR: Tracer<IMStandardIsaConfig>>(aux_data: TR::AuxData) -> TR {
+    let mut state = RiscV32State::<IMStandardIsaConfig>::initial(INITIAL_PC);
+    let mut memory = VectorMemoryImpl::new_for_byte_size(64);
+    let mut empty_hash: HashMap<String, u32> = HashMap::new();
+    for (idx, instr) in PROGRAM.iter().enumerate() {
+        let address = INITIAL_PC + idx as u32 * (core::mem::size_of::<u32>() as u32);
+        let encoding = lib_rv32_asm::assemble_ir(instr, &mut empty_hash, address)
+            .unwrap()
+            .unwrap();
+        memory.populate(address, encoding);
+    }
+    let mut tracer = TR::create_from_initial_state(&state, aux_data);
+    let mut mmu = NoMMU::default();
+    for cycle in 0..PROGRAM.len() as u32 {
+        state.cycle(&mut memory, &mut tracer, &mut mmu, &mut ZeroedSource, cycle);
+    }
+    assert_eq!(state.registers[4], 8);
+
+    tracer
+}
+
+#[test]
+fn test_tuple_tracers_observe_the_same_events() {
+    let single = run_program::<CountingTracer>(()).counts;
+    assert_eq!(
+        single,
+        Counts {
+            cycles: 4,
+            opcode_reads: 4,
+            rs1_reads: 4,
+            rs2_reads: 4,
+            rd_writes: 4,
+        }
+    );
+
+    let (a, b) = run_program::<(CountingTracer, CountingTracer)>(((), ()));
+    assert_eq!(a.counts, single);
+    assert_eq!(b.counts, single);
+
+    let (a, (), c) = run_program::<(CountingTracer, (), CountingTracer)>(((), (), ()));
+    assert_eq!(a.counts, single);
+    assert_eq!(c.counts, single);
+}
+
+#[test]
+fn test_optional_tracer() {
+    let enabled = run_program::<Option<CountingTracer>>(Some(()));
+    assert_eq!(enabled.unwrap().counts.rd_writes, 4);
+
+    let disabled = run_program::<Option<CountingTracer>>(None);
+    assert!(disabled.is_none());
+}
+
+#[test]
+fn test_dynamic_tracer_list() {
+    let first = Rc::new(Cell::new(0));
+    let second = Rc::new(Cell::new(0));
+    let initial_state = RiscV32State::<IMStandardIsaConfig>::initial(INITIAL_PC);
+    let tracers: Vec<Box<dyn DynTracer<IMStandardIsaConfig>>> = vec![
+        Box::new(SharedRdWriteCounter::create_from_initial_state(
+            &initial_state,
+            first.clone(),
+        )),
+        Box::new(()),
+        Box::new(SharedRdWriteCounter::create_from_initial_state(
+            &initial_state,
+            second.clone(),
+        )),
+    ];
+
+    let tracers = run_program::<Vec<Box<dyn DynTracer<IMStandardIsaConfig>>>>(tracers);
+    assert_eq!(tracers.len(), 3);
+    assert_eq!(first.get(), 4);
+    assert_eq!(second.get(), 4);
+}
+
+#[test]
+fn test_borrowed_tracer() {
+    let mut owned = CountingTracer::default();
+    let (borrowed, other) = run_program::<(&mut CountingTracer, CountingTracer)>((&mut owned, ()));
+    assert_eq!(borrowed.counts, other.counts);
+    assert_eq!(owned.counts.rd_writes, 4);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Event {
+    CsrRead {
+        csr: u32,
+        value: u32,
+    },
+    CsrWrite {
+        csr: u32,
+        written_value: u32,
+    },
+    Trap {
+        trap: TrapReason,
+        pc: u32,
+    },
+    PrivilegeModeChange {
+        from: Mode,
+        to: Mode,
+    },
+    WaitForInterruptEntry {
+        pc: u32,
+    },
+    WaitForInterruptExit {
+        pc: u32,
+    },
+    NonDeterminismWrite {
+        value: u32,
+    },
+    AddressTranslation {
+        virt_address: u64,
+        phys_address: u64,
+        proc_cycle: u32,
+        cycle_timestamp: u32,
+    },
+    MmioRead {
+        address: u64,
+        value: u32,
+    },
+    MmioWrite {
+        address: u64,
+        value: u32,
+    },
+}
+
+#[derive(Debug, Default)]
+struct EventLog {
+    events: Vec<Event>,
+}
+
+impl Tracer<MachineWithTraps> for EventLog {
+    type AuxData = ();
+
+    fn create_from_initial_state(
+        _state: &RiscV32State<MachineWithTraps>,
+        _aux_data: Self::AuxData,
+    ) -> Self {
+        Self::default()
+    }
+
+    fn trace_csr_read(&mut self, csr_number: u32, read_value: u32, _: u32, _: u32) {
+        self.events.push(Event::CsrRead {
+            csr: csr_number,
+            value: read_value,
+        });
+    }
+
+    fn trace_csr_write(&mut self, csr_number: u32, _: u32, written_value: u32, _: u32, _: u32) {
+        self.events.push(Event::CsrWrite {
+            csr: csr_number,
+            written_value,
+        });
+    }
+
+    fn trace_trap(&mut self, trap: TrapReason, pc: u32, _instr: u32, _: u32, _: u32) {
+        self.events.push(Event::Trap { trap, pc });
+    }
+
+    fn trace_privilege_mode_change(&mut self, previous_mode: Mode, new_mode: Mode, _: u32, _: u32) {
+        self.events.push(Event::PrivilegeModeChange {
+            from: previous_mode,
+            to: new_mode,
+        });
+    }
+
+    fn trace_wait_for_interrupt_entry(&mut self, pc: u32, _: u32, _: u32) {
+        self.events.push(Event::WaitForInterruptEntry { pc });
+    }
+
+    fn trace_wait_for_interrupt_exit(&mut self, pc: u32, _: u32, _: u32) {
+        self.events.push(Event::WaitForInterruptExit { pc });
+    }
+
+    fn trace_non_determinism_write(&mut self, written_value: u32, _: u32, _: u32) {
+        self.events.push(Event::NonDeterminismWrite {
+            value: written_value,
+        });
+    }
+
+    fn trace_address_translation(
+        &mut self,
+        _satp_value: u32,
+        virtual_address: u64,
+        physical_address: u64,
+        proc_cycle: u32,
+        cycle_timestamp: u32,
+    ) {
+        self.events.push(Event::AddressTranslation {
+            virt_address: virtual_address,
+            phys_address: physical_address,
+            proc_cycle,
+            cycle_timestamp,
+        });
+    }
+
+    fn trace_mmio_read(&mut self, phys_address: u64, read_value: u32, _: u32, _: u32) {
+        self.events.push(Event::MmioRead {
+            address: phys_address,
+            value: read_value,
+        });
+    }
+
+    fn trace_mmio_write(&mut self, phys_address: u64, written_value: u32, _: u32, _: u32) {
+        self.events.push(Event::MmioWrite {
+            address: phys_address,
+            value: written_value,
+        });
+    }
+}
+
+const MTVEC: u32 = 0x305;
+const TRAP_HANDLER: u32 = 0x40;
+
+fn run_machine_program(
+    state: &mut RiscV32State<MachineWithTraps>,
+    program: &[u32],
+    num_cycles: u32,
+) -> Vec<Event> {
+    let mut memory = VectorMemoryImpl::new_for_byte_size(TRAP_HANDLER as usize + 4);
+    for (idx, insn) in program.iter().enumerate() {
+        memory.populate(
+            INITIAL_PC + idx as u32 * (core::mem::size_of::<u32>() as u32),
+            *insn,
+        );
+    }
+    let mut tracer = EventLog::create_from_initial_state(state, ());
+    let mut mmu = NoMMU::default();
+    for cycle in 0..num_cycles {
+        state.cycle(&mut memory, &mut tracer, &mut mmu, &mut ZeroedSource, cycle);
+    }
+
+    tracer.events
+}
+
+#[test]
+fn test_csr_and_trap_events() {
+    let mut state = RiscV32State::<MachineWithTraps>::initial(INITIAL_PC);
+    state.registers[2] = TRAP_HANDLER;
+    // csrrw x1, mtvec, x2 followed by an illegal instruction
+    let csrrw = (MTVEC << 20) | (2 << 15) | (0b001 << 12) | (1 << 7) | 0b1110011;
+    let events = run_machine_program(&mut state, &[csrrw, 0], 2);
+
+    assert_eq!(
+        events,
+        vec![
+            Event::CsrRead {
+                csr: MTVEC,
+                value: 0
+            },
+            Event::CsrWrite {
+                csr: MTVEC,
+                written_value: TRAP_HANDLER
+            },
+            Event::Trap {
+                trap: TrapReason::IllegalInstruction,
+                pc: 4
+            },
+        ]
+    );
+    assert_eq!(state.pc, TRAP_HANDLER);
+}
+
+#[test]
+fn test_privilege_mode_change_on_trap() {
+    let mut state = RiscV32State::<MachineWithTraps>::initial(INITIAL_PC);
+    state.machine_mode_trap_data.setup.tvec = TRAP_HANDLER;
+    state.extra_flags.set_mode(Mode::User);
+    let events = run_machine_program(&mut state, &[0], 1);
+
+    assert_eq!(
+        events,
+        vec![
+            Event::Trap {
+                trap: TrapReason::IllegalInstruction,
+                pc: INITIAL_PC
+            },
+            Event::PrivilegeModeChange {
+                from: Mode::User,
+                to: Mode::Machine
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_wait_for_interrupt_entry_and_exit() {
+    let mut state = RiscV32State::<MachineWithTraps>::initial(INITIAL_PC);
+    // wfi followed by a nop, nothing is pending so the hart keeps waiting after the wfi
+    let program = [0x10500073, 0x00000013];
+    let events = run_machine_program(&mut state, &program, 3);
+    assert_eq!(
+        events,
+        vec![Event::WaitForInterruptEntry { pc: INITIAL_PC }]
+    );
+    assert_eq!(state.pc, INITIAL_PC + 4);
+
+    state.machine_mode_trap_data.state.ie = 1 << 7;
+    state.machine_mode_trap_data.state.ip = 1 << 7;
+    let events = run_machine_program(&mut state, &program, 1);
+    assert_eq!(
+        events,
+        vec![Event::WaitForInterruptExit { pc: INITIAL_PC + 4 }]
+    );
+    assert_eq!(state.extra_flags.get_wait_for_interrupt(), 0);
+    assert_eq!(state.pc, INITIAL_PC + 8);
+}
+
+#[test]
+fn test_non_determinism_write() {
+    let mut state = RiscV32State::<MachineWithTraps>::initial(INITIAL_PC);
+    state.registers[1] = 5;
+    // a write, then a read that is not reported as a write
+    let program = [
+        csrrw(0, NON_DETERMINISM_CSR, 1),
+        csrrw(2, NON_DETERMINISM_CSR, 0),
+    ];
+    let events = run_machine_program(&mut state, &program, 2);
+
+    assert_eq!(events, vec![Event::NonDeterminismWrite { value: 5 }]);
+}
+
+#[test]
+fn test_address_translation() {
+    use crate::mmu::{EntryBit, MMUImplementation, SimpleMMU};
+
+    // a superpage at the root of the table in page 1, mapping the first 4 MiB to the next ones
+    let bits = [
+        EntryBit::Valid,
+        EntryBit::Read,
+        EntryBit::UserMode,
+        EntryBit::Accessed,
+    ];
+    let pte = bits.iter().fold(1 << 18, |pte, bit| pte | 1 << *bit as u32);
+    let mut memory = VectorMemoryImpl::new_for_byte_size(0x2000);
+    memory.populate(0x1000, pte);
+    let mmu = SimpleMMU {
+        sapt: 1 << 31 | 0x1,
+    };
+
+    let mut tracer = EventLog::default();
+    let mut trap = TrapReason::NoTrap;
+    let phys_address = MMUImplementation::<_, _, MachineWithTraps>::map_virtual_to_physical(
+        &mmu,
+        0x123,
+        Mode::User,
+        AccessType::MemLoad,
+        &mut memory,
+        &mut tracer,
+        3,
+        7,
+        &mut trap,
+    );
+
+    assert!(!trap.is_a_trap());
+    assert_eq!(phys_address, 0x400123);
+    assert_eq!(
+        tracer.events,
+        vec![Event::AddressTranslation {
+            virt_address: 0x123,
+            phys_address: 0x400123,
+            proc_cycle: 3,
+            cycle_timestamp: 7
+        }]
+    );
+}
+
+#[test]
+fn test_mmio_accesses() {
+    use crate::mmio::quasi_uart::{QuasiUART, QUASI_UART_ADDRESS};
+    use crate::mmio::{MMIOImplementation, MMIOSource};
+
+    let mut uart = QuasiUART::default();
+    uart.oracle.push_back(42);
+    let mut sources: Vec<Box<dyn MMIOSource>> = vec![Box::new(uart)];
+    let mut mmio = MMIOImplementation::<1>::construct(&mut sources);
+    let address = QUASI_UART_ADDRESS as u64;
+
+    let mut tracer = EventLog::default();
+    let mut trap = TrapReason::NoTrap;
+    let value = mmio
+        .read::<MachineWithTraps, _>(address, &mut tracer, 0, 0, &mut trap)
+        .unwrap();
+    mmio.write::<MachineWithTraps, _>(address, 0, &mut tracer, 1, 1, &mut trap)
+        .unwrap();
+    // accesses outside of any device are not reported
+    assert!(mmio
+        .read::<MachineWithTraps, _>(0x100, &mut tracer, 2, 2, &mut trap)
+        .is_err());
+
+    assert_eq!(value, 42);
+    assert_eq!(
+        tracer.events,
+        vec![
+            Event::MmioRead { address, value: 42 },
+            Event::MmioWrite { address, value: 0 },
+        ]
+    );
+}
@@ -0,0 +1,111 @@
+use num_bigint::BigUint;
+use rand::Rng;
+
+use super::*;
+use crate::delegations::u256::*;
+
+// Runs the operation on the ABI words and returns them after it.
+fn run(words: [u32; U256_ABI_NUM_MEM_ACCESSES]) -> [u32; U256_ABI_NUM_MEM_ACCESSES] {
+    run_delegation(
+        U256_ACCESS_ID,
+        &words,
+        &mut (),
+        &mut DelegationRegistry::builtin(),
+    )
+    .try_into()
+    .unwrap()
+}
+
+fn words(
+    a: &U256,
+    b: &U256,
+    modulus: &U256,
+    op: u32,
+    carry: u32,
+) -> [u32; U256_ABI_NUM_MEM_ACCESSES] {
+    let mut words = [0u32; U256_ABI_NUM_MEM_ACCESSES];
+    words[0..8].copy_from_slice(a);
+    words[8..16].copy_from_slice(b);
+    words[16..24].copy_from_slice(modulus);
+    words[24] = op;
+    words[25] = carry;
+
+    words
+}
+
+fn big(words: &[u32]) -> BigUint {
+    BigUint::from_slice(words)
+}
+
+fn random_u256<R: Rng>(rng: &mut R) -> U256 {
+    // small and saturated limbs make the carries interesting
+    core::array::from_fn(|_| match rng.gen_range(0..4) {
+        0 => 0,
+        1 => u32::MAX,
+        _ => rng.gen(),
+    })
+}
+
+#[test]
+fn test_random_operations_against_bigint() {
+    let mut rng = seeded_rng();
+    let two_256 = BigUint::from(1u32) << 256;
+
+    for _ in 0..256 {
+        let a = random_u256(&mut rng);
+        let b = random_u256(&mut rng);
+        let mut modulus = random_u256(&mut rng);
+        if modulus.iter().all(|word| *word == 0) {
+            modulus[0] = 1;
+        }
+        let carry = rng.gen_range(0..2);
+        let (big_a, big_b, big_modulus) = (big(&a), big(&b), big(&modulus));
+
+        let out = run(words(&a, &b, &modulus, U256_OP_ADD, carry));
+        let sum = &big_a + &big_b + carry;
+        assert_eq!(big(&out[0..8]), &sum % &two_256);
+        assert_eq!(out[25], (sum >= two_256) as u32);
+        assert_eq!(
+            out[8..25],
+            words(&a, &b, &modulus, U256_OP_ADD, carry)[8..25]
+        );
+
+        let out = run(words(&a, &b, &modulus, U256_OP_SUB, carry));
+        let subtrahend = &big_b + carry;
+        let borrow = big_a < subtrahend;
+        let difference = &big_a + if borrow { &two_256 } else { &BigUint::ZERO } - subtrahend;
+        assert_eq!(big(&out[0..8]), difference);
+        assert_eq!(out[25], borrow as u32);
+
+        let product = &big_a * &big_b;
+        let out = run(words(&a, &b, &modulus, U256_OP_MUL_WIDE, carry));
+        assert_eq!(big(&out[0..16]), product);
+        assert_eq!(out[25], carry);
+
+        let out = run(words(&a, &b, &modulus, U256_OP_MUL_LOW, carry));
+        assert_eq!(big(&out[0..8]), &product % &two_256);
+        assert_eq!(out[8..16], b);
+
+        let out = run(words(&a, &b, &modulus, U256_OP_MUL_HIGH, carry));
+        assert_eq!(big(&out[0..8]), &product >> 256);
+
+        let out = run(words(&a, &b, &modulus, U256_OP_MUL_MOD, carry));
+        assert_eq!(big(&out[0..8]), &product % &big_modulus);
+    }
+}
+
+#[test]
+fn test_mul_mod_edge_cases() {
+    let max = [u32::MAX; 8];
+    let mut one = [0u32; 8];
+    one[0] = 1;
+
+    // (2^256 - 1)^2 mod (2^256 - 1)
+    assert_eq!(mul_mod(&max, &max, &max), [0; 8]);
+    // anything mod 1
+    assert_eq!(mul_mod(&max, &max, &one), [0; 8]);
+    // the top bit of the remainder is set
+    let mut modulus = max;
+    modulus[0] -= 2;
+    assert_eq!(big(&mul_mod(&max, &one, &modulus)), BigUint::from(2u32));
+}
@@ -0,0 +1,196 @@
+use crate::abstractions::memory::{AccessType, MemorySource, VectorMemoryImpl};
+use crate::abstractions::uninit::UninitCheckingMemory;
+use crate::abstractions::{mem_read, mem_write};
+use crate::cycle::status_registers::TrapReason;
+use crate::cycle::IMStandardIsaConfig;
+use crate::runner::run_with_uninit_read_checks_for_config;
+use crate::sim::SimulatorConfig;
+
+use super::*;
+
+type Memory = UninitCheckingMemory<VectorMemoryImpl>;
+
+fn load(memory: &mut Memory, address: u64, num_bytes: u32) {
+    let mut trap = TrapReason::NoTrap;
+    let _ = mem_read::<_, _, IMStandardIsaConfig, true>(
+        memory,
+        &mut (),
+        address,
+        num_bytes,
+        AccessType::MemLoad,
+        0,
+        0,
+        &mut trap,
+    );
+    assert!(!trap.is_a_trap());
+}
+
+fn store(memory: &mut Memory, address: u64, num_bytes: u32) {
+    let mut trap = TrapReason::NoTrap;
+    mem_write::<_, _, IMStandardIsaConfig, true>(
+        memory,
+        &mut (),
+        address,
+        0,
+        num_bytes,
+        0,
+        0,
+        &mut trap,
+    );
+    assert!(!trap.is_a_trap());
+}
+
+fn fetch(memory: &mut Memory, pc: u64) {
+    let mut trap = TrapReason::NoTrap;
+    let _ = mem_read::<_, _, IMStandardIsaConfig, true>(
+        memory,
+        &mut (),
+        pc,
+        4,
+        AccessType::Instruction,
+        0,
+        0,
+        &mut trap,
+    );
+}
+
+#[test]
+fn test_uninit_reads() {
+    let mut memory = UninitCheckingMemory::new(VectorMemoryImpl::new_for_byte_size(1 << 12));
+    memory.mark_initialized(0x0, 0x100);
+    memory.allow(0x800..0x900);
+
+    fetch(&mut memory, 0x20);
+    store(&mut memory, 0x401, 1);
+    load(&mut memory, 0x401, 1);
+    // the store reads the whole word, but only initializes one byte of it
+    let reads = memory.take_uninit_reads();
+    assert!(reads.is_empty());
+
+    load(&mut memory, 0x400, 2);
+    load(&mut memory, 0x800, 4);
+    fetch(&mut memory, 0x200);
+
+    let reads = memory.take_uninit_reads();
+    let reads = reads
+        .iter()
+        .map(|read| (read.address, read.num_bytes, read.access_type, read.pc))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        reads,
+        [
+            (0x400, 2, AccessType::MemLoad, 0x20),
+            (0x200, 4, AccessType::Instruction, 0x200),
+        ]
+    );
+    assert!(memory.take_uninit_reads().is_empty());
+    assert_eq!(memory.reads().len(), 2);
+
+    // delegations write whole words
+    let mut trap = TrapReason::NoTrap;
+    memory.set(0x600, 0, AccessType::RegWrite, &mut trap);
+    load(&mut memory, 0x603, 1);
+    store(&mut memory, 0x700, 4);
+    load(&mut memory, 0x700, 4);
+    assert!(memory.take_uninit_reads().is_empty());
+}
+
+#[test]
+fn test_read_at_the_top_of_memory() {
+    let mut memory = UninitCheckingMemory::new(VectorMemoryImpl::new_for_byte_size(1 << 32));
+    memory.mark_initialized(0xffff_fffc, 3);
+
+    load(&mut memory, 0xffff_fffc, 4);
+    load(&mut memory, 0xffff_ffff, 1);
+
+    let reads = memory.take_uninit_reads();
+    let reads = reads
+        .iter()
+        .map(|read| (read.address, read.num_bytes))
+        .collect::<Vec<_>>();
+    assert_eq!(reads, [(0xffff_fffc, 4), (0xffff_ffff, 1)]);
+}
+
+#[test]
+fn test_runner_returns_reads_of_the_guest() {
+    // lui x1, 0x10; lw x2, 0(x1); j .
+    let bin_path = write_bin("uninit_reads", &[0x000100b7, 0x0000a103, 0x0000006f]);
+
+    let config = SimulatorConfig::new(bin_path.clone(), INITIAL_PC, 16, None);
+    let (_, reads, fault, _) = run_with_uninit_read_checks_for_config::<_, IMStandardIsaConfig>(
+        config,
+        ZeroedSource,
+        Vec::new(),
+    );
+    std::fs::remove_file(bin_path).unwrap();
+
+    assert_eq!(fault, None);
+    let reads = reads
+        .iter()
+        .map(|read| (read.address, read.num_bytes, read.pc))
+        .collect::<Vec<_>>();
+    assert_eq!(reads, [(0x10000, 4, INITIAL_PC + 4)]);
+}
+
+#[test]
+fn test_profiler_reads_are_not_reported() {
+    use crate::sim::{DiagnosticsConfig, ProfilerConfig, ProfilerOutput, ProfilerWeighting};
+
+    // `main` loads a word and calls `callee`, which loads two more. Unwinding `callee` walks into
+    // `main`'s frame record, which the guest never writes.
+    let program = [
+        0x00010137, // lui sp, 0x10
+        0x10010413, // addi s0, sp, 0x100
+        0x10002503, // lw a0, 0x100(zero)
+        0x014000ef, // jal ra, callee
+        0x0000006f, // j .
+        0x00000013, // nop
+        0x00000013, // nop
+        0x00000013, // nop
+        0xff010113, // callee: addi sp, sp, -16
+        0x00112623, // sw ra, 12(sp)
+        0x00812423, // sw s0, 8(sp)
+        0x01010413, // addi s0, sp, 16
+        0x10002583, // lw a1, 0x100(zero)
+        0x10402603, // lw a2, 0x104(zero)
+        0x00c12083, // lw ra, 12(sp)
+        0x00812403, // lw s0, 8(sp)
+        0x01010113, // addi sp, sp, 16
+        0x00008067, // ret
+    ];
+    let bin_path = write_bin("uninit_profiler", &program);
+    let symbols_path = write_symbols(
+        "uninit_profiler",
+        &[("main", 0x00, 0x20), ("callee", 0x20, 0x28)],
+    );
+    let folded_path =
+        std::env::temp_dir().join(format!("uninit_profiler_{}.folded", std::process::id()));
+
+    let mut profiler_config = ProfilerConfig::new(folded_path.clone());
+    profiler_config.outputs = vec![ProfilerOutput::FoldedStacks(folded_path.clone())];
+    profiler_config.weightings = vec![ProfilerWeighting::Cycles, ProfilerWeighting::MemoryLoads];
+    let mut diagnostics = DiagnosticsConfig::new(symbols_path.clone());
+    diagnostics.profiler_config = Some(profiler_config);
+    let config = SimulatorConfig::new(bin_path.clone(), 0, 64, Some(diagnostics));
+    let (_, reads, fault, _) = run_with_uninit_read_checks_for_config::<_, IMStandardIsaConfig>(
+        config,
+        ZeroedSource,
+        Vec::new(),
+    );
+
+    let mut outputs = vec![bin_path, symbols_path];
+    for weighting in [ProfilerWeighting::Cycles, ProfilerWeighting::MemoryLoads] {
+        let output = ProfilerOutput::FoldedStacks(folded_path.clone()).for_weighting(weighting);
+        outputs.push(output.path().clone());
+    }
+    for path in outputs {
+        std::fs::remove_file(path).unwrap();
+    }
+
+    assert_eq!(fault, None);
+    let reads = reads
+        .iter()
+        .map(|read| (read.address, read.pc))
+        .collect::<Vec<_>>();
+    assert_eq!(reads, [(0x100, 0x08), (0x100, 0x30), (0x104, 0x34)]);
+}
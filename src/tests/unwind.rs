@@ -0,0 +1,82 @@
+use addr2line::gimli::RunTimeEndian;
+
+use crate::sim::unwind::{CfiUnwinder, Unwound};
+
+// CFI for a single function at 0x100..0x120 with the usual prologue:
+//   0x100: addi sp, sp, -16
+//   0x104: sw ra, 12(sp)
+//   0x108: sw s0, 8(sp)
+#[rustfmt::skip]
+static DEBUG_FRAME: [u8; 40] = [
+    // CIE
+    0x0c, 0x00, 0x00, 0x00, // length
+    0xff, 0xff, 0xff, 0xff, // CIE id
+    0x01,                   // version
+    0x00,                   // augmentation
+    0x01,                   // code alignment factor
+    0x7c,                   // data alignment factor (-4)
+    0x01,                   // return address register (ra)
+    0x0c, 0x02, 0x00,       // DW_CFA_def_cfa: sp + 0
+    // FDE
+    0x14, 0x00, 0x00, 0x00, // length
+    0x00, 0x00, 0x00, 0x00, // CIE pointer
+    0x00, 0x01, 0x00, 0x00, // initial location
+    0x20, 0x00, 0x00, 0x00, // address range
+    0x44,                   // DW_CFA_advance_loc: 4
+    0x0e, 0x10,             // DW_CFA_def_cfa_offset: 16
+    0x81, 0x01,             // DW_CFA_offset: ra at cfa - 4
+    0x88, 0x02,             // DW_CFA_offset: s0 at cfa - 8
+    0x00,                   // DW_CFA_nop
+];
+
+fn unwinder() -> CfiUnwinder {
+    CfiUnwinder::from_sections(RunTimeEndian::Little, 4, 0, (0, &[]), &DEBUG_FRAME)
+}
+
+fn read_word(address: u32) -> Option<u32> {
+    match address {
+        0xffc => Some(0x3008),
+        0xff8 => Some(0x777),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_unwind_before_prologue() {
+    let mut registers = [0u32; 32];
+    registers[1] = 0x2004;
+    registers[2] = 0x1000;
+
+    let unwound = unwinder().unwind_frame(0x100, &mut registers, true, read_word);
+
+    assert_eq!(unwound, Unwound::Caller(0x2004));
+    assert_eq!(registers[2], 0x1000);
+}
+
+#[test]
+fn test_unwind_spilled_registers() {
+    let mut registers = [0u32; 32];
+    registers[1] = 0x2004;
+    registers[2] = 0xff0;
+    registers[8] = 0x1;
+
+    let unwound = unwinder().unwind_frame(0x110, &mut registers, true, read_word);
+
+    assert_eq!(unwound, Unwound::Caller(0x3008));
+    assert_eq!(registers[2], 0x1000);
+    assert_eq!(registers[8], 0x777);
+}
+
+#[test]
+fn test_unwind_without_return_address() {
+    let mut registers = [0u32; 32];
+    registers[1] = 0x2004;
+    registers[2] = 0x1000;
+
+    // only the frame being executed may keep the return address in `ra`
+    let unwound = unwinder().unwind_frame(0x100, &mut registers, false, read_word);
+    assert_eq!(unwound, Unwound::Outermost);
+
+    let unwound = unwinder().unwind_frame(0x200, &mut registers, true, read_word);
+    assert_eq!(unwound, Unwound::NoCfi);
+}
@@ -0,0 +1,98 @@
+use crate::abstractions::tracer::{BatchAccessPartialData, Tracer};
+use crate::cycle::IMStandardIsaConfig;
+use crate::sim::{WatchAction, WatchKind, Watcher, Watchpoint};
+
+fn tracer(watcher: &mut Watcher) -> &mut impl Tracer<IMStandardIsaConfig> {
+    watcher
+}
+
+#[test]
+fn test_parse_watchpoints() {
+    assert_eq!(
+        "change:0x1002+2:stop".parse::<Watchpoint>(),
+        Ok(Watchpoint {
+            range: 0x1002..0x1004,
+            kind: WatchKind::Change,
+            action: WatchAction::Stop,
+        })
+    );
+    assert_eq!("read:16..32".parse::<Watchpoint>().unwrap().range, 16..32);
+    assert_eq!(
+        "write:0x20".parse::<Watchpoint>().unwrap().range,
+        0x20..0x24
+    );
+    assert!("write:0x20:later".parse::<Watchpoint>().is_err());
+    assert!("poke:0x20".parse::<Watchpoint>().is_err());
+    assert!("read:0x20..0x20".parse::<Watchpoint>().is_err());
+    assert!("read:0xfffffffe+4".parse::<Watchpoint>().is_err());
+    assert!("write:0xfffffffe".parse::<Watchpoint>().is_err());
+}
+
+#[test]
+fn test_watch_hits() {
+    let mut watcher = Watcher::new(vec![
+        "change:0x1002+2".parse().unwrap(),
+        "read:0x2000+4".parse().unwrap(),
+        "write:0x30000".parse().unwrap(),
+    ]);
+    let memory = tracer(&mut watcher);
+
+    // only the low half of the word changes, which is not watched
+    memory.trace_ram_read_write(0x1000, 0x1111_0000, 0x1111_2222, 7, 7);
+    memory.trace_ram_read_write(0x1000, 0x1111_2222, 0x3333_2222, 8, 8);
+    memory.trace_ram_read(0x2000, 0x42, 9, 9);
+    memory.trace_ram_read_write(0x2000, 0x42, 0x43, 9, 9);
+    memory.trace_batch_memory_access(
+        0,
+        0x3,
+        &[
+            BatchAccessPartialData::Read { read_value: 1 },
+            BatchAccessPartialData::Write {
+                read_value: 2,
+                written_value: 3,
+            },
+        ],
+        10,
+        10,
+    );
+
+    let hits = watcher
+        .take_hits()
+        .into_iter()
+        .map(|hit| {
+            (
+                hit.watchpoint,
+                hit.address,
+                hit.old_value,
+                hit.new_value,
+                hit.cycle,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        hits,
+        [
+            (0, 0x1000, 0x1111_2222, Some(0x3333_2222), 8),
+            (1, 0x2000, 0x42, None, 9),
+        ]
+    );
+    assert!(watcher.take_hits().is_empty());
+
+    let mut watcher = Watcher::new(vec!["write:0x30004".parse().unwrap()]);
+    tracer(&mut watcher).trace_batch_memory_access(
+        0,
+        0x3,
+        &[
+            BatchAccessPartialData::Read { read_value: 1 },
+            BatchAccessPartialData::Write {
+                read_value: 2,
+                written_value: 3,
+            },
+        ],
+        10,
+        10,
+    );
+    let hits = watcher.take_hits();
+    assert_eq!(hits.len(), 1);
+    assert_eq!((hits[0].address, hits[0].new_value), (0x30004, Some(3)));
+}